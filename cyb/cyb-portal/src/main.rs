@@ -2,12 +2,34 @@ use leptos::prelude::*;
 use leptos_meta::*;
 use leptos_router::components::*;
 use leptos_router::path;
+use serde::Deserialize;
+
+/// cyb-services DB server on its default port, for when the app runs
+/// outside cyb-shell (`trunk serve` in a browser)
+const DEFAULT_SERVICES_URL: &str = "http://127.0.0.1:3031";
+/// Global cyb-shell sets to the DB server's configured URL
+const SERVICES_URL_GLOBAL: &str = "cybServicesUrl";
 
 fn main() {
     console_error_panic_hook::set_once();
     mount_to_body(App);
 }
 
+fn services_url() -> String {
+    web_sys::window()
+        .and_then(|window| js_sys::Reflect::get(&window, &SERVICES_URL_GLOBAL.into()).ok())
+        .and_then(|url| url.as_string())
+        .unwrap_or_else(|| DEFAULT_SERVICES_URL.to_string())
+}
+
+/// Route showing the particle `cid`.
+fn particle_href(cid: &str) -> String {
+    format!(
+        "/particles?cid={}",
+        String::from(js_sys::encode_uri_component(cid))
+    )
+}
+
 #[component]
 fn App() -> impl IntoView {
     provide_meta_context();
@@ -73,29 +95,153 @@ fn OraclePage() -> impl IntoView {
     }
 }
 
+#[derive(Clone, Deserialize)]
+struct SearchHit {
+    cid: String,
+    score: f64,
+    snippet: String,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    ok: bool,
+    #[serde(default)]
+    results: Vec<SearchHit>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+async fn fetch_search(query: String) -> Result<Vec<SearchHit>, String> {
+    let url = format!(
+        "{}/search?q={}",
+        services_url(),
+        String::from(js_sys::encode_uri_component(&query))
+    );
+    let response: SearchResponse = gloo_net::http::Request::get(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.ok {
+        Ok(response.results)
+    } else {
+        Err(response.error.unwrap_or_else(|| "search failed".into()))
+    }
+}
+
 #[component]
 fn SearchPage() -> impl IntoView {
     let params = leptos_router::hooks::use_params_map();
     let query = move || {
         params.with(|p| p.get("query").unwrap_or_default())
     };
+    let results = LocalResource::new(move || fetch_search(query()));
 
     view! {
         <div class="search">
             <h2 class="search-query">{query}</h2>
             <div class="search-results">
-                <p class="placeholder">"searching the knowledge graph..."</p>
+                <Suspense fallback=|| view! { <p class="placeholder">"searching the knowledge graph..."</p> }>
+                    {move || results.get().map(|res| match (*res).clone() {
+                        Ok(hits) if hits.is_empty() => view! {
+                            <p class="placeholder">"nothing found"</p>
+                        }.into_any(),
+                        Ok(hits) => hits.into_iter().map(|hit| view! {
+                            <div class="search-hit">
+                                <a class="search-hit-cid" href=particle_href(&hit.cid)>{hit.cid.clone()}</a>
+                                <span class="search-hit-score">{format!("{:.2}", hit.score)}</span>
+                                <p class="search-hit-snippet">{hit.snippet}</p>
+                            </div>
+                        }).collect_view().into_any(),
+                        Err(e) => view! { <p class="placeholder">{e}</p> }.into_any(),
+                    })}
+                </Suspense>
             </div>
         </div>
     }
 }
 
+#[derive(Clone, Deserialize)]
+struct Particle {
+    cid: String,
+    mime: String,
+    text: String,
+    size: i64,
+}
+
+#[derive(Deserialize)]
+struct ParticleResponse {
+    ok: bool,
+    #[serde(default)]
+    particle: Option<Particle>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+async fn fetch_particle(cid: String) -> Result<Particle, String> {
+    let url = format!(
+        "{}/particle?cid={}",
+        services_url(),
+        String::from(js_sys::encode_uri_component(&cid))
+    );
+    let response: ParticleResponse = gloo_net::http::Request::get(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.ok {
+        response.particle.ok_or_else(|| "particle not found".into())
+    } else {
+        Err(response.error.unwrap_or_else(|| "lookup failed".into()))
+    }
+}
+
+/// `/particles?cid=…` shows that particle; without a cid, the explorer.
 #[component]
 fn ParticlesPage() -> impl IntoView {
+    let query = leptos_router::hooks::use_query_map();
+    let cid = move || query.with(|q| q.get("cid").filter(|cid| !cid.is_empty()));
+    let particle = LocalResource::new(move || {
+        let cid = cid();
+        async move {
+            match cid {
+                Some(cid) => fetch_particle(cid).await.map(Some),
+                None => Ok(None),
+            }
+        }
+    });
+
     view! {
         <div class="page">
             <h2>"particles"</h2>
-            <p class="placeholder">"cyberlinks and particles explorer"</p>
+            <Suspense fallback=|| view! { <p class="placeholder">"loading the particle..."</p> }>
+                {move || particle.get().map(|res| match (*res).clone() {
+                    Ok(Some(particle)) => view! { <ParticleView particle=particle/> }.into_any(),
+                    Ok(None) => view! {
+                        <p class="placeholder">"cyberlinks and particles explorer"</p>
+                    }.into_any(),
+                    Err(e) => view! {
+                        <p class="placeholder">{format!("{}: {}", cid().unwrap_or_default(), e)}</p>
+                    }.into_any(),
+                })}
+            </Suspense>
+        </div>
+    }
+}
+
+#[component]
+fn ParticleView(particle: Particle) -> impl IntoView {
+    view! {
+        <div class="particle">
+            <p class="particle-cid">{particle.cid}</p>
+            <p class="particle-meta">{format!("{}, {} bytes", particle.mime, particle.size)}</p>
+            <pre class="particle-text">{particle.text}</pre>
         </div>
     }
 }
@@ -119,3 +265,24 @@ fn SettingsPage() -> impl IntoView {
         </div>
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use leptos::tachys::view::RenderHtml;
+
+    use super::*;
+
+    #[test]
+    fn test_particle_view_renders_cid() {
+        let particle = Particle {
+            cid: "QmParticle".into(),
+            mime: "text/plain".into(),
+            text: "hello cyber".into(),
+            size: 11,
+        };
+        let html = view! { <ParticleView particle=particle/> }.to_html();
+        assert!(html.contains("QmParticle"));
+        assert!(html.contains("text/plain, 11 bytes"));
+        assert!(html.contains("hello cyber"));
+    }
+}
@@ -0,0 +1,54 @@
+//! Headless cyb-services: runs the services from `~/.cyb/config.toml` and
+//! answers `cybctl` on the control socket until SIGINT/SIGTERM.
+
+#[cfg(unix)]
+#[tokio::main]
+async fn main() {
+    use cyb_services::control::{self, ControlServer};
+    use cyb_services::{CybConfig, CybServices, logging};
+    use std::sync::Arc;
+    use tokio::signal::unix::{SignalKind, signal};
+    use tracing::{error, info};
+
+    let loaded = CybConfig::load();
+    let config = loaded.as_ref().cloned().unwrap_or_default();
+    let logs = logging::init(&config.logging);
+    if let Err(e) = &loaded {
+        error!("{}, using defaults", e);
+    }
+
+    let server = match control::socket_path(&config.services.daemon) {
+        Ok(path) => ControlServer::bind(path).await,
+        Err(e) => Err(e.to_string()),
+    };
+    let server = match server {
+        Ok(server) => server,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Answer the control socket while services start (IPFS can take
+    // seconds); until then `Status` reports them as starting
+    let services = Arc::new(CybServices::with_config(config.services));
+    let control = tokio::spawn(server.serve(services.clone(), Some(logs.recent.clone())));
+    services.start().await;
+
+    let mut terminate = signal(SignalKind::terminate()).expect("SIGTERM handler");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+
+    info!("Shutting down");
+    control.abort();
+    let _ = control.await;
+    services.stop().await;
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("cyb-servicesd needs Unix domain sockets");
+    std::process::exit(1);
+}
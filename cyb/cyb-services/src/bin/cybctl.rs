@@ -0,0 +1,236 @@
+//! Control a running `cyb-servicesd`.
+
+#[cfg(unix)]
+const USAGE: &str = "usage: cybctl [--socket PATH] [--json] <command>
+
+commands:
+  status                                     service states
+  ipfs gc                                    garbage-collect unpinned blocks
+  mining start --seed S --address A --difficulty N [--threads N] [--timestamp T]
+  mining stop
+  mining status
+  db query [--mutable] <script>              run a CozoScript query
+  logs [-n LINES] [-t TARGET]                recent daemon log events";
+
+#[cfg(unix)]
+#[tokio::main]
+async fn main() {
+    use cyb_services::control::{self, Request};
+    use cyb_services::{CybConfig, ServiceStatus};
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cli = match parse_args(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("cybctl: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let socket = match cli.socket {
+        Some(path) => path,
+        None => match control::socket_path(&CybConfig::load_or_default().services.daemon) {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("cybctl: {}", e);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let result = match control::request(&socket, &cli.request).await {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("cybctl: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&result).unwrap_or_default());
+        return;
+    }
+    match &cli.request {
+        Request::Status => {
+            let statuses: Vec<ServiceStatus> = serde_json::from_value(result).unwrap_or_default();
+            println!("{:<12} {:<9} {:<10} {:>8}  ERROR", "SERVICE", "STATE", "HEALTH", "RESTARTS");
+            for s in statuses {
+                let health = match &s.health {
+                    Some(cyb_services::Health::Healthy) => "healthy",
+                    Some(cyb_services::Health::Degraded(_)) => "degraded",
+                    Some(cyb_services::Health::Unhealthy(_)) => "unhealthy",
+                    None => "-",
+                };
+                println!(
+                    "{:<12} {:<9} {:<10} {:>8}  {}",
+                    s.name,
+                    format!("{:?}", s.state).to_lowercase(),
+                    health,
+                    s.restarts,
+                    s.last_error.as_deref().unwrap_or("")
+                );
+            }
+        }
+        Request::Logs { .. } => {
+            for line in result.as_array().into_iter().flatten() {
+                let secs = line["timestamp"].as_u64().unwrap_or(0) / 1000;
+                println!(
+                    "{:02}:{:02}:{:02} {:>5} {}: {}",
+                    secs / 3600 % 24,
+                    secs / 60 % 60,
+                    secs % 60,
+                    line["level"].as_str().unwrap_or(""),
+                    line["target"].as_str().unwrap_or(""),
+                    line["message"].as_str().unwrap_or("")
+                );
+            }
+        }
+        _ => println!("{}", serde_json::to_string_pretty(&result).unwrap_or_default()),
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("cybctl needs Unix domain sockets");
+    std::process::exit(1);
+}
+
+#[cfg(unix)]
+struct Cli {
+    socket: Option<std::path::PathBuf>,
+    json: bool,
+    request: cyb_services::control::Request,
+}
+
+#[cfg(unix)]
+fn parse_args(args: &[String]) -> Result<Cli, String> {
+    use cyb_services::control::Request;
+
+    let mut socket = None;
+    let mut json = false;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--socket" => socket = Some(args.next().ok_or("--socket needs a path")?.into()),
+            "--json" => json = true,
+            "-h" | "--help" => return Err("help requested".to_string()),
+            _ => rest.push(arg.as_str()),
+        }
+    }
+
+    let request = match rest.as_slice() {
+        ["status"] => Request::Status,
+        ["ipfs", "gc"] => Request::IpfsGc,
+        ["mining", "start", flags @ ..] => {
+            let flags = Flags::parse(flags, &[])?;
+            Request::MiningStart {
+                seed: flags.value("--seed")?.ok_or("--seed is required")?,
+                address: flags.value("--address")?.ok_or("--address is required")?,
+                difficulty: flags.value("--difficulty")?.ok_or("--difficulty is required")?,
+                threads: flags.value("--threads")?,
+                timestamp: flags.value("--timestamp")?,
+            }
+        }
+        ["mining", "stop"] => Request::MiningStop,
+        ["mining", "status"] => Request::MiningStatus,
+        ["db", "query", flags @ ..] => {
+            let flags = Flags::parse(flags, &["--mutable"])?;
+            let [script] = flags.positional.as_slice() else {
+                return Err("db query takes one script argument".to_string());
+            };
+            Request::DbQuery {
+                script: script.to_string(),
+                mutable: flags.switches.contains(&"--mutable"),
+            }
+        }
+        ["logs", flags @ ..] => {
+            let flags = Flags::parse(flags, &[])?;
+            Request::Logs {
+                lines: flags.value("-n")?.or(flags.value("--lines")?).unwrap_or(50),
+                target: flags.value("-t")?.or(flags.value("--target")?),
+            }
+        }
+        [] => return Err("missing command".to_string()),
+        other => return Err(format!("unknown command: {}", other.join(" "))),
+    };
+
+    Ok(Cli { socket, json, request })
+}
+
+/// `--name value` pairs, bare switches and positional arguments of a subcommand.
+#[cfg(unix)]
+struct Flags<'a> {
+    named: Vec<(&'a str, &'a str)>,
+    switches: Vec<&'a str>,
+    positional: Vec<&'a str>,
+}
+
+#[cfg(unix)]
+impl<'a> Flags<'a> {
+    fn parse(args: &[&'a str], switches: &[&str]) -> Result<Self, String> {
+        let mut flags = Flags {
+            named: Vec::new(),
+            switches: Vec::new(),
+            positional: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            if switches.contains(&arg) {
+                flags.switches.push(arg);
+            } else if arg.starts_with('-') && arg.len() > 1 {
+                let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+                flags.named.push((arg, value));
+            } else {
+                flags.positional.push(arg);
+            }
+        }
+        Ok(flags)
+    }
+
+    fn value<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        self.named
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.parse().map_err(|_| format!("invalid value for {}: {}", name, v)))
+            .transpose()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use cyb_services::control::Request;
+
+    fn parse(line: &str) -> Result<Request, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        parse_args(&args).map(|cli| cli.request)
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(parse("status"), Ok(Request::Status));
+        assert_eq!(parse("--json ipfs gc"), Ok(Request::IpfsGc));
+        assert_eq!(
+            parse("mining start --seed ab --address bostrom1x --difficulty 12"),
+            Ok(Request::MiningStart {
+                seed: "ab".into(),
+                address: "bostrom1x".into(),
+                difficulty: 12,
+                threads: None,
+                timestamp: None,
+            })
+        );
+        assert_eq!(
+            parse("db query --mutable ::relations"),
+            Ok(Request::DbQuery { script: "::relations".into(), mutable: true })
+        );
+        assert_eq!(
+            parse("logs -n 5 -t cyb_services::ipfs"),
+            Ok(Request::Logs { lines: 5, target: Some("cyb_services::ipfs".into()) })
+        );
+        assert!(parse("mining start --seed ab").is_err());
+        assert!(parse("logs -n many").is_err());
+        assert!(parse("reboot").is_err());
+    }
+}
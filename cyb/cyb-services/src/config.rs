@@ -0,0 +1,541 @@
+use notify::{RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+/// Overrides the location of `~/.cyb/config.toml`.
+pub const CONFIG_ENV: &str = "CYB_CONFIG";
+
+/// Changes arriving within this window are coalesced into one reload.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Serialize)]
+pub enum ConfigError {
+    HomeDirNotFound,
+    Io(String),
+    /// TOML syntax error, unknown key or wrong type, with location
+    Parse(String),
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::HomeDirNotFound => write!(f, "home directory not found"),
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Parse(e) => write!(f, "{}", e),
+            ConfigError::Invalid(e) => write!(f, "invalid value: {}", e),
+        }
+    }
+}
+
+/// `~/.cyb/config.toml`. Every section and key is optional; missing ones
+/// take the defaults below, unknown ones are rejected.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CybConfig {
+    pub services: ServicesConfig,
+    pub worlds: WorldsConfig,
+    pub terminal: TerminalConfig,
+    pub hotkeys: HotkeysConfig,
+    pub logging: LoggingConfig,
+}
+
+/// Read at startup; changes need a restart.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServicesConfig {
+    pub ipfs: IpfsConfig,
+    pub db: DbConfig,
+    pub mining: MiningConfig,
+    pub indexer: IndexerConfig,
+    pub queue: QueueConfig,
+    pub daemon: DaemonConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IpfsConfig {
+    pub enabled: bool,
+    pub repo_path: String,
+    pub api_port: u16,
+}
+
+impl Default for IpfsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            repo_path: "~/.cyb/ipfs-repo".to_string(),
+            api_port: 5001,
+        }
+    }
+}
+
+impl IpfsConfig {
+    /// Base URL of the Kubo HTTP API, e.g. `http://127.0.0.1:5001/api/v0`.
+    pub fn api_url(&self) -> String {
+        format!("http://127.0.0.1:{}/api/v0", self.api_port)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DbConfig {
+    pub enabled: bool,
+    /// `mem`, `sqlite` or `rocksdb`. Unset: `CYB_DB_ENGINE`, then the preferred engine.
+    pub engine: Option<String>,
+    /// Directory holding `cozo.sqlite` / `cozo/`
+    pub data_dir: String,
+    pub port: u16,
+    /// Run on in-memory storage when the engine can't be opened. Nothing
+    /// written is kept, and `db-server` reports itself degraded.
+    pub memory_fallback: bool,
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            engine: None,
+            data_dir: "~/.cyb".to_string(),
+            port: 3031,
+            memory_fallback: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MiningConfig {
+    pub enabled: bool,
+}
+
+impl Default for MiningConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexerConfig {
+    /// Neuron to index. Unset: `CYB_NEURON`, otherwise the indexer stays off.
+    pub neuron: Option<String>,
+    pub lcd_url: String,
+    pub page_limit: u64,
+    pub poll_interval_secs: u64,
+}
+
+impl Default for IndexerConfig {
+    fn default() -> Self {
+        Self {
+            neuron: None,
+            lcd_url: "https://lcd.bostrom.cybernode.ai".to_string(),
+            page_limit: 50,
+            poll_interval_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueConfig {
+    pub enabled: bool,
+    pub batch_size: usize,
+    pub max_attempts: u32,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            batch_size: 8,
+            max_attempts: 5,
+        }
+    }
+}
+
+/// `cyb-servicesd` control socket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Unix socket `cybctl` and the shell connect to
+    pub socket: String,
+    /// Shell uses a running daemon instead of starting its own services
+    pub attach: bool,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            socket: "~/.cyb/services.sock".to_string(),
+            attach: true,
+        }
+    }
+}
+
+/// Applied the next time a world is entered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorldsConfig {
+    pub legacy_url: String,
+    /// Used instead of `legacy_url` in debug builds
+    pub legacy_dev_url: String,
+}
+
+impl Default for WorldsConfig {
+    fn default() -> Self {
+        Self {
+            legacy_url: "https://cyb.ai".to_string(),
+            legacy_dev_url: "https://localhost:3001".to_string(),
+        }
+    }
+}
+
+/// Hot-reloaded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TerminalConfig {
+    pub font_size: f32,
+    pub font: FontConfig,
+    /// Bundled theme or `~/.cyb/themes/<name>.toml`
+    pub theme: String,
+    /// Follow the system's light/dark preference instead of `theme`
+    pub adaptive_theme: Option<AdaptiveTheme>,
+    /// Run lone external commands on a pseudo-terminal, so interactive
+    /// programs (vim, htop, ssh) work; otherwise their output is captured
+    pub pty: bool,
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        Self {
+            font_size: 16.0,
+            font: FontConfig::default(),
+            theme: "default".to_string(),
+            adaptive_theme: None,
+            pty: true,
+        }
+    }
+}
+
+/// `[terminal.font]`, after rio's `[fonts]`. Hot-reloaded; a change reloads
+/// every font.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FontConfig {
+    /// Family for every style; sugarloaf's bundled Cascadia Code when unset
+    pub family: Option<String>,
+    /// Per-style faces, overriding `family` and the default weights
+    pub regular: Option<FontFace>,
+    pub bold: Option<FontFace>,
+    pub italic: Option<FontFace>,
+    pub bold_italic: Option<FontFace>,
+    /// OpenType features by tag (`ss01`, `zero`); `-tag` turns one off
+    pub features: Vec<String>,
+    /// Programming ligatures (`liga` and `calt`)
+    pub ligatures: bool,
+    pub hinting: bool,
+    /// Fallback families, tried in order for characters the main font lacks
+    pub extras: Vec<String>,
+    /// Families for code point ranges, e.g. Nerd Font icons
+    pub symbol_map: Vec<SymbolMap>,
+    /// Directories searched for font files besides the system ones
+    pub additional_dirs: Vec<String>,
+    /// Draw box-drawing, block and Powerline characters instead of taking
+    /// them from the font
+    pub drawable_chars: bool,
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        Self {
+            family: None,
+            regular: None,
+            bold: None,
+            italic: None,
+            bold_italic: None,
+            features: Vec::new(),
+            ligatures: true,
+            hinting: true,
+            extras: Vec::new(),
+            symbol_map: Vec::new(),
+            additional_dirs: Vec::new(),
+            drawable_chars: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FontFace {
+    pub family: Option<String>,
+    /// 100 (thin) to 900 (black)
+    pub weight: Option<u16>,
+}
+
+/// Code points `start..=end`, in hex as rio writes them (`E0A0`), drawn
+/// with `family`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolMap {
+    pub start: String,
+    pub end: String,
+    pub family: String,
+}
+
+/// Theme names for each system appearance, as in rio's `adaptive-theme`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdaptiveTheme {
+    pub dark: String,
+    pub light: String,
+}
+
+/// Global world-switch hotkeys in `global_hotkey` syntax (`super+Digit1`,
+/// `ctrl+shift+KeyT`, ...). Hot-reloaded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HotkeysConfig {
+    pub terminal: String,
+    pub portal: String,
+    pub legacy: String,
+    pub interface: String,
+}
+
+impl Default for HotkeysConfig {
+    fn default() -> Self {
+        Self {
+            terminal: "super+Digit1".to_string(),
+            portal: "super+Digit2".to_string(),
+            legacy: "super+Digit3".to_string(),
+            interface: "super+Digit4".to_string(),
+        }
+    }
+}
+
+/// Read at startup.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Directory for the daily-rotated `cyb.<date>.log` files
+    pub dir: String,
+    /// Rotated files kept before the oldest is deleted
+    pub max_files: usize,
+    /// `tracing` filter for the log file, e.g. `info` or `info,cyb_services::queue=debug`
+    pub level: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            dir: "~/.cyb/logs".to_string(),
+            max_files: 7,
+            level: "info".to_string(),
+        }
+    }
+}
+
+/// Expand a leading `~/` to the home directory.
+pub fn expand_path(path: &str) -> Result<PathBuf, ConfigError> {
+    match path.strip_prefix("~/").or_else(|| (path == "~").then_some("")) {
+        Some(rest) => Ok(dirs::home_dir().ok_or(ConfigError::HomeDirNotFound)?.join(rest)),
+        None => Ok(PathBuf::from(path)),
+    }
+}
+
+/// `$CYB_CONFIG`, or `~/.cyb/config.toml`.
+pub fn config_path() -> Result<PathBuf, ConfigError> {
+    match std::env::var(CONFIG_ENV) {
+        Ok(path) if !path.trim().is_empty() => expand_path(&path),
+        _ => expand_path("~/.cyb/config.toml"),
+    }
+}
+
+impl CybConfig {
+    pub fn from_toml_str(source: &str) -> Result<Self, ConfigError> {
+        let config: CybConfig = toml::from_str(source).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse `path`; a missing file yields the defaults.
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => Self::from_toml_str(&source).map_err(|e| match e {
+                ConfigError::Parse(msg) => ConfigError::Parse(format!("{}: {}", path.display(), msg)),
+                other => other,
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(ConfigError::Io(format!("{}: {}", path.display(), e))),
+        }
+    }
+
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_from(&config_path()?)
+    }
+
+    /// `load()`, reporting errors and falling back to the defaults.
+    pub fn load_or_default() -> Self {
+        Self::load().unwrap_or_else(|e| {
+            tracing::warn!("{}, using defaults", e);
+            Self::default()
+        })
+    }
+
+    /// Checks serde can't express: ranges and enumerated strings.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: String| Err(ConfigError::Invalid(msg));
+
+        if let Some(engine) = &self.services.db.engine
+            && !matches!(engine.as_str(), "mem" | "memory" | "sqlite" | "rocksdb")
+        {
+            return invalid(format!("services.db.engine = {:?} (expected mem, sqlite or rocksdb)", engine));
+        }
+        for (key, port) in [
+            ("services.ipfs.api_port", self.services.ipfs.api_port),
+            ("services.db.port", self.services.db.port),
+        ] {
+            if port == 0 {
+                return invalid(format!("{} must be non-zero", key));
+            }
+        }
+        if self.services.indexer.page_limit == 0 {
+            return invalid("services.indexer.page_limit must be at least 1".to_string());
+        }
+        if self.services.queue.batch_size == 0 || self.services.queue.max_attempts == 0 {
+            return invalid("services.queue.batch_size and max_attempts must be at least 1".to_string());
+        }
+        if self.logging.max_files == 0 {
+            return invalid("logging.max_files must be at least 1".to_string());
+        }
+        if !(4.0..=200.0).contains(&self.terminal.font_size) {
+            return invalid(format!("terminal.font_size = {} (expected 4..200)", self.terminal.font_size));
+        }
+        let font = &self.terminal.font;
+        for feature in &font.features {
+            let tag = feature.strip_prefix(['+', '-']).unwrap_or(feature);
+            if tag.len() != 4 || !tag.is_ascii() {
+                return invalid(format!("terminal.font.features: {:?} is not a four-letter OpenType tag", feature));
+            }
+        }
+        let faces = [&font.regular, &font.bold, &font.italic, &font.bold_italic];
+        for weight in faces.into_iter().flatten().filter_map(|face| face.weight) {
+            if !(100..=900).contains(&weight) {
+                return invalid(format!("terminal.font weight = {} (expected 100..900)", weight));
+            }
+        }
+        for map in &font.symbol_map {
+            let point = |hex: &str| u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+            match (point(&map.start), point(&map.end)) {
+                (Some(start), Some(end)) if start <= end => {}
+                _ => {
+                    return invalid(format!(
+                        "terminal.font.symbol_map: {}..{} is not a range of hex code points",
+                        map.start, map.end
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Watches the config file and delivers each reparsed version.
+pub struct ConfigWatcher {
+    _watcher: notify::RecommendedWatcher,
+    rx: Mutex<Receiver<Result<CybConfig, ConfigError>>>,
+}
+
+impl ConfigWatcher {
+    /// Watch `path`'s directory (editors often replace the file rather than
+    /// writing it in place) and reload when `path` changes.
+    pub fn spawn(path: PathBuf) -> Result<Self, ConfigError> {
+        let dir = path
+            .parent()
+            .map(Path::to_path_buf)
+            .ok_or_else(|| ConfigError::Io(format!("{} has no parent directory", path.display())))?;
+        std::fs::create_dir_all(&dir).map_err(|e| ConfigError::Io(e.to_string()))?;
+
+        let (event_tx, event_rx) = mpsc::channel::<()>();
+        let file_name = path.file_name().map(|n| n.to_os_string());
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event
+                && event.paths.iter().any(|p| p.file_name().map(|n| n.to_os_string()) == file_name)
+            {
+                let _ = event_tx.send(());
+            }
+        })
+        .map_err(|e| ConfigError::Io(e.to_string()))?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| ConfigError::Io(e.to_string()))?;
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            while event_rx.recv().is_ok() {
+                std::thread::sleep(RELOAD_DEBOUNCE);
+                while event_rx.try_recv().is_ok() {}
+                if tx.send(CybConfig::load_from(&path)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            _watcher: watcher,
+            rx: Mutex::new(rx),
+        })
+    }
+
+    /// Most recent reload since the last call, if any.
+    pub fn poll(&self) -> Option<Result<CybConfig, ConfigError>> {
+        self.rx.lock().unwrap().try_iter().last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_partial_config_and_reject_unknown_keys() {
+        let config = CybConfig::from_toml_str(
+            r#"
+            [services.db]
+            port = 4000
+
+            [terminal]
+            font_size = 13.5
+            adaptive_theme = { dark = "nord", light = "one-light" }
+
+            [terminal.font]
+            family = "JetBrains Mono"
+            features = ["ss01", "-calt"]
+            symbol_map = [{ start = "E0A0", end = "E0A3", family = "PowerlineSymbols" }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.services.db.port, 4000);
+        assert_eq!(config.services.ipfs.api_port, 5001);
+        assert_eq!(config.terminal.font_size, 13.5);
+        assert_eq!(config.terminal.theme, "default");
+        assert_eq!(config.terminal.adaptive_theme.unwrap().light, "one-light");
+        assert_eq!(config.terminal.font.family.as_deref(), Some("JetBrains Mono"));
+        assert!(config.terminal.font.ligatures);
+        assert_eq!(config.worlds, WorldsConfig::default());
+        assert_eq!(CybConfig::from_toml_str("").unwrap(), CybConfig::default());
+
+        let err = CybConfig::from_toml_str("[terminal]\nfont_sise = 12").unwrap_err();
+        assert!(matches!(&err, ConfigError::Parse(msg) if msg.contains("font_sise")), "{:?}", err);
+
+        let err = CybConfig::from_toml_str("[services.db]\nengine = \"postgres\"").unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid(_)));
+
+        let err = CybConfig::from_toml_str("[terminal.font]\nfeatures = [\"ligatures\"]").unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid(_)));
+    }
+}
@@ -0,0 +1,241 @@
+//! Control protocol between `cyb-servicesd` and its clients (`cybctl`, the shell).
+//!
+//! Newline-delimited JSON over a Unix socket: each request line is answered
+//! with one `{"Ok": <value>}` or `{"Err": "<message>"}` line.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tracing::{info, warn};
+
+use crate::CybServices;
+use crate::config::{ConfigError, DaemonConfig, expand_path};
+use crate::logging::RecentLogs;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    Status,
+    IpfsGc,
+    MiningStart {
+        seed: String,
+        address: String,
+        difficulty: u32,
+        threads: Option<u32>,
+        /// Unix seconds; the daemon's clock when unset
+        timestamp: Option<u64>,
+    },
+    MiningStop,
+    MiningStatus,
+    DbQuery {
+        script: String,
+        mutable: bool,
+    },
+    Logs {
+        lines: usize,
+        target: Option<String>,
+    },
+}
+
+pub type Response = Result<Value, String>;
+
+pub fn socket_path(config: &DaemonConfig) -> Result<PathBuf, ConfigError> {
+    expand_path(&config.socket)
+}
+
+/// Send one request to the daemon listening on `path`.
+pub async fn request(path: &Path, request: &Request) -> Response {
+    let stream = UnixStream::connect(path)
+        .await
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let (read, mut write) = stream.into_split();
+
+    let mut line = serde_json::to_string(request).map_err(|e| e.to_string())?;
+    line.push('\n');
+    write.write_all(line.as_bytes()).await.map_err(|e| e.to_string())?;
+
+    let mut reply = String::new();
+    BufReader::new(read)
+        .read_line(&mut reply)
+        .await
+        .map_err(|e| e.to_string())?;
+    if reply.is_empty() {
+        return Err("daemon closed the connection".to_string());
+    }
+    serde_json::from_str::<Response>(&reply).map_err(|e| format!("bad reply: {}", e))?
+}
+
+/// Listening control socket.
+pub struct ControlServer {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl ControlServer {
+    /// Bind `path`, replacing a stale socket file. Fails if another daemon
+    /// is already answering on it.
+    pub async fn bind(path: PathBuf) -> Result<Self, String> {
+        if path.exists() {
+            if UnixStream::connect(&path).await.is_ok() {
+                return Err(format!("another daemon is listening on {}", path.display()));
+            }
+            std::fs::remove_file(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+
+        let listener = UnixListener::bind(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        // Owner only: the socket can stop mining and write to the DB
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        info!(path = %path.display(), "Control socket listening");
+        Ok(Self { listener, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Accept connections until the task is dropped or aborted.
+    pub async fn serve(self, services: Arc<CybServices>, logs: Option<RecentLogs>) {
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(handle_connection(stream, services.clone(), logs.clone()));
+                }
+                Err(e) => warn!("accept failed: {}", e),
+            }
+        }
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn handle_connection(stream: UnixStream, services: Arc<CybServices>, logs: Option<RecentLogs>) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => dispatch(&services, logs.as_ref(), request).await,
+            Err(e) => Err(format!("bad request: {}", e)),
+        };
+        let mut reply = serde_json::to_string(&response).unwrap_or_else(|e| format!("{{\"Err\":\"{}\"}}", e));
+        reply.push('\n');
+        if write.write_all(reply.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn dispatch(services: &CybServices, logs: Option<&RecentLogs>, request: Request) -> Response {
+    match request {
+        Request::Status => serde_json::to_value(services.status()).map_err(|e| e.to_string()),
+
+        #[cfg(feature = "ipfs")]
+        Request::IpfsGc => crate::ipfs::repo_gc(&services.config.ipfs)
+            .await
+            .map(|removed| json!({ "removed": removed }))
+            .map_err(|e| format!("{:?}", e)),
+
+        #[cfg(feature = "mining")]
+        Request::MiningStart {
+            seed,
+            address,
+            difficulty,
+            threads,
+            timestamp,
+        } => {
+            let timestamp = timestamp.unwrap_or_else(|| {
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0)
+            });
+            let result = crate::mining::start_mining(&services.mining, seed, address, timestamp, difficulty, threads);
+            mining_result(result)
+        }
+        #[cfg(feature = "mining")]
+        Request::MiningStop => mining_result(crate::mining::stop_mining(&services.mining)),
+        #[cfg(feature = "mining")]
+        Request::MiningStatus => Ok(crate::mining::get_mining_status(&services.mining)),
+
+        #[cfg(feature = "db")]
+        Request::DbQuery { script, mutable } => {
+            let db = services.db.clone().ok_or("DB is disabled")?;
+            let out = tokio::task::spawn_blocking(move || {
+                let mut db = db.db.lock().unwrap();
+                crate::db::run_command(&mut db, &script, !mutable)
+            })
+            .await
+            .map_err(|e| e.to_string())??;
+            serde_json::from_str(&out).map_err(|e| e.to_string())
+        }
+
+        Request::Logs { lines, target } => {
+            let logs = logs.ok_or("logs are not captured by this daemon")?;
+            serde_json::to_value(logs.tail(lines, target.as_deref())).map_err(|e| e.to_string())
+        }
+
+        #[allow(unreachable_patterns)]
+        other => Err(format!("{:?} is not supported by this build", other)),
+    }
+}
+
+/// Mining calls report failure as `{"success": false, "error": ...}`.
+#[cfg(feature = "mining")]
+fn mining_result(result: Value) -> Response {
+    match result.get("success").and_then(Value::as_bool) {
+        Some(false) => Err(result
+            .get("error")
+            .and_then(Value::as_str)
+            .unwrap_or("mining failed")
+            .to_string()),
+        _ => Ok(result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServicesConfig;
+
+    #[tokio::test]
+    async fn test_request_round_trip() {
+        let mut config = ServicesConfig::default();
+        config.ipfs.enabled = false;
+        config.db.enabled = false;
+        config.mining.enabled = false;
+        let services = Arc::new(CybServices::with_config(config));
+
+        let path = std::env::temp_dir().join(format!("cyb-control-test-{}.sock", std::process::id()));
+        let server = ControlServer::bind(path.clone()).await.unwrap();
+        assert!(ControlServer::bind(path.clone()).await.is_err());
+        let task = tokio::spawn(server.serve(services, Some(RecentLogs::default())));
+
+        assert_eq!(request(&path, &Request::Status).await, Ok(json!([])));
+        assert_eq!(
+            request(&path, &Request::Logs { lines: 10, target: None }).await,
+            Ok(json!([]))
+        );
+        #[cfg(feature = "db")]
+        assert_eq!(
+            request(&path, &Request::DbQuery { script: "?[a] <- [[1]]".into(), mutable: false }).await,
+            Err("DB is disabled".to_string())
+        );
+
+        task.abort();
+        let _ = task.await;
+        assert!(!path.exists());
+    }
+}
@@ -1,35 +1,207 @@
 use cozo::*;
+use serde::Serialize;
 use serde_json::json;
 use std::fs;
+use std::path::Path;
 use std::sync::Mutex;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::info;
 
-fn get_cozo_path() -> Result<String, String> {
-    let home_dir = dirs::home_dir().ok_or("Cannot find home directory")?;
-    let cyb_dir = home_dir.join(".cyb");
+use crate::config::{DbConfig, expand_path};
 
-    if !cyb_dir.exists() {
-        fs::create_dir_all(&cyb_dir).map_err(|e| e.to_string())?;
+#[derive(Debug, Serialize)]
+pub enum DbError {
+    HomeDirNotFound,
+    UnknownEngine(String),
+    EngineUnavailable(String),
+    Other(String),
+}
+
+/// CozoDB storage backend. `Sqlite` and `Mem` are always compiled in;
+/// `RocksDb` needs the `db-rocksdb` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbEngine {
+    Mem,
+    Sqlite,
+    RocksDb,
+}
+
+impl DbEngine {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DbEngine::Mem => "mem",
+            DbEngine::Sqlite => "sqlite",
+            DbEngine::RocksDb => "rocksdb",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, DbError> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "mem" | "memory" => Ok(DbEngine::Mem),
+            "sqlite" => Ok(DbEngine::Sqlite),
+            "rocksdb" => Ok(DbEngine::RocksDb),
+            other => Err(DbError::UnknownEngine(other.to_string())),
+        }
     }
-    let cozo_dir = cyb_dir.join("cozo");
-    if !cozo_dir.exists() {
-        fs::create_dir_all(&cozo_dir).map_err(|e| e.to_string())?;
+
+    pub fn is_available(&self) -> bool {
+        match self {
+            DbEngine::Mem | DbEngine::Sqlite => true,
+            DbEngine::RocksDb => cfg!(feature = "db-rocksdb"),
+        }
+    }
+
+    /// RocksDB when compiled in, otherwise SQLite.
+    pub fn preferred() -> Self {
+        if DbEngine::RocksDb.is_available() {
+            DbEngine::RocksDb
+        } else {
+            DbEngine::Sqlite
+        }
     }
 
-    Ok(cozo_dir.to_string_lossy().into_owned())
+    /// Engine requested via `CYB_DB_ENGINE`, falling back to `preferred()`.
+    pub fn from_env() -> Result<Self, DbError> {
+        match std::env::var("CYB_DB_ENGINE") {
+            Ok(name) if !name.trim().is_empty() => DbEngine::from_name(&name),
+            _ => Ok(DbEngine::preferred()),
+        }
+    }
+}
+
+fn ensure_dir(dir: &Path) -> Result<(), DbError> {
+    if !dir.exists() {
+        fs::create_dir_all(dir).map_err(|e| DbError::Other(e.to_string()))?;
+    }
+    Ok(())
+}
+
+fn get_cozo_path(engine: DbEngine, data_dir: &Path) -> Result<String, DbError> {
+    let path = match engine {
+        DbEngine::Mem => return Ok(String::new()),
+        DbEngine::Sqlite => {
+            ensure_dir(data_dir)?;
+            data_dir.join("cozo.sqlite")
+        }
+        DbEngine::RocksDb => {
+            let cozo_dir = data_dir.join("cozo");
+            ensure_dir(&cozo_dir)?;
+            cozo_dir
+        }
+    };
+
+    Ok(path.to_string_lossy().into_owned())
 }
 
 pub struct DbState {
     pub db: Mutex<DbInstance>,
+    pub engine: DbEngine,
 }
 
 impl DbState {
-    pub fn new() -> Self {
-        let path = get_cozo_path().unwrap();
-        println!("[cyb-services] CozoDB path: {}", &path);
+    /// Open the engine selected by `CYB_DB_ENGINE` (or the preferred one).
+    pub fn new() -> Result<Self, DbError> {
+        Self::from_config(&DbConfig::default())
+    }
+
+    /// Open `config.engine` (or the `CYB_DB_ENGINE` / preferred one) under `config.data_dir`.
+    pub fn from_config(config: &DbConfig) -> Result<Self, DbError> {
+        let engine = match &config.engine {
+            Some(name) => DbEngine::from_name(name)?,
+            None => DbEngine::from_env()?,
+        };
+        let data_dir = expand_path(&config.data_dir).map_err(|_| DbError::HomeDirNotFound)?;
+        Self::open(engine, &data_dir)
+    }
 
-        DbState {
-            db: Mutex::new(DbInstance::new("rocksdb", &path, Default::default()).unwrap()),
+    /// Open `engine` in the default data directory.
+    pub fn with_engine(engine: DbEngine) -> Result<Self, DbError> {
+        let data_dir = expand_path(&DbConfig::default().data_dir).map_err(|_| DbError::HomeDirNotFound)?;
+        Self::open(engine, &data_dir)
+    }
+
+    fn open(engine: DbEngine, data_dir: &Path) -> Result<Self, DbError> {
+        if !engine.is_available() {
+            return Err(DbError::EngineUnavailable(format!(
+                "{} (rebuild with the db-rocksdb feature)",
+                engine.name()
+            )));
         }
+
+        let path = get_cozo_path(engine, data_dir)?;
+        info!(engine = engine.name(), path = %path, "CozoDB opened");
+
+        let db = DbInstance::new(engine.name(), &path, Default::default())
+            .map_err(|e| DbError::Other(e.to_string()))?;
+
+        Ok(DbState {
+            db: Mutex::new(db),
+            engine,
+        })
+    }
+
+    /// Create missing shared relations and the search indices over them.
+    pub fn prepare(&self) -> Result<(), String> {
+        let db = self.db.lock().unwrap();
+        crate::schema::ensure_schema(&db)?;
+        crate::search::ensure_indices(&db)
+    }
+}
+
+/// Change on a subscribed relation, as delivered by a Cozo callback.
+#[derive(Debug, Clone, Serialize)]
+pub struct RelationEvent {
+    pub relation: String,
+    pub op: &'static str,
+    pub headers: Vec<String>,
+    pub rows: Vec<serde_json::Value>,
+    pub old_rows: Vec<serde_json::Value>,
+}
+
+fn named_rows_json(rows: NamedRows) -> (Vec<String>, Vec<serde_json::Value>) {
+    let headers = rows.headers.clone();
+    let rows = match rows.into_json().get_mut("rows").map(serde_json::Value::take) {
+        Some(serde_json::Value::Array(rows)) => rows,
+        _ => Vec::new(),
+    };
+    (headers, rows)
+}
+
+impl DbState {
+    /// Register a change callback on `relation` and forward its events to `tx`
+    /// until `unsubscribe` is called or the receiver is dropped. Returns the callback id.
+    pub fn subscribe(&self, relation: &str, tx: UnboundedSender<RelationEvent>) -> u32 {
+        let (id, rx) = self.db.lock().unwrap().register_callback(relation, None);
+        let relation = relation.to_string();
+
+        // Cozo callbacks deliver on a blocking channel, so bridge on a plain thread.
+        // The loop ends when the callback is unregistered (sender dropped) or `tx` closes.
+        std::thread::spawn(move || {
+            while let Ok((op, new_rows, old_rows)) = rx.recv() {
+                let op = match op {
+                    CallbackOp::Put => "put",
+                    CallbackOp::Rm => "rm",
+                };
+                let (headers, rows) = named_rows_json(new_rows);
+                let (_, old_rows) = named_rows_json(old_rows);
+                let event = RelationEvent {
+                    relation: relation.clone(),
+                    op,
+                    headers,
+                    rows,
+                    old_rows,
+                };
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+
+        id
+    }
+
+    pub fn unsubscribe(&self, id: u32) {
+        self.db.lock().unwrap().unregister_callback(id);
     }
 }
 
@@ -54,3 +226,60 @@ pub fn run_command(db: &mut DbInstance, script: &str, immutable: bool) -> Result
 
     Ok(serialized_result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_from_name() {
+        assert_eq!(DbEngine::from_name("sqlite").unwrap(), DbEngine::Sqlite);
+        assert_eq!(DbEngine::from_name(" Memory ").unwrap(), DbEngine::Mem);
+        assert_eq!(DbEngine::from_name("rocksdb").unwrap(), DbEngine::RocksDb);
+        assert!(matches!(
+            DbEngine::from_name("sled"),
+            Err(DbError::UnknownEngine(_))
+        ));
+    }
+
+    #[test]
+    fn test_mem_engine_runs_script() {
+        let state = DbState::with_engine(DbEngine::Mem).unwrap();
+        let mut db = state.db.lock().unwrap();
+        let out = run_command(&mut db, "?[a] <- [[1]]", true).unwrap();
+        assert!(out.contains("\"ok\":true"));
+    }
+
+    #[test]
+    fn test_subscribe_receives_puts() {
+        let state = DbState::with_engine(DbEngine::Mem).unwrap();
+        {
+            let mut db = state.db.lock().unwrap();
+            run_command(&mut db, ":create kv { k: String => v: Int }", false).unwrap();
+        }
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let id = state.subscribe("kv", tx);
+        {
+            let mut db = state.db.lock().unwrap();
+            run_command(&mut db, "?[k, v] <- [['a', 1]] :put kv { k => v }", false).unwrap();
+        }
+
+        let event = rx.blocking_recv().unwrap();
+        assert_eq!(event.relation, "kv");
+        assert_eq!(event.op, "put");
+        assert_eq!(event.headers, vec!["k".to_string(), "v".to_string()]);
+        assert_eq!(event.rows.len(), 1);
+
+        state.unsubscribe(id);
+    }
+
+    #[cfg(not(feature = "db-rocksdb"))]
+    #[test]
+    fn test_rocksdb_unavailable_is_reported() {
+        assert!(matches!(
+            DbState::with_engine(DbEngine::RocksDb),
+            Err(DbError::EngineUnavailable(_))
+        ));
+    }
+}
@@ -0,0 +1,610 @@
+use cozo::{DataValue, DbInstance, ScriptMutability};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, instrument, warn};
+
+use crate::db::DbState;
+
+pub const DEFAULT_LCD_URL: &str = "https://lcd.bostrom.cybernode.ai";
+
+const CYBERLINK_MSG_TYPE: &str = "cyber.graph.v1beta1.MsgCyberlink";
+
+/// `EntryType.transactions` in the web app's sync_status
+const ENTRY_TYPE_TRANSACTIONS: i64 = 1;
+/// `SyncQueueJobType.particle` / `QueuePriority.LOW`
+const JOB_TYPE_PARTICLE: i64 = 0;
+const PRIORITY_LOW: f64 = 0.1;
+
+/// Tx event streams paged for a neuron, keyed by their cursor name in sync_status meta.
+const EVENT_STREAMS: &[(&str, &str)] = &[
+    ("sent_offset", "message.sender"),
+    ("received_offset", "transfer.recipient"),
+];
+
+#[derive(Debug, Serialize)]
+pub enum IndexerError {
+    Http(String),
+    Decode(String),
+    Db(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexerConfig {
+    pub lcd_url: String,
+    pub neuron: String,
+    pub page_limit: u64,
+    pub poll_interval: Duration,
+}
+
+impl IndexerConfig {
+    pub fn new(neuron: impl Into<String>) -> Self {
+        Self {
+            lcd_url: DEFAULT_LCD_URL.to_string(),
+            neuron: neuron.into(),
+            page_limit: 50,
+            poll_interval: Duration::from_secs(30),
+        }
+    }
+
+    /// `[services.indexer]` settings for `neuron`.
+    pub fn from_config(config: &crate::config::IndexerConfig, neuron: impl Into<String>) -> Self {
+        Self {
+            lcd_url: config.lcd_url.clone(),
+            neuron: neuron.into(),
+            page_limit: config.page_limit,
+            poll_interval: Duration::from_secs(config.poll_interval_secs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncProgress {
+    pub transactions: usize,
+    pub links: usize,
+    pub particles_queued: usize,
+}
+
+// --- LCD response (cosmos/tx/v1beta1/txs) ---
+
+#[derive(Debug, Deserialize)]
+struct TxsResponse {
+    #[serde(default)]
+    tx_responses: Vec<TxResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TxResponse {
+    height: String,
+    txhash: String,
+    #[serde(default)]
+    code: u32,
+    timestamp: String,
+    tx: TxEnvelope,
+}
+
+#[derive(Debug, Deserialize)]
+struct TxEnvelope {
+    body: TxBody,
+}
+
+#[derive(Debug, Deserialize)]
+struct TxBody {
+    #[serde(default)]
+    messages: Vec<serde_json::Value>,
+    #[serde(default)]
+    memo: String,
+}
+
+// --- Rows written to Cozo ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionRow {
+    pub hash: String,
+    pub index: i64,
+    pub neuron: String,
+    pub tx_type: String,
+    pub block_height: i64,
+    pub success: bool,
+    pub timestamp: i64,
+    pub value: serde_json::Value,
+    pub memo: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkRow {
+    pub from: String,
+    pub to: String,
+    pub neuron: String,
+    pub timestamp: i64,
+    pub transaction_hash: String,
+}
+
+/// Split a tx into one transaction row per message (like the indexer's
+/// `messages_by_address`) plus the cyberlinks it created.
+fn map_tx_response(neuron: &str, tx: &TxResponse) -> Result<(Vec<TransactionRow>, Vec<LinkRow>), IndexerError> {
+    let timestamp = chrono::DateTime::parse_from_rfc3339(&tx.timestamp)
+        .map_err(|e| IndexerError::Decode(format!("timestamp {}: {}", tx.timestamp, e)))?
+        .timestamp_millis();
+    let block_height = tx
+        .height
+        .parse::<i64>()
+        .map_err(|e| IndexerError::Decode(format!("height {}: {}", tx.height, e)))?;
+
+    let mut transactions = Vec::new();
+    let mut links = Vec::new();
+
+    for (index, message) in tx.tx.body.messages.iter().enumerate() {
+        let mut value = message.clone();
+        let tx_type = value
+            .as_object_mut()
+            .and_then(|obj| obj.remove("@type"))
+            .and_then(|t| t.as_str().map(|s| s.trim_start_matches('/').to_string()))
+            .unwrap_or_default();
+
+        if tx_type == CYBERLINK_MSG_TYPE && tx.code == 0 {
+            let link_neuron = value["neuron"].as_str().unwrap_or(neuron).to_string();
+            for link in value["links"].as_array().into_iter().flatten() {
+                let (Some(from), Some(to)) = (link["from"].as_str(), link["to"].as_str()) else {
+                    continue;
+                };
+                links.push(LinkRow {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    neuron: link_neuron.clone(),
+                    timestamp,
+                    transaction_hash: tx.txhash.clone(),
+                });
+            }
+        }
+
+        transactions.push(TransactionRow {
+            hash: tx.txhash.clone(),
+            index: index as i64,
+            neuron: neuron.to_string(),
+            tx_type,
+            block_height,
+            success: tx.code == 0,
+            timestamp,
+            value,
+            memo: tx.tx.body.memo.clone(),
+        });
+    }
+
+    Ok((transactions, links))
+}
+
+// --- Cozo writes ---
+
+fn run(db: &DbInstance, script: &str, params: BTreeMap<String, DataValue>) -> Result<cozo::NamedRows, IndexerError> {
+    db.run_script(script, params, ScriptMutability::Mutable)
+        .map_err(|e| IndexerError::Db(e.to_string()))
+}
+
+fn put_transactions(db: &DbInstance, rows: &[TransactionRow]) -> Result<(), IndexerError> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let rows = rows
+        .iter()
+        .map(|r| {
+            DataValue::List(vec![
+                DataValue::from(r.hash.as_str()),
+                DataValue::from(r.index),
+                DataValue::from(r.neuron.as_str()),
+                DataValue::from(r.tx_type.as_str()),
+                DataValue::from(r.block_height),
+                DataValue::Bool(r.success),
+                DataValue::from(r.timestamp),
+                DataValue::from(r.value.to_string().as_str()),
+                DataValue::from(r.memo.as_str()),
+            ])
+        })
+        .collect();
+
+    let mut params = BTreeMap::new();
+    params.insert("rows".to_string(), DataValue::List(rows));
+
+    run(
+        db,
+        "input[hash, index, neuron, type, block_height, success, timestamp, value_str, memo] <- $rows
+        ?[hash, index, neuron, type, block_height, success, timestamp, value, memo] :=
+            input[hash, index, neuron, type, block_height, success, timestamp, value_str, memo],
+            value = parse_json(value_str)
+        :put transaction { hash, index, neuron, type => block_height, success, timestamp, value, memo }",
+        params,
+    )?;
+    Ok(())
+}
+
+/// How many of `rows` aren't stored yet. A tx can come back on more than one
+/// event stream (a self-transfer is both sent and received), and only its
+/// first sighting is unread.
+fn count_new_transactions(db: &DbInstance, neuron: &str, rows: &[TransactionRow]) -> Result<usize, IndexerError> {
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let mut params = BTreeMap::new();
+    params.insert(
+        "keys".to_string(),
+        DataValue::List(
+            rows.iter()
+                .map(|r| DataValue::List(vec![DataValue::from(r.hash.as_str()), DataValue::from(r.index)]))
+                .collect(),
+        ),
+    );
+    params.insert("neuron".to_string(), DataValue::from(neuron));
+
+    let new = db
+        .run_script(
+            "input[hash, index] <- $keys
+            ?[hash, index] := input[hash, index], neuron = $neuron, not *transaction{hash, index, neuron}",
+            params,
+            ScriptMutability::Immutable,
+        )
+        .map_err(|e| IndexerError::Db(e.to_string()))?;
+    Ok(new.rows.len())
+}
+
+fn put_links(db: &DbInstance, rows: &[LinkRow]) -> Result<(), IndexerError> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let rows = rows
+        .iter()
+        .map(|r| {
+            DataValue::List(vec![
+                DataValue::from(r.from.as_str()),
+                DataValue::from(r.to.as_str()),
+                DataValue::from(r.neuron.as_str()),
+                DataValue::from(r.timestamp),
+                DataValue::from(r.transaction_hash.as_str()),
+            ])
+        })
+        .collect();
+
+    let mut params = BTreeMap::new();
+    params.insert("rows".to_string(), DataValue::List(rows));
+
+    run(
+        db,
+        "?[from, to, neuron, timestamp, transaction_hash] <- $rows
+        :put link { from, to, neuron => timestamp, transaction_hash }",
+        params,
+    )?;
+    Ok(())
+}
+
+/// Queue particle resolution for linked CIDs that aren't queued yet.
+fn enqueue_particles(db: &DbInstance, links: &[LinkRow]) -> Result<usize, IndexerError> {
+    let mut cids: Vec<&str> = links.iter().flat_map(|l| [l.from.as_str(), l.to.as_str()]).collect();
+    cids.sort_unstable();
+    cids.dedup();
+    if cids.is_empty() {
+        return Ok(0);
+    }
+
+    let mut params = BTreeMap::new();
+    params.insert(
+        "cids".to_string(),
+        DataValue::List(cids.iter().map(|c| DataValue::List(vec![DataValue::from(*c)])).collect()),
+    );
+    params.insert("job_type".to_string(), DataValue::from(JOB_TYPE_PARTICLE));
+    params.insert("priority".to_string(), DataValue::from(PRIORITY_LOW));
+
+    let missing = db
+        .run_script(
+            "input[id] <- $cids
+            ?[id] := input[id], job_type = $job_type, not *sync_queue{id, job_type}",
+            params.clone(),
+            ScriptMutability::Immutable,
+        )
+        .map_err(|e| IndexerError::Db(e.to_string()))?;
+    if missing.rows.is_empty() {
+        return Ok(0);
+    }
+
+    params.insert("cids".to_string(), DataValue::List(missing.rows.iter().cloned().map(DataValue::List).collect()));
+    run(
+        db,
+        "input[id] <- $cids
+        ?[id, job_type, data, status, priority] :=
+            input[id], job_type = $job_type, data = '', status = 0, priority = $priority
+        :put sync_queue { id, job_type => data, status, priority }",
+        params,
+    )?;
+    Ok(missing.rows.len())
+}
+
+/// Persisted sync state for a neuron (its own row in sync_status).
+#[derive(Debug, Clone)]
+struct SyncStatus {
+    timestamp_update: i64,
+    timestamp_read: i64,
+    unread_count: i64,
+    meta: serde_json::Value,
+}
+
+impl Default for SyncStatus {
+    fn default() -> Self {
+        Self {
+            timestamp_update: 0,
+            timestamp_read: 0,
+            unread_count: 0,
+            meta: serde_json::json!({}),
+        }
+    }
+}
+
+fn load_sync_status(db: &DbInstance, neuron: &str) -> Result<Option<SyncStatus>, IndexerError> {
+    let mut params = BTreeMap::new();
+    params.insert("neuron".to_string(), DataValue::from(neuron));
+
+    let rows = db
+        .run_script(
+            "?[timestamp_update, timestamp_read, unread_count, meta_str] :=
+                *sync_status{owner_id, id, timestamp_update, timestamp_read, unread_count, meta},
+                owner_id = $neuron, id = $neuron,
+                meta_str = dump_json(meta)",
+            params,
+            ScriptMutability::Immutable,
+        )
+        .map_err(|e| IndexerError::Db(e.to_string()))?;
+
+    Ok(rows.rows.first().map(|row| {
+        let int = |i: usize| row.get(i).and_then(|v| v.get_int()).unwrap_or(0);
+        let meta = row
+            .get(3)
+            .and_then(|v| v.get_str())
+            .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+            .filter(|m| m.is_object())
+            .unwrap_or_else(|| serde_json::json!({}));
+        SyncStatus {
+            timestamp_update: int(0),
+            timestamp_read: int(1),
+            unread_count: int(2),
+            meta,
+        }
+    }))
+}
+
+fn put_sync_status(db: &DbInstance, neuron: &str, status: &SyncStatus) -> Result<(), IndexerError> {
+    let mut params = BTreeMap::new();
+    params.insert("neuron".to_string(), DataValue::from(neuron));
+    params.insert("entry_type".to_string(), DataValue::from(ENTRY_TYPE_TRANSACTIONS));
+    params.insert("timestamp_update".to_string(), DataValue::from(status.timestamp_update));
+    params.insert("timestamp_read".to_string(), DataValue::from(status.timestamp_read));
+    params.insert("unread_count".to_string(), DataValue::from(status.unread_count));
+    params.insert("meta".to_string(), DataValue::from(status.meta.to_string().as_str()));
+
+    run(
+        db,
+        "?[owner_id, id, entry_type, disabled, timestamp_update, timestamp_read, unread_count, meta] :=
+            owner_id = $neuron, id = $neuron, entry_type = $entry_type, disabled = false,
+            timestamp_update = $timestamp_update, timestamp_read = $timestamp_read,
+            unread_count = $unread_count, meta = parse_json($meta)
+        :put sync_status { owner_id, id => entry_type, disabled, timestamp_update, timestamp_read, unread_count, meta }",
+        params,
+    )?;
+    Ok(())
+}
+
+// --- Indexer ---
+
+/// Pages a neuron's transactions from an LCD endpoint into the `transaction`
+/// and `link` relations. Paging cursors live in the neuron's sync_status meta,
+/// so a restart resumes where the last page ended.
+pub struct ChainIndexer {
+    client: reqwest::Client,
+    config: IndexerConfig,
+    db: Arc<DbState>,
+}
+
+impl ChainIndexer {
+    pub fn new(db: Arc<DbState>, config: IndexerConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_default();
+
+        Self { client, config, db }
+    }
+
+    async fn fetch_page(&self, event: &str, offset: u64) -> Result<Vec<TxResponse>, IndexerError> {
+        let url = format!("{}/cosmos/tx/v1beta1/txs", self.config.lcd_url.trim_end_matches('/'));
+        let response = self
+            .client
+            .get(&url)
+            .query(&[
+                ("events", format!("{}='{}'", event, self.config.neuron)),
+                ("pagination.offset", offset.to_string()),
+                ("pagination.limit", self.config.page_limit.to_string()),
+                ("order_by", "ORDER_BY_ASC".to_string()),
+            ])
+            .send()
+            .await
+            .map_err(|e| IndexerError::Http(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(IndexerError::Http(format!("{} returned {}", url, response.status())));
+        }
+
+        let page: TxsResponse = response
+            .json()
+            .await
+            .map_err(|e| IndexerError::Decode(e.to_string()))?;
+        Ok(page.tx_responses)
+    }
+
+    /// Fetch every page past the stored cursors and write it to Cozo.
+    #[instrument(skip(self), fields(neuron = %self.config.neuron))]
+    pub async fn sync_once(&self) -> Result<SyncProgress, IndexerError> {
+        let neuron = self.config.neuron.clone();
+        let mut progress = SyncProgress::default();
+
+        let mut status = {
+            let db = self.db.db.lock().unwrap();
+            load_sync_status(&db, &neuron)?.unwrap_or_default()
+        };
+
+        for (cursor, event) in EVENT_STREAMS {
+            let mut offset = status.meta[*cursor].as_u64().unwrap_or(0);
+
+            loop {
+                let page = self.fetch_page(event, offset).await?;
+                if page.is_empty() {
+                    break;
+                }
+
+                let mut transactions = Vec::new();
+                let mut links = Vec::new();
+                for tx in &page {
+                    let (t, l) = map_tx_response(&neuron, tx)?;
+                    transactions.extend(t);
+                    links.extend(l);
+                }
+
+                offset += page.len() as u64;
+                status.meta[*cursor] = serde_json::json!(offset);
+                if let Some(last) = transactions.last() {
+                    status.timestamp_update = status.timestamp_update.max(last.timestamp);
+                    status.meta["transaction_hash"] = serde_json::json!(last.hash);
+                    status.meta["index"] = serde_json::json!(last.index);
+                }
+
+                let new_transactions = {
+                    let db = self.db.db.lock().unwrap();
+                    let new_transactions = count_new_transactions(&db, &neuron, &transactions)?;
+                    status.unread_count += new_transactions as i64;
+                    put_transactions(&db, &transactions)?;
+                    put_links(&db, &links)?;
+                    progress.particles_queued += enqueue_particles(&db, &links)?;
+                    put_sync_status(&db, &neuron, &status)?;
+                    new_transactions
+                };
+
+                progress.transactions += new_transactions;
+                progress.links += links.len();
+
+                if (page.len() as u64) < self.config.page_limit {
+                    break;
+                }
+            }
+        }
+
+        Ok(progress)
+    }
+
+    /// Sync forever, waiting `poll_interval` between rounds.
+    pub async fn run(self) {
+        loop {
+            match self.sync_once().await {
+                Ok(p) if p.transactions > 0 => info!(
+                    neuron = %self.config.neuron,
+                    "+{} transactions, +{} links, {} particles queued",
+                    p.transactions, p.links, p.particles_queued
+                ),
+                Ok(_) => {}
+                Err(e) => warn!(neuron = %self.config.neuron, "sync failed: {:?}", e),
+            }
+            tokio::time::sleep(self.config.poll_interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DbEngine;
+    use std::collections::HashMap;
+    use warp::Filter;
+
+    const NEURON: &str = "bostrom1neuron";
+    const PAGE_1: &str = include_str!("../tests/fixtures/lcd_txs_page1.json");
+    const PAGE_2: &str = include_str!("../tests/fixtures/lcd_txs_page2.json");
+    /// Starts with the self-transfer that ends the sender stream
+    const RECEIVED: &str = include_str!("../tests/fixtures/lcd_txs_received.json");
+    const EMPTY: &str = r#"{"txs": [], "tx_responses": [], "pagination": null, "total": "0"}"#;
+
+    /// Serve recorded LCD pages for the sender and recipient streams; pages
+    /// past them are empty.
+    async fn fixture_lcd() -> String {
+        let route = warp::path!("cosmos" / "tx" / "v1beta1" / "txs")
+            .and(warp::query::<HashMap<String, String>>())
+            .map(|q: HashMap<String, String>| {
+                let event = q.get("events").map(String::as_str).unwrap_or_default();
+                let stream = event.split('=').next().unwrap_or_default();
+                let body = match (stream, q.get("pagination.offset").map(String::as_str)) {
+                    ("message.sender", Some("0")) => PAGE_1,
+                    ("message.sender", Some("2")) => PAGE_2,
+                    ("transfer.recipient", Some("0")) => RECEIVED,
+                    _ => EMPTY,
+                };
+                warp::reply::with_header(body, "content-type", "application/json")
+            });
+
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        format!("http://{}", addr)
+    }
+
+    fn count(db: &DbInstance, script: &str) -> i64 {
+        let rows = db
+            .run_script(script, Default::default(), ScriptMutability::Immutable)
+            .unwrap();
+        rows.rows[0][0].get_int().unwrap()
+    }
+
+    #[test]
+    fn test_map_cyberlink_tx() {
+        let page: TxsResponse = serde_json::from_str(PAGE_1).unwrap();
+        let (transactions, links) = map_tx_response(NEURON, &page.tx_responses[0]).unwrap();
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].tx_type, CYBERLINK_MSG_TYPE);
+        assert_eq!(transactions[0].block_height, 100);
+        assert_eq!(transactions[0].timestamp, 1_700_000_000_000);
+        assert!(transactions[0].value.get("@type").is_none());
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].transaction_hash, "HASH1");
+    }
+
+    #[tokio::test]
+    async fn test_sync_against_fixture_lcd() {
+        let lcd_url = fixture_lcd().await;
+        let state = Arc::new(DbState::with_engine(DbEngine::Mem).unwrap());
+        state.prepare().unwrap();
+
+        let mut config = IndexerConfig::new(NEURON);
+        config.lcd_url = lcd_url;
+        config.page_limit = 2;
+        let indexer = ChainIndexer::new(state.clone(), config);
+
+        // HASH3 is a self-transfer on both streams; it is stored and counted once
+        let progress = indexer.sync_once().await.unwrap();
+        assert_eq!(progress.transactions, 4);
+        assert_eq!(progress.links, 2);
+        assert_eq!(progress.particles_queued, 3);
+
+        {
+            let db = state.db.lock().unwrap();
+            assert_eq!(count(&db, "?[count(hash)] := *transaction{hash}"), 4);
+            assert_eq!(count(&db, "?[count(from)] := *link{from}"), 2);
+            assert_eq!(count(&db, "?[count(id)] := *sync_queue{id, status}, status == 0"), 3);
+            let status = load_sync_status(&db, NEURON).unwrap().unwrap();
+            assert_eq!(status.unread_count, 4);
+            assert_eq!(status.timestamp_update, 1_700_001_200_000);
+            assert_eq!(status.meta["sent_offset"], 3);
+            assert_eq!(status.meta["received_offset"], 2);
+            assert_eq!(status.meta["transaction_hash"], "HASH4");
+        }
+
+        // Cursor is persisted, so a second round fetches nothing new
+        let progress = indexer.sync_once().await.unwrap();
+        assert_eq!(progress.transactions, 0);
+    }
+}
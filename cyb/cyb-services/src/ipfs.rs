@@ -1,6 +1,10 @@
 use serde::Serialize;
 use std::path::PathBuf;
 use std::process::Command;
+use tracing::{info, warn};
+
+use crate::config::{IpfsConfig, expand_path};
+use crate::registry::{Health, Service, ServiceFuture};
 
 #[derive(Debug, Serialize)]
 pub enum IpfsError {
@@ -8,9 +12,8 @@ pub enum IpfsError {
     Other(String),
 }
 
-fn get_ipfs_repo_path() -> Result<PathBuf, IpfsError> {
-    let home_dir = dirs::home_dir().ok_or(IpfsError::HomeDirNotFound)?;
-    Ok(home_dir.join(".cyb").join("ipfs-repo"))
+fn get_ipfs_repo_path(config: &IpfsConfig) -> Result<PathBuf, IpfsError> {
+    expand_path(&config.repo_path).map_err(|_| IpfsError::HomeDirNotFound)
 }
 
 fn get_ipfs_binary_path() -> Result<PathBuf, IpfsError> {
@@ -81,20 +84,25 @@ fn get_target_triple() -> &'static str {
     }
 }
 
-pub async fn start_ipfs() -> Result<(), IpfsError> {
-    println!("[IPFS] Starting IPFS daemon");
+pub async fn start_ipfs(config: &IpfsConfig) -> Result<(), IpfsError> {
+    info!("Starting IPFS daemon");
 
     let ipfs_binary = get_ipfs_binary_path()?;
-    let repo_path = get_ipfs_repo_path()?;
+    let repo_path = get_ipfs_repo_path(config)?;
     let repo_str = repo_path.to_string_lossy().to_string();
 
     let _ = std::fs::create_dir_all(&repo_path);
 
     if !is_ipfs_initialized_inner(&ipfs_binary, &repo_str) {
-        println!("[IPFS] Initializing IPFS repo at {}", repo_str);
+        info!("Initializing IPFS repo at {}", repo_str);
         init_ipfs_inner(&ipfs_binary, &repo_str).map_err(IpfsError::Other)?;
     }
 
+    let _ = Command::new(&ipfs_binary)
+        .env("IPFS_PATH", &repo_str)
+        .args(["config", "Addresses.API", &format!("/ip4/127.0.0.1/tcp/{}", config.api_port)])
+        .output();
+
     // Configure CORS
     let _ = Command::new(&ipfs_binary)
         .env("IPFS_PATH", &repo_str)
@@ -107,7 +115,7 @@ pub async fn start_ipfs() -> Result<(), IpfsError> {
         .output();
 
     if is_ipfs_running() {
-        println!("[IPFS] Daemon is already running");
+        info!("Daemon is already running");
         return Ok(());
     }
 
@@ -124,9 +132,9 @@ pub async fn start_ipfs() -> Result<(), IpfsError> {
         .unwrap();
 
     for i in 0..15 {
-        match client.post("http://127.0.0.1:5001/api/v0/id").send().await {
+        match client.post(format!("{}/id", config.api_url())).send().await {
             Ok(resp) if resp.status().is_success() => {
-                println!("[IPFS] API is ready!");
+                info!("API is ready");
                 return Ok(());
             }
             _ => {
@@ -137,13 +145,103 @@ pub async fn start_ipfs() -> Result<(), IpfsError> {
         }
     }
 
-    println!("[IPFS] Daemon spawned (API may still be starting)");
+    warn!("Daemon spawned (API may still be starting)");
     Ok(())
 }
 
-pub fn stop_ipfs() -> Result<(), String> {
+/// Fetch up to `max_bytes` of `cid` through the local Kubo API.
+pub async fn cat(config: &IpfsConfig, cid: &str, max_bytes: usize) -> Result<Vec<u8>, IpfsError> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(60))
+        .build()
+        .map_err(|e| IpfsError::Other(e.to_string()))?;
+
+    let resp = client
+        .post(format!("{}/cat", config.api_url()))
+        .query(&[("arg", cid.to_string()), ("length", max_bytes.to_string())])
+        .send()
+        .await
+        .map_err(|e| IpfsError::Other(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(IpfsError::Other(format!("cat {} returned {}", cid, resp.status())));
+    }
+
+    resp.bytes()
+        .await
+        .map(|b| b.to_vec())
+        .map_err(|e| IpfsError::Other(e.to_string()))
+}
+
+/// Size of a file in IPFS and the number of blocks it links to.
+#[derive(Debug, Clone, Copy)]
+pub struct FileStat {
+    pub size: u64,
+    pub blocks: u64,
+}
+
+/// `ipfs files stat` of `cid` through the local Kubo API.
+pub async fn stat(config: &IpfsConfig, cid: &str) -> Result<FileStat, IpfsError> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(60))
+        .build()
+        .map_err(|e| IpfsError::Other(e.to_string()))?;
+
+    let resp = client
+        .post(format!("{}/files/stat", config.api_url()))
+        .query(&[("arg", format!("/ipfs/{}", cid))])
+        .send()
+        .await
+        .map_err(|e| IpfsError::Other(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(IpfsError::Other(format!("files stat {} returned {}", cid, resp.status())));
+    }
+
+    let body: serde_json::Value = resp.json().await.map_err(|e| IpfsError::Other(e.to_string()))?;
+    let field = |key: &str| body.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+    Ok(FileStat {
+        size: field("Size"),
+        blocks: field("Blocks"),
+    })
+}
+
+/// Garbage-collect unpinned blocks through the local Kubo API.
+/// Returns the number of blocks removed.
+pub async fn repo_gc(config: &IpfsConfig) -> Result<usize, IpfsError> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(600))
+        .build()
+        .map_err(|e| IpfsError::Other(e.to_string()))?;
+
+    let resp = client
+        .post(format!("{}/repo/gc", config.api_url()))
+        .send()
+        .await
+        .map_err(|e| IpfsError::Other(e.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(IpfsError::Other(format!("repo gc returned {}", resp.status())));
+    }
+
+    // One JSON object per removed block: {"Key":{"/":"<cid>"}} or {"Error":"..."}
+    let body = resp.text().await.map_err(|e| IpfsError::Other(e.to_string()))?;
+    let mut removed = 0;
+    for line in body.lines().filter(|l| !l.trim().is_empty()) {
+        let entry: serde_json::Value = serde_json::from_str(line).map_err(|e| IpfsError::Other(e.to_string()))?;
+        if let Some(error) = entry.get("Error").and_then(|e| e.as_str()) {
+            return Err(IpfsError::Other(format!("repo gc: {}", error)));
+        }
+        removed += 1;
+    }
+
+    info!(removed, "Repo GC finished");
+    Ok(removed)
+}
+
+pub fn stop_ipfs(config: &IpfsConfig) -> Result<(), String> {
     let ipfs_binary = get_ipfs_binary_path().map_err(|e| format!("{:?}", e))?;
-    let repo_path = get_ipfs_repo_path().map_err(|e| format!("{:?}", e))?;
+    let repo_path = get_ipfs_repo_path(config).map_err(|e| format!("{:?}", e))?;
 
     Command::new(ipfs_binary)
         .env("IPFS_PATH", repo_path.to_string_lossy().as_ref())
@@ -197,3 +295,34 @@ fn is_ipfs_initialized_inner(ipfs_binary: &PathBuf, repo_path: &str) -> bool {
         Err(_) => false,
     }
 }
+
+/// Kubo daemon as a supervised service.
+pub struct IpfsService(pub IpfsConfig);
+
+impl Service for IpfsService {
+    fn name(&self) -> &'static str {
+        "ipfs"
+    }
+
+    fn start(&self) -> ServiceFuture<'_, Result<(), String>> {
+        Box::pin(async { start_ipfs(&self.0).await.map_err(|e| format!("{:?}", e)) })
+    }
+
+    fn stop(&self) -> ServiceFuture<'_, Result<(), String>> {
+        Box::pin(async { stop_ipfs(&self.0) })
+    }
+
+    fn health(&self) -> ServiceFuture<'_, Health> {
+        Box::pin(async {
+            let client = reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(3))
+                .build()
+                .unwrap_or_default();
+            match client.post(format!("{}/id", self.0.api_url())).send().await {
+                Ok(resp) if resp.status().is_success() => Health::Healthy,
+                _ if is_ipfs_running() => Health::Degraded("API not responding".to_string()),
+                _ => Health::Unhealthy("daemon not running".to_string()),
+            }
+        })
+    }
+}
@@ -1,36 +1,187 @@
+pub mod config;
+#[cfg(unix)]
+pub mod control;
 #[cfg(feature = "db")]
 pub mod db;
+#[cfg(feature = "indexer")]
+pub mod indexer;
 #[cfg(feature = "ipfs")]
 pub mod ipfs;
+pub mod logging;
 #[cfg(feature = "mining")]
 pub mod mining;
+#[cfg(feature = "queue")]
+pub mod queue;
+pub mod registry;
+#[cfg(feature = "db")]
+pub mod schema;
+#[cfg(feature = "db")]
+pub mod search;
 #[cfg(feature = "db")]
 pub mod server;
 
-#[cfg(feature = "mining")]
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+#[cfg(feature = "db")]
+use tracing::error;
 
+#[cfg(feature = "db")]
+use db::{DbEngine, DbState};
 #[cfg(feature = "mining")]
 use mining::MiningState;
+pub use config::{CybConfig, ServicesConfig};
+pub use registry::{Health, ServiceState, ServiceStatus};
+use registry::{ServiceRegistry, SupervisorConfig};
+#[cfg(feature = "db")]
+use registry::{TaskService, UnavailableService};
 
 pub struct CybServices {
     #[cfg(feature = "mining")]
     pub mining: Arc<MiningState>,
+    #[cfg(feature = "db")]
+    pub db: Option<Arc<DbState>>,
+    pub registry: Arc<ServiceRegistry>,
+    pub config: ServicesConfig,
+    supervisor: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl CybServices {
+    /// Services configured by `~/.cyb/config.toml`.
     pub fn new() -> Self {
+        Self::with_config(CybConfig::load_or_default().services)
+    }
+
+    pub fn with_config(config: ServicesConfig) -> Self {
+        #[cfg(feature = "mining")]
+        let mining = Arc::new(MiningState::new());
+        #[cfg(feature = "db")]
+        let (db, db_problem) = match config.db.enabled.then(|| open_db(&config.db)) {
+            None => (None, None),
+            Some(Ok((db, fallback))) => (Some(Arc::new(db)), fallback),
+            Some(Err(e)) => (None, Some(e)),
+        };
+
+        #[allow(unused_mut)]
+        let mut registry = ServiceRegistry::new(SupervisorConfig::default());
+        #[cfg(feature = "ipfs")]
+        if config.ipfs.enabled {
+            registry.register(ipfs::IpfsService(config.ipfs.clone()));
+        }
+        #[cfg(feature = "mining")]
+        if config.mining.enabled {
+            registry.register(mining::MiningService(mining.clone()));
+        }
+        #[cfg(feature = "db")]
+        match (&db, db_problem) {
+            (Some(db), degraded) => register_db_services(&mut registry, db, &config, degraded),
+            (None, Some(e)) => {
+                registry.register(UnavailableService::new("db-server", e));
+            }
+            (None, None) => {}
+        }
+
         Self {
             #[cfg(feature = "mining")]
-            mining: Arc::new(MiningState::new()),
+            mining,
+            #[cfg(feature = "db")]
+            db,
+            registry: Arc::new(registry),
+            config,
+            supervisor: Mutex::new(None),
         }
     }
 
+    /// Start every registered service, then supervise them.
     pub async fn start(&self) {
-        #[cfg(feature = "ipfs")]
-        match ipfs::start_ipfs().await {
-            Ok(()) => println!("[cyb-services] IPFS daemon started"),
-            Err(e) => eprintln!("[cyb-services] IPFS start failed: {:?}", e),
+        #[cfg(feature = "db")]
+        if let Some(db) = &self.db
+            && let Err(e) = db.prepare()
+        {
+            error!("DB schema setup failed: {}", e);
+        }
+
+        self.registry.start_all().await;
+
+        if let Some(old) = self.supervisor.lock().unwrap().replace(self.registry.supervise()) {
+            old.abort();
         }
     }
+
+    pub async fn stop(&self) {
+        if let Some(supervisor) = self.supervisor.lock().unwrap().take() {
+            supervisor.abort();
+        }
+        self.registry.stop_all().await;
+    }
+
+    pub fn status(&self) -> Vec<ServiceStatus> {
+        self.registry.status()
+    }
+}
+
+/// DB server, chain indexer (when a neuron is configured) and sync queue
+/// worker. `degraded` is why the DB isn't the configured one, if it isn't.
+#[cfg(feature = "db")]
+fn register_db_services(
+    registry: &mut ServiceRegistry,
+    db: &Arc<DbState>,
+    config: &ServicesConfig,
+    degraded: Option<String>,
+) {
+    let db_server = TaskService::new("db-server", {
+        let db = db.clone();
+        let port = config.db.port;
+        move || server::start_server(db.clone(), port)
+    });
+    registry.register(db_server.degraded(degraded));
+
+    #[cfg(feature = "indexer")]
+    if let Some(neuron) = config
+        .indexer
+        .neuron
+        .clone()
+        .or_else(|| std::env::var("CYB_NEURON").ok())
+    {
+        let config = indexer::IndexerConfig::from_config(&config.indexer, neuron);
+        let db = db.clone();
+        registry.register(TaskService::new("indexer", move || {
+            indexer::ChainIndexer::new(db.clone(), config.clone()).run()
+        }));
+    }
+
+    #[cfg(feature = "queue")]
+    if config.queue.enabled {
+        let db = db.clone();
+        let config = config.clone();
+        registry.register(TaskService::new("sync-queue", move || {
+            #[allow(unused_mut)]
+            let mut worker = queue::QueueWorker::new(db.clone(), queue::QueueConfig::from_config(&config.queue));
+            #[cfg(feature = "ipfs")]
+            worker.register(queue::JOB_PARTICLE, queue::particle_handler(db.clone(), config.ipfs.clone()));
+            #[cfg(feature = "indexer")]
+            worker.register(queue::JOB_CHAIN_SYNC, queue::chain_sync_handler(db.clone(), config.indexer.clone()));
+            worker.run()
+        }));
+    }
+}
+
+/// Open the selected storage engine. With `memory_fallback` set, an engine
+/// that can't be opened is replaced by in-memory storage, and the reason is
+/// returned alongside so the DB server can report it.
+#[cfg(feature = "db")]
+fn open_db(config: &config::DbConfig) -> Result<(DbState, Option<String>), String> {
+    let e = match DbState::from_config(config) {
+        Ok(state) => return Ok((state, None)),
+        Err(e) => format!("CozoDB open failed: {:?}", e),
+    };
+    if !config.memory_fallback {
+        error!("{}", e);
+        return Err(e);
+    }
+
+    error!("{}, using in-memory storage; nothing written will be kept", e);
+    match DbState::with_engine(DbEngine::Mem) {
+        Ok(state) => Ok((state, Some(format!("{}; running on in-memory storage", e)))),
+        Err(mem) => Err(format!("{}; in-memory storage failed too: {:?}", e, mem)),
+    }
 }
@@ -0,0 +1,184 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::config::{LoggingConfig, expand_path};
+
+/// Events kept in memory for in-app display.
+const RECENT_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    /// Unix milliseconds
+    pub timestamp: u64,
+    pub level: String,
+    pub target: String,
+    /// Message followed by `key=value` fields
+    pub message: String,
+}
+
+/// Ring buffer of the latest events, shared between the layer and readers.
+#[derive(Clone, Default)]
+pub struct RecentLogs(Arc<Mutex<VecDeque<LogLine>>>);
+
+impl RecentLogs {
+    fn push(&self, line: LogLine) {
+        let mut lines = self.0.lock().unwrap();
+        if lines.len() == RECENT_CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// Last `count` events whose target starts with `target` (if given), oldest first.
+    pub fn tail(&self, count: usize, target: Option<&str>) -> Vec<LogLine> {
+        let lines = self.0.lock().unwrap();
+        let mut tail: Vec<LogLine> = lines
+            .iter()
+            .rev()
+            .filter(|l| target.is_none_or(|t| l.target.starts_with(t)))
+            .take(count)
+            .cloned()
+            .collect();
+        tail.reverse();
+        tail
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+}
+
+/// Layer feeding [`RecentLogs`].
+pub struct RecentLogsLayer(RecentLogs);
+
+impl<S: Subscriber> Layer<S> for RecentLogsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        self.0.push(LogLine {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            level: meta.level().to_string(),
+            target: meta.target().to_string(),
+            message: format!("{}{}", visitor.message, visitor.fields),
+        });
+    }
+}
+
+/// Keeps the file writer flushing; dropping it flushes and stops file logging.
+pub struct LogGuard {
+    pub recent: RecentLogs,
+    _file: Option<WorkerGuard>,
+}
+
+/// File and recent-events layers for an existing subscriber (e.g. Bevy's
+/// `LogPlugin::custom_layer`). File logging is skipped, with a warning on
+/// stderr, if the log directory can't be created.
+pub fn layers<S>(config: &LoggingConfig) -> (Box<dyn Layer<S> + Send + Sync>, LogGuard)
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let recent = RecentLogs::default();
+    let recent_layer = RecentLogsLayer(recent.clone()).with_filter(EnvFilter::new("info"));
+
+    let (file_layer, file_guard) = match file_appender(config) {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let filter = EnvFilter::try_new(&config.level).unwrap_or_else(|_| EnvFilter::new("info"));
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer)
+                .with_filter(filter);
+            (Some(layer), Some(guard))
+        }
+        Err(e) => {
+            eprintln!("cyb: file logging disabled: {}", e);
+            (None, None)
+        }
+    };
+
+    let layer = recent_layer.and_then(file_layer).boxed();
+    (layer, LogGuard { recent, _file: file_guard })
+}
+
+fn file_appender(config: &LoggingConfig) -> Result<RollingFileAppender, String> {
+    let dir = expand_path(&config.dir).map_err(|e| e.to_string())?;
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("cyb")
+        .filename_suffix("log")
+        .max_log_files(config.max_files)
+        .build(&dir)
+        .map_err(|e| format!("{}: {}", dir.display(), e))
+}
+
+/// Install a global subscriber for standalone binaries: stderr (filtered by
+/// `RUST_LOG`, default `info`), the rotating log file and recent events.
+pub fn init(config: &LoggingConfig) -> LogGuard {
+    let (layer, guard) = layers(config);
+    let stderr = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")));
+    let _ = tracing_subscriber::registry().with(layer).with(stderr).try_init();
+    guard
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_logs_capture_and_tail() {
+        let recent = RecentLogs::default();
+        let subscriber = tracing_subscriber::registry().with(RecentLogsLayer(recent.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "cyb_services::ipfs", "API is ready");
+            tracing::warn!(target: "cyb_services::queue", id = "Qm1", "job failed: {}", "timeout");
+            tracing::info!(target: "cyb_services::ipfs", "Daemon is already running");
+        });
+
+        let all = recent.tail(10, None);
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[1].level, "WARN");
+        assert_eq!(all[1].message, "job failed: timeout id=Qm1");
+
+        let ipfs = recent.tail(1, Some("cyb_services::ipfs"));
+        assert_eq!(ipfs.len(), 1);
+        assert_eq!(ipfs[0].message, "Daemon is already running");
+    }
+}
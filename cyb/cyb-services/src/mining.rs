@@ -4,6 +4,8 @@ use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use uhash_core::UniversalHash;
 
+use crate::registry::{Health, Service, ServiceFuture};
+
 pub struct MiningState {
     mining: AtomicBool,
     hash_count: AtomicU64,
@@ -29,6 +31,31 @@ impl MiningState {
     }
 }
 
+/// Mining is started on demand with `start_mining`; as a service it only
+/// makes sure the worker threads are stopped on shutdown.
+pub struct MiningService(pub Arc<MiningState>);
+
+impl Service for MiningService {
+    fn name(&self) -> &'static str {
+        "mining"
+    }
+
+    fn start(&self) -> ServiceFuture<'_, Result<(), String>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn stop(&self) -> ServiceFuture<'_, Result<(), String>> {
+        Box::pin(async {
+            stop_mining(&self.0);
+            Ok(())
+        })
+    }
+
+    fn health(&self) -> ServiceFuture<'_, Health> {
+        Box::pin(async { Health::Healthy })
+    }
+}
+
 fn meets_difficulty(hash: &[u8], difficulty: u32) -> bool {
     let mut leading_zeros = 0u32;
     for byte in hash {
@@ -0,0 +1,545 @@
+use cozo::{DataValue, DbInstance, NamedRows, ScriptMutability};
+use futures_util::future::join_all;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tracing::{Instrument, error, info, info_span, warn};
+
+use crate::db::DbState;
+
+/// `SyncQueueStatus` in the web app
+pub const STATUS_PENDING: i64 = 0;
+pub const STATUS_EXECUTING: i64 = 1;
+pub const STATUS_DONE: i64 = 2;
+pub const STATUS_ERROR: i64 = -1;
+
+/// `SyncQueueJobType` in the web app, plus native-only job types
+pub const JOB_PARTICLE: i64 = 0;
+pub const JOB_EMBEDDING: i64 = 1;
+pub const JOB_CHAIN_SYNC: i64 = 2;
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: String,
+    pub job_type: i64,
+    pub data: String,
+    pub priority: f64,
+    /// Failed attempts before this run
+    pub attempts: u32,
+}
+
+pub type JobFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
+/// Executes one `job_type`. Any `Fn(Job) -> impl Future<Output = Result<(), String>>` qualifies.
+pub trait JobHandler: Send + Sync {
+    fn run(&self, job: Job) -> JobFuture;
+}
+
+impl<F, Fut> JobHandler for F
+where
+    F: Fn(Job) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<(), String>> + Send + 'static,
+{
+    fn run(&self, job: Job) -> JobFuture {
+        Box::pin(self(job))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueConfig {
+    /// Jobs claimed (and run concurrently) per round
+    pub batch_size: usize,
+    pub max_attempts: u32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    pub poll_interval: Duration,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 8,
+            max_attempts: 5,
+            base_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(600),
+            poll_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+impl QueueConfig {
+    /// Defaults with the `[services.queue]` overrides applied.
+    pub fn from_config(config: &crate::config::QueueConfig) -> Self {
+        Self {
+            batch_size: config.batch_size,
+            max_attempts: config.max_attempts,
+            ..Self::default()
+        }
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Exponential backoff after `attempts` failures, capped at `max_backoff`.
+fn backoff(config: &QueueConfig, attempts: u32) -> Duration {
+    let factor = 1u32.checked_shl(attempts.saturating_sub(1)).unwrap_or(u32::MAX);
+    config
+        .base_backoff
+        .checked_mul(factor)
+        .unwrap_or(config.max_backoff)
+        .min(config.max_backoff)
+}
+
+fn run(db: &DbInstance, script: &str, params: BTreeMap<String, DataValue>, mutability: ScriptMutability) -> Result<NamedRows, String> {
+    db.run_script(script, params, mutability).map_err(|e| e.to_string())
+}
+
+fn key_params(job: &Job) -> BTreeMap<String, DataValue> {
+    let mut params = BTreeMap::new();
+    params.insert("id".to_string(), DataValue::from(job.id.as_str()));
+    params.insert("job_type".to_string(), DataValue::from(job.job_type));
+    params
+}
+
+fn set_status(db: &DbInstance, job: &Job, status: i64) -> Result<(), String> {
+    let mut params = key_params(job);
+    params.insert("status".to_string(), DataValue::from(status));
+    run(
+        db,
+        "?[id, job_type, status] <- [[$id, $job_type, $status]]
+        :update sync_queue { id, job_type => status }",
+        params,
+        ScriptMutability::Mutable,
+    )?;
+    Ok(())
+}
+
+/// Add (or re-queue) a job as pending.
+pub fn enqueue(db: &DbInstance, id: &str, job_type: i64, data: &str, priority: f64) -> Result<(), String> {
+    let mut params = BTreeMap::new();
+    params.insert("id".to_string(), DataValue::from(id));
+    params.insert("job_type".to_string(), DataValue::from(job_type));
+    params.insert("data".to_string(), DataValue::from(data));
+    params.insert("status".to_string(), DataValue::from(STATUS_PENDING));
+    params.insert("priority".to_string(), DataValue::from(priority));
+    run(
+        db,
+        "?[id, job_type, data, status, priority] <- [[$id, $job_type, $data, $status, $priority]]
+        :put sync_queue { id, job_type => data, status, priority }
+        ",
+        params.clone(),
+        ScriptMutability::Mutable,
+    )?;
+    run(
+        db,
+        "?[id, job_type] <- [[$id, $job_type]]
+        :rm sync_queue_retry { id, job_type }",
+        params,
+        ScriptMutability::Mutable,
+    )?;
+    Ok(())
+}
+
+/// Put jobs left `executing` by a previous run back to pending.
+pub fn recover(db: &DbInstance) -> Result<usize, String> {
+    let mut params = BTreeMap::new();
+    params.insert("executing".to_string(), DataValue::from(STATUS_EXECUTING));
+    params.insert("pending".to_string(), DataValue::from(STATUS_PENDING));
+    let stale = run(
+        db,
+        "?[id, job_type] := *sync_queue{id, job_type, status}, status == $executing",
+        params.clone(),
+        ScriptMutability::Immutable,
+    )?;
+    if stale.rows.is_empty() {
+        return Ok(0);
+    }
+
+    run(
+        db,
+        "?[id, job_type, status] := *sync_queue{id, job_type, status: s}, s == $executing, status = $pending
+        :update sync_queue { id, job_type => status }",
+        params,
+        ScriptMutability::Mutable,
+    )?;
+    Ok(stale.rows.len())
+}
+
+/// Claim up to `limit` pending jobs of `job_types` whose backoff has expired,
+/// highest priority first, and mark them executing.
+fn claim(db: &DbInstance, job_types: &[i64], limit: usize) -> Result<Vec<Job>, String> {
+    if job_types.is_empty() || limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut params = BTreeMap::new();
+    params.insert(
+        "types".to_string(),
+        DataValue::List(job_types.iter().map(|t| DataValue::from(*t)).collect()),
+    );
+    params.insert("pending".to_string(), DataValue::from(STATUS_PENDING));
+    params.insert("now".to_string(), DataValue::from(now_ms()));
+    params.insert("limit".to_string(), DataValue::from(limit as i64));
+
+    let rows = run(
+        db,
+        "retry[id, job_type, attempts, next_run_at] :=
+            *sync_queue_retry{id, job_type, attempts, next_run_at}
+        retry[id, job_type, attempts, next_run_at] :=
+            *sync_queue{id, job_type}, not *sync_queue_retry{id, job_type},
+            attempts = 0, next_run_at = 0
+        ?[id, job_type, data, priority, attempts] :=
+            *sync_queue{id, job_type, data, status, priority},
+            status == $pending, is_in(job_type, $types),
+            retry[id, job_type, attempts, next_run_at], next_run_at <= $now
+        :order -priority
+        :limit $limit",
+        params,
+        ScriptMutability::Immutable,
+    )?;
+
+    let jobs: Vec<Job> = rows
+        .rows
+        .iter()
+        .filter_map(|row| {
+            Some(Job {
+                id: row.first()?.get_str()?.to_string(),
+                job_type: row.get(1)?.get_int()?,
+                data: row.get(2)?.get_str().unwrap_or_default().to_string(),
+                priority: row.get(3)?.get_float().unwrap_or(0.0),
+                attempts: row.get(4)?.get_int().unwrap_or(0) as u32,
+            })
+        })
+        .collect();
+
+    for job in &jobs {
+        set_status(db, job, STATUS_EXECUTING)?;
+    }
+
+    Ok(jobs)
+}
+
+fn complete(db: &DbInstance, job: &Job) -> Result<(), String> {
+    set_status(db, job, STATUS_DONE)?;
+    run(
+        db,
+        "?[id, job_type] <- [[$id, $job_type]]
+        :rm sync_queue_retry { id, job_type }",
+        key_params(job),
+        ScriptMutability::Mutable,
+    )?;
+    Ok(())
+}
+
+/// Record a failure: schedule a retry after backoff, or mark the job as
+/// errored once `max_attempts` is reached.
+fn fail(db: &DbInstance, config: &QueueConfig, job: &Job, error: &str) -> Result<(), String> {
+    let attempts = job.attempts + 1;
+    let status = if attempts >= config.max_attempts {
+        STATUS_ERROR
+    } else {
+        STATUS_PENDING
+    };
+
+    let mut params = key_params(job);
+    params.insert("attempts".to_string(), DataValue::from(attempts as i64));
+    params.insert(
+        "next_run_at".to_string(),
+        DataValue::from(now_ms() + backoff(config, attempts).as_millis() as i64),
+    );
+    params.insert("last_error".to_string(), DataValue::from(error));
+    run(
+        db,
+        "?[id, job_type, attempts, next_run_at, last_error] <- [[$id, $job_type, $attempts, $next_run_at, $last_error]]
+        :put sync_queue_retry { id, job_type => attempts, next_run_at, last_error }",
+        params,
+        ScriptMutability::Mutable,
+    )?;
+    set_status(db, job, status)
+}
+
+/// Consumes `sync_queue`: claims jobs by priority, dispatches them to the
+/// handler registered for their `job_type`, and records the outcome.
+pub struct QueueWorker {
+    db: Arc<DbState>,
+    config: QueueConfig,
+    handlers: HashMap<i64, Arc<dyn JobHandler>>,
+}
+
+impl QueueWorker {
+    pub fn new(db: Arc<DbState>, config: QueueConfig) -> Self {
+        Self {
+            db,
+            config,
+            handlers: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, job_type: i64, handler: impl JobHandler + 'static) -> &mut Self {
+        self.handlers.insert(job_type, Arc::new(handler));
+        self
+    }
+
+    /// Claim and run one batch. Returns the number of jobs processed.
+    pub async fn run_once(&self) -> Result<usize, String> {
+        let job_types: Vec<i64> = self.handlers.keys().copied().collect();
+        let jobs = {
+            let db = self.db.db.lock().unwrap();
+            claim(&db, &job_types, self.config.batch_size)?
+        };
+        if jobs.is_empty() {
+            return Ok(0);
+        }
+
+        let runs = jobs.into_iter().map(|job| {
+            let handler = self.handlers[&job.job_type].clone();
+            let span = info_span!("job", id = %job.id, job_type = job.job_type);
+            async move {
+                let result = handler.run(job.clone()).await;
+                (job, result)
+            }
+            .instrument(span)
+        });
+        let results = join_all(runs).await;
+
+        let db = self.db.db.lock().unwrap();
+        for (job, result) in &results {
+            match result {
+                Ok(()) => complete(&db, job)?,
+                Err(e) => {
+                    warn!(id = %job.id, job_type = job.job_type, attempt = job.attempts + 1, "job failed: {}", e);
+                    fail(&db, &self.config, job, e)?;
+                }
+            }
+        }
+
+        Ok(results.len())
+    }
+
+    /// Recover interrupted jobs, then process the queue until the task is dropped.
+    pub async fn run(self) {
+        match recover(&self.db.db.lock().unwrap()) {
+            Ok(0) => {}
+            Ok(n) => info!("Re-queued {} interrupted jobs", n),
+            Err(e) => error!("Recovery failed: {}", e),
+        }
+
+        loop {
+            match self.run_once().await {
+                Ok(0) => tokio::time::sleep(self.config.poll_interval).await,
+                Ok(_) => {}
+                Err(e) => {
+                    error!("{}", e);
+                    tokio::time::sleep(self.config.poll_interval).await;
+                }
+            }
+        }
+    }
+}
+
+/// Largest particle body fetched for the `particle` relation's text preview.
+#[cfg(feature = "ipfs")]
+const PARTICLE_MAX_BYTES: usize = 1 << 20;
+
+/// `particle` jobs: fetch the CID from IPFS and store it, like the web app's
+/// ParticlesResolverQueue.
+#[cfg(feature = "ipfs")]
+pub fn particle_handler(db: Arc<DbState>, ipfs: crate::config::IpfsConfig) -> impl JobHandler {
+    move |job: Job| {
+        let db = db.clone();
+        let ipfs = ipfs.clone();
+        async move {
+            let stat = crate::ipfs::stat(&ipfs, &job.id).await.map_err(|e| format!("{:?}", e))?;
+            let bytes = crate::ipfs::cat(&ipfs, &job.id, PARTICLE_MAX_BYTES)
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+            let (mime, text) = sniff_particle(&bytes);
+            let kind = content_type(mime);
+            let text = if kind == "text" { text.unwrap_or_default() } else { "" };
+
+            let mut params = BTreeMap::new();
+            params.insert("cid".to_string(), DataValue::from(job.id.as_str()));
+            params.insert("mime".to_string(), DataValue::from(mime));
+            params.insert("text".to_string(), DataValue::from(text));
+            params.insert("blocks".to_string(), DataValue::from(stat.blocks as i64));
+            params.insert("size".to_string(), DataValue::from(stat.size as i64));
+            params.insert("type".to_string(), DataValue::from(kind));
+            run(
+                &db.db.lock().unwrap(),
+                "?[cid, mime, text, blocks, size, size_local, type] <- [[$cid, $mime, $text, $blocks, $size, -1, $type]]
+                :put particle { cid => mime, text, blocks, size, size_local, type }",
+                params,
+                ScriptMutability::Mutable,
+            )?;
+            Ok(())
+        }
+    }
+}
+
+/// Leading bytes of binary formats, as the web app's `file-type` sniffing
+/// recognises them. Checked before the text test: some are valid UTF-8.
+#[cfg(feature = "ipfs")]
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"\0asm", "application/wasm"),
+    (b"ID3", "audio/mpeg"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/x-flac"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+];
+
+/// MIME type of a particle body cut at `PARTICLE_MAX_BYTES`, and its text
+/// if it is text. A character split by the cut doesn't make it binary.
+#[cfg(feature = "ipfs")]
+fn sniff_particle(bytes: &[u8]) -> (&'static str, Option<&str>) {
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+        return (*mime, None);
+    }
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(&b"WEBP"[..]) {
+        return ("image/webp", None);
+    }
+    if bytes.get(4..8) == Some(&b"ftyp"[..]) {
+        return ("video/mp4", None);
+    }
+
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text),
+        // Only an incomplete sequence at the very end, i.e. the cut
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    };
+    match text {
+        Some(text) => ("text/plain", Some(text)),
+        None => ("unknown", None),
+    }
+}
+
+/// The `particle.type` for a MIME type, as the web app's `mimeToBaseContentType`.
+#[cfg(feature = "ipfs")]
+fn content_type(mime: &str) -> &'static str {
+    if mime.contains("text/plain") || mime.contains("application/xml") {
+        "text"
+    } else if mime.contains("image") {
+        "image"
+    } else if mime.contains("application/pdf") {
+        "pdf"
+    } else {
+        "other"
+    }
+}
+
+/// `chain_sync` jobs: one indexer pass for the neuron address in `data`.
+#[cfg(feature = "indexer")]
+pub fn chain_sync_handler(db: Arc<DbState>, settings: crate::config::IndexerConfig) -> impl JobHandler {
+    move |job: Job| {
+        let config = crate::indexer::IndexerConfig::from_config(&settings, job.data);
+        let indexer = crate::indexer::ChainIndexer::new(db.clone(), config);
+        async move {
+            indexer.sync_once().await.map_err(|e| format!("{:?}", e))?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DbEngine;
+
+    fn status_of(db: &DbInstance, id: &str) -> i64 {
+        let mut params = BTreeMap::new();
+        params.insert("id".to_string(), DataValue::from(id));
+        let rows = run(
+            db,
+            "?[status] := *sync_queue{id, status}, id = $id",
+            params,
+            ScriptMutability::Immutable,
+        )
+        .unwrap();
+        rows.rows[0][0].get_int().unwrap()
+    }
+
+    #[cfg(feature = "ipfs")]
+    #[test]
+    fn test_sniff_particle() {
+        assert_eq!(sniff_particle(b"hello"), ("text/plain", Some("hello")));
+        // Cut in the middle of the two-byte 'ж'
+        assert_eq!(sniff_particle(&"aж".as_bytes()[..2]), ("text/plain", Some("a")));
+        assert_eq!(sniff_particle(b"a\xffb"), ("unknown", None));
+        assert_eq!(sniff_particle(b"%PDF-1.4 plain ascii"), ("application/pdf", None));
+        assert_eq!(sniff_particle(b"\x89PNG\r\n\x1a\n...."), ("image/png", None));
+        assert_eq!(sniff_particle(b"RIFF\0\0\0\0WEBPVP8 "), ("image/webp", None));
+
+        assert_eq!(content_type("text/plain"), "text");
+        assert_eq!(content_type("image/png"), "image");
+        assert_eq!(content_type("application/pdf"), "pdf");
+        assert_eq!(content_type("unknown"), "other");
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let config = QueueConfig {
+            base_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(60),
+            ..Default::default()
+        };
+        assert_eq!(backoff(&config, 1), Duration::from_secs(5));
+        assert_eq!(backoff(&config, 2), Duration::from_secs(10));
+        assert_eq!(backoff(&config, 4), Duration::from_secs(40));
+        assert_eq!(backoff(&config, 5), Duration::from_secs(60));
+        assert_eq!(backoff(&config, 100), Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_worker_completes_and_retries_jobs() {
+        let state = Arc::new(DbState::with_engine(DbEngine::Mem).unwrap());
+        state.prepare().unwrap();
+        {
+            let db = state.db.lock().unwrap();
+            enqueue(&db, "good", JOB_PARTICLE, "", 0.9).unwrap();
+            enqueue(&db, "bad", JOB_PARTICLE, "", 0.1).unwrap();
+            enqueue(&db, "other", JOB_EMBEDDING, "", 1.0).unwrap();
+        }
+
+        let config = QueueConfig {
+            max_attempts: 2,
+            base_backoff: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut worker = QueueWorker::new(state.clone(), config);
+        worker.register(JOB_PARTICLE, |job: Job| async move {
+            if job.id == "bad" {
+                Err("unreachable".to_string())
+            } else {
+                Ok(())
+            }
+        });
+
+        assert_eq!(worker.run_once().await.unwrap(), 2);
+        {
+            let db = state.db.lock().unwrap();
+            assert_eq!(status_of(&db, "good"), STATUS_DONE);
+            assert_eq!(status_of(&db, "bad"), STATUS_PENDING);
+            // No handler registered for embeddings
+            assert_eq!(status_of(&db, "other"), STATUS_PENDING);
+        }
+
+        assert_eq!(worker.run_once().await.unwrap(), 1);
+        assert_eq!(status_of(&state.db.lock().unwrap(), "bad"), STATUS_ERROR);
+        assert_eq!(worker.run_once().await.unwrap(), 0);
+    }
+}
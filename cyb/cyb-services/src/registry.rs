@@ -0,0 +1,445 @@
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+pub type ServiceFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceState {
+    Stopped,
+    Starting,
+    Running,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "message", rename_all = "snake_case")]
+pub enum Health {
+    Healthy,
+    /// Up, but not fully working (e.g. daemon running, API not answering yet)
+    Degraded(String),
+    /// Down; the supervisor will restart it
+    Unhealthy(String),
+}
+
+/// Lifecycle of a long-running service managed by [`ServiceRegistry`].
+pub trait Service: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn start(&self) -> ServiceFuture<'_, Result<(), String>>;
+    fn stop(&self) -> ServiceFuture<'_, Result<(), String>>;
+    fn health(&self) -> ServiceFuture<'_, Health>;
+}
+
+/// Point-in-time view of one service, for the tray, status world and `cybctl status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub state: ServiceState,
+    pub health: Option<Health>,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    /// Unix seconds of the last state change
+    pub since: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SupervisorConfig {
+    pub check_interval: Duration,
+    pub restart_delay: Duration,
+    /// Restarts attempted before a service is left failed
+    pub max_restarts: u32,
+    /// Running this long without being unhealthy clears the restart count,
+    /// so only failures close together use up `max_restarts`
+    pub healthy_reset: Duration,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: Duration::from_secs(10),
+            restart_delay: Duration::from_secs(5),
+            max_restarts: 5,
+            healthy_reset: Duration::from_secs(600),
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+struct Entry {
+    service: Arc<dyn Service>,
+    status: Mutex<ServiceStatus>,
+    failed_at: Mutex<Option<Instant>>,
+    /// When it last became running; `None` while it is in any other state
+    running_at: Mutex<Option<Instant>>,
+}
+
+impl Entry {
+    fn set_state(&self, state: ServiceState, error: Option<String>) {
+        let mut status = self.status.lock().unwrap();
+        status.state = state;
+        status.since = unix_now();
+        if error.is_some() {
+            status.last_error = error;
+        }
+        *self.failed_at.lock().unwrap() = (state == ServiceState::Failed).then(Instant::now);
+        *self.running_at.lock().unwrap() = (state == ServiceState::Running).then(Instant::now);
+    }
+
+    async fn start(&self) {
+        self.set_state(ServiceState::Starting, None);
+        match self.service.start().await {
+            Ok(()) => {
+                info!(service = self.service.name(), "started");
+                self.set_state(ServiceState::Running, None);
+            }
+            Err(e) => {
+                error!(service = self.service.name(), "start failed: {}", e);
+                self.set_state(ServiceState::Failed, Some(e));
+            }
+        }
+    }
+
+    async fn stop(&self) {
+        if let Err(e) = self.service.stop().await {
+            warn!(service = self.service.name(), "stop failed: {}", e);
+        }
+        self.set_state(ServiceState::Stopped, None);
+        self.status.lock().unwrap().health = None;
+    }
+}
+
+/// Ordered set of services. Started in registration order, stopped in reverse.
+pub struct ServiceRegistry {
+    entries: Vec<Entry>,
+    config: SupervisorConfig,
+}
+
+impl ServiceRegistry {
+    pub fn new(config: SupervisorConfig) -> Self {
+        Self {
+            entries: Vec::new(),
+            config,
+        }
+    }
+
+    pub fn register(&mut self, service: impl Service + 'static) -> &mut Self {
+        let name = service.name();
+        self.entries.push(Entry {
+            service: Arc::new(service),
+            status: Mutex::new(ServiceStatus {
+                name: name.to_string(),
+                state: ServiceState::Stopped,
+                health: None,
+                restarts: 0,
+                last_error: None,
+                since: unix_now(),
+            }),
+            failed_at: Mutex::new(None),
+            running_at: Mutex::new(None),
+        });
+        self
+    }
+
+    fn entry(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.service.name() == name)
+    }
+
+    /// Every service reports `Starting` until its turn comes.
+    pub async fn start_all(&self) {
+        for entry in &self.entries {
+            entry.set_state(ServiceState::Starting, None);
+        }
+        for entry in &self.entries {
+            entry.start().await;
+        }
+    }
+
+    pub async fn stop_all(&self) {
+        for entry in self.entries.iter().rev() {
+            entry.stop().await;
+        }
+    }
+
+    pub async fn start_service(&self, name: &str) -> Result<(), String> {
+        let entry = self.entry(name).ok_or_else(|| format!("unknown service {}", name))?;
+        entry.start().await;
+        Ok(())
+    }
+
+    /// Stopped services are left alone by the supervisor until started again.
+    pub async fn stop_service(&self, name: &str) -> Result<(), String> {
+        let entry = self.entry(name).ok_or_else(|| format!("unknown service {}", name))?;
+        entry.stop().await;
+        Ok(())
+    }
+
+    pub fn status(&self) -> Vec<ServiceStatus> {
+        self.entries
+            .iter()
+            .map(|e| e.status.lock().unwrap().clone())
+            .collect()
+    }
+
+    /// One supervision pass: refresh health of running services, mark
+    /// unhealthy ones failed, and restart failed ones after `restart_delay`.
+    /// Services healthy for `healthy_reset` get their restart count back.
+    pub async fn check_once(&self) {
+        for entry in &self.entries {
+            let state = entry.status.lock().unwrap().state;
+
+            if state == ServiceState::Running {
+                let health = entry.service.health().await;
+                entry.status.lock().unwrap().health = Some(health.clone());
+                if let Health::Unhealthy(reason) = health {
+                    warn!(service = entry.service.name(), "unhealthy: {}", reason);
+                    entry.set_state(ServiceState::Failed, Some(reason));
+                } else if entry
+                    .running_at
+                    .lock()
+                    .unwrap()
+                    .is_some_and(|t| t.elapsed() >= self.config.healthy_reset)
+                {
+                    entry.status.lock().unwrap().restarts = 0;
+                }
+            }
+
+            if entry.status.lock().unwrap().state != ServiceState::Failed {
+                continue;
+            }
+            let restarts = entry.status.lock().unwrap().restarts;
+            let waited = entry
+                .failed_at
+                .lock()
+                .unwrap()
+                .is_none_or(|t| t.elapsed() >= self.config.restart_delay);
+            if restarts >= self.config.max_restarts || !waited {
+                continue;
+            }
+
+            info!(service = entry.service.name(), "restarting ({}/{})", restarts + 1, self.config.max_restarts);
+            entry.status.lock().unwrap().restarts += 1;
+            let _ = entry.service.stop().await;
+            entry.start().await;
+        }
+    }
+
+    /// Run [`check_once`](Self::check_once) every `check_interval` until the task is aborted.
+    pub fn supervise(self: &Arc<Self>) -> JoinHandle<()> {
+        let registry = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(registry.config.check_interval).await;
+                registry.check_once().await;
+            }
+        })
+    }
+}
+
+/// Wraps a background loop (DB server, indexer, queue worker) as a service.
+/// `start` spawns a fresh task from the factory; the service is unhealthy
+/// once that task exits.
+pub struct TaskService<F> {
+    name: &'static str,
+    factory: F,
+    handle: Mutex<Option<JoinHandle<()>>>,
+    degraded: Option<String>,
+}
+
+impl<F, Fut> TaskService<F>
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    pub fn new(name: &'static str, factory: F) -> Self {
+        Self {
+            name,
+            factory,
+            handle: Mutex::new(None),
+            degraded: None,
+        }
+    }
+
+    /// Report the running task as degraded for `reason`, if there is one.
+    pub fn degraded(mut self, reason: Option<String>) -> Self {
+        self.degraded = reason;
+        self
+    }
+}
+
+impl<F, Fut> Service for TaskService<F>
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn start(&self) -> ServiceFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            let handle = tokio::spawn((self.factory)());
+            if let Some(old) = self.handle.lock().unwrap().replace(handle) {
+                old.abort();
+            }
+            Ok(())
+        })
+    }
+
+    fn stop(&self) -> ServiceFuture<'_, Result<(), String>> {
+        Box::pin(async move {
+            if let Some(handle) = self.handle.lock().unwrap().take() {
+                handle.abort();
+            }
+            Ok(())
+        })
+    }
+
+    fn health(&self) -> ServiceFuture<'_, Health> {
+        Box::pin(async move {
+            match &*self.handle.lock().unwrap() {
+                Some(handle) if !handle.is_finished() => match &self.degraded {
+                    Some(reason) => Health::Degraded(reason.clone()),
+                    None => Health::Healthy,
+                },
+                Some(_) => Health::Unhealthy("task exited".to_string()),
+                None => Health::Unhealthy("not started".to_string()),
+            }
+        })
+    }
+}
+
+/// Stands in for a service that can't run at all (e.g. the DB server when
+/// the database didn't open), so the reason shows up in the status.
+pub struct UnavailableService {
+    name: &'static str,
+    reason: String,
+}
+
+impl UnavailableService {
+    pub fn new(name: &'static str, reason: String) -> Self {
+        Self { name, reason }
+    }
+}
+
+impl Service for UnavailableService {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn start(&self) -> ServiceFuture<'_, Result<(), String>> {
+        Box::pin(async move { Err(self.reason.clone()) })
+    }
+
+    fn stop(&self) -> ServiceFuture<'_, Result<(), String>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn health(&self) -> ServiceFuture<'_, Health> {
+        Box::pin(async move { Health::Unhealthy(self.reason.clone()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_supervisor_restarts_exited_task() {
+        let runs = Arc::new(AtomicU32::new(0));
+        let mut registry = ServiceRegistry::new(SupervisorConfig {
+            restart_delay: Duration::ZERO,
+            max_restarts: 2,
+            ..Default::default()
+        });
+        registry.register(TaskService::new("flaky", {
+            let runs = runs.clone();
+            move || {
+                runs.fetch_add(1, Ordering::SeqCst);
+                async {}
+            }
+        }));
+
+        registry.start_all().await;
+        assert_eq!(registry.status()[0].state, ServiceState::Running);
+
+        for _ in 0..4 {
+            tokio::task::yield_now().await;
+            registry.check_once().await;
+        }
+
+        let status = &registry.status()[0];
+        assert_eq!(status.restarts, 2);
+        assert_eq!(status.state, ServiceState::Failed);
+        assert_eq!(status.last_error.as_deref(), Some("task exited"));
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+
+        registry.stop_all().await;
+        assert_eq!(registry.status()[0].state, ServiceState::Stopped);
+    }
+
+    /// Healthy or not, as the test says.
+    struct Switchable(Arc<AtomicBool>);
+
+    impl Service for Switchable {
+        fn name(&self) -> &'static str {
+            "switchable"
+        }
+
+        fn start(&self) -> ServiceFuture<'_, Result<(), String>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn stop(&self) -> ServiceFuture<'_, Result<(), String>> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn health(&self) -> ServiceFuture<'_, Health> {
+            Box::pin(async move {
+                match self.0.load(Ordering::SeqCst) {
+                    true => Health::Healthy,
+                    false => Health::Unhealthy("down".to_string()),
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_healthy_service_gets_restarts_back() {
+        let healthy = Arc::new(AtomicBool::new(true));
+        let mut registry = ServiceRegistry::new(SupervisorConfig {
+            restart_delay: Duration::ZERO,
+            max_restarts: 1,
+            healthy_reset: Duration::ZERO,
+            ..Default::default()
+        });
+        registry.register(Switchable(healthy.clone()));
+        registry.start_all().await;
+
+        healthy.store(false, Ordering::SeqCst);
+        registry.check_once().await;
+        assert_eq!(registry.status()[0].restarts, 1);
+        assert_eq!(registry.status()[0].state, ServiceState::Running);
+
+        healthy.store(true, Ordering::SeqCst);
+        registry.check_once().await;
+        assert_eq!(registry.status()[0].restarts, 0);
+
+        // A later failure is restarted again instead of hitting the limit
+        healthy.store(false, Ordering::SeqCst);
+        registry.check_once().await;
+        assert_eq!(registry.status()[0].restarts, 1);
+        assert_eq!(registry.status()[0].state, ServiceState::Running);
+    }
+}
@@ -0,0 +1,165 @@
+use cozo::{DbInstance, ScriptMutability};
+use tracing::info;
+
+/// Matches `DB_VERSION` in `src/services/CozoDb/cozoDb.ts`.
+const DB_VERSION: f64 = 1.2;
+
+/// Relations shared with the web app (`src/services/CozoDb/migrations/schema.cozo`),
+/// followed by the native-only ones.
+/// Only created when missing, so an existing database keeps its data.
+const RELATIONS: &[(&str, &str)] = &[
+    ("pin", ":create pin { cid: String => type: Int }"),
+    (
+        "particle",
+        ":create particle {
+            cid: String =>
+            mime: String,
+            text: String,
+            blocks: Int,
+            size: Int,
+            size_local: Int,
+            type: String
+        }",
+    ),
+    (
+        "link",
+        ":create link {
+            from: String,
+            to: String,
+            neuron: String =>
+            timestamp: Int,
+            transaction_hash: String default ''
+        }",
+    ),
+    (
+        "transaction",
+        ":create transaction {
+            hash: String,
+            index: Int,
+            neuron: String,
+            type: String =>
+            block_height: Int,
+            success: Bool,
+            timestamp: Int,
+            value: Json,
+            memo: String
+        }",
+    ),
+    (
+        "sync_status",
+        ":create sync_status {
+            owner_id: String,
+            id: String =>
+            entry_type: Int,
+            disabled: Bool,
+            timestamp_update: Int,
+            timestamp_read: Int,
+            unread_count: Int,
+            meta: Json
+        }",
+    ),
+    ("config", ":create config { key: String, group_key: String => value: Json }"),
+    (
+        "sync_queue",
+        ":create sync_queue {
+            id: String,
+            job_type: Int =>
+            data: String default '',
+            status: Int default 0,
+            priority: Float default 0,
+        }",
+    ),
+    (
+        "community",
+        ":create community {
+            owner_id: String,
+            neuron: String =>
+            particle: String,
+            name: String default '',
+            following: Bool,
+            follower: Bool
+        }",
+    ),
+    ("embeddings", ":create embeddings { cid: String => vec: <F32; 384> }"),
+    // Native-only: retry bookkeeping for the sync_queue worker, kept out of
+    // sync_queue itself so the web app's view of the queue is unchanged.
+    (
+        "sync_queue_retry",
+        ":create sync_queue_retry {
+            id: String,
+            job_type: Int =>
+            attempts: Int,
+            next_run_at: Int,
+            last_error: String default ''
+        }",
+    ),
+];
+
+fn run(db: &DbInstance, script: &str, mutability: ScriptMutability) -> Result<cozo::NamedRows, String> {
+    db.run_script(script, Default::default(), mutability)
+        .map_err(|e| e.to_string())
+}
+
+/// Names of all stored relations.
+pub fn list_relations(db: &DbInstance) -> Result<Vec<String>, String> {
+    let rows = run(db, "::relations", ScriptMutability::Immutable)?;
+    Ok(rows
+        .rows
+        .iter()
+        .filter_map(|row| row.first().and_then(|v| v.get_str()).map(str::to_string))
+        .collect())
+}
+
+/// Names of indices (`fts`, `semantic`, ...) defined on `relation`.
+pub fn list_indices(db: &DbInstance, relation: &str) -> Result<Vec<String>, String> {
+    let rows = run(db, &format!("::indices {}", relation), ScriptMutability::Immutable)?;
+    Ok(rows
+        .rows
+        .iter()
+        .filter_map(|row| row.first().and_then(|v| v.get_str()).map(str::to_string))
+        .collect())
+}
+
+/// Create any missing relation from the shared schema. A fresh database also
+/// gets the current `DB_VERSION` so the web app doesn't re-run its migrations.
+pub fn ensure_schema(db: &DbInstance) -> Result<(), String> {
+    let existing = list_relations(db)?;
+
+    for (name, ddl) in RELATIONS {
+        if !existing.iter().any(|r| r == name) {
+            run(db, ddl, ScriptMutability::Mutable)?;
+            info!("Created relation {}", name);
+        }
+    }
+
+    if existing.is_empty() {
+        run(
+            db,
+            &format!(
+                "?[key, group_key, value] <- [['DB_VERSION', 'system', {}]]
+                :put config {{ key, group_key => value }}",
+                DB_VERSION
+            ),
+            ScriptMutability::Mutable,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ensure_schema_is_idempotent() {
+        let db = DbInstance::new("mem", "", Default::default()).unwrap();
+        ensure_schema(&db).unwrap();
+        ensure_schema(&db).unwrap();
+
+        let relations = list_relations(&db).unwrap();
+        for (name, _) in RELATIONS {
+            assert!(relations.iter().any(|r| r == name), "missing {}", name);
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use cozo::{DataValue, DbInstance, ScriptMutability};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::info;
+
+use crate::schema::list_indices;
+
+const SNIPPET_RADIUS: usize = 60;
+const EMBEDDING_DIM: usize = 384;
+
+const CREATE_FTS_INDEX: &str = "::fts create particle:fts {
+    extractor: text,
+    tokenizer: Simple,
+    filters: [Lowercase, Stemmer('english'), Stopwords('en')],
+}";
+
+/// Same index as the web app's `schema.cozo`: L2 distance over `vec`.
+fn create_semantic_index() -> String {
+    format!(
+        "::hnsw create embeddings:semantic {{
+    fields: [vec],
+    dim: {},
+    ef: 100,
+    m: 16
+}}",
+        EMBEDDING_DIM
+    )
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub cid: String,
+    pub score: f64,
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarHit {
+    pub cid: String,
+    /// L2 distance to the query vector; smaller is closer
+    pub distance: f64,
+    pub snippet: String,
+}
+
+/// A stored particle: its content type, text and size in bytes.
+#[derive(Debug, Clone, Serialize)]
+pub struct Particle {
+    pub cid: String,
+    pub mime: String,
+    pub text: String,
+    pub size: i64,
+}
+
+/// Create the particle full-text index and the embeddings HNSW index if missing.
+/// Cozo keeps both in sync with their relations on every write afterwards.
+pub fn ensure_indices(db: &DbInstance) -> Result<(), String> {
+    if !list_indices(db, "particle")?.iter().any(|i| i == "fts") {
+        db.run_script(CREATE_FTS_INDEX, Default::default(), ScriptMutability::Mutable)
+            .map_err(|e| e.to_string())?;
+        info!("Created particle:fts index");
+    }
+
+    if !list_indices(db, "embeddings")?.iter().any(|i| i == "semantic") {
+        db.run_script(&create_semantic_index(), Default::default(), ScriptMutability::Mutable)
+            .map_err(|e| e.to_string())?;
+        info!("Created embeddings:semantic index");
+    }
+
+    Ok(())
+}
+
+/// Full-text search over particle text, ranked by TF-IDF score.
+pub fn search_particles(db: &DbInstance, query: &str, limit: usize) -> Result<Vec<SearchHit>, String> {
+    let query = query.trim();
+    let fts = fts_query(query);
+    if fts.is_empty() || limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let script = "?[cid, score, text] := ~particle:fts{cid, text | query: $q, k: $k, score_kind: 'tf_idf', bind_score: score}
+        :order -score
+        :limit $k";
+
+    let mut params = BTreeMap::new();
+    params.insert("q".to_string(), DataValue::from(fts.as_str()));
+    params.insert("k".to_string(), DataValue::from(limit as i64));
+
+    let rows = db
+        .run_script(script, params, ScriptMutability::Immutable)
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows
+        .rows
+        .iter()
+        .filter_map(|row| {
+            let cid = row.first()?.get_str()?.to_string();
+            let score = row.get(1)?.get_float()?;
+            let text = row.get(2)?.get_str().unwrap_or_default();
+            Some(SearchHit {
+                cid,
+                score,
+                snippet: make_snippet(text, query),
+            })
+        })
+        .collect())
+}
+
+/// The particle stored under `cid`, if any.
+pub fn get_particle(db: &DbInstance, cid: &str) -> Result<Option<Particle>, String> {
+    let script = "?[mime, text, size] := *particle{cid: $cid, mime, text, size}";
+
+    let mut params = BTreeMap::new();
+    params.insert("cid".to_string(), DataValue::from(cid));
+
+    let rows = db
+        .run_script(script, params, ScriptMutability::Immutable)
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows.rows.first().and_then(|row| {
+        Some(Particle {
+            cid: cid.to_string(),
+            mime: row.first()?.get_str()?.to_string(),
+            text: row.get(1)?.get_str()?.to_string(),
+            size: row.get(2)?.get_int()?,
+        })
+    }))
+}
+
+/// `query` in Cozo's FTS syntax with every word quoted, so operators,
+/// parentheses and stray quotes in user input are matched as text rather
+/// than parsed. Words are what the `Simple` tokenizer would split out.
+fn fts_query(query: &str) -> String {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"", word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Nearest particles to an embedding vector, closest first.
+pub fn search_similar(db: &DbInstance, vec: &[f32], limit: usize) -> Result<Vec<SimilarHit>, String> {
+    if vec.len() != EMBEDDING_DIM {
+        return Err(format!("expected {}-dim embedding, got {}", EMBEDDING_DIM, vec.len()));
+    }
+    if limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let script = "?[cid, dist, text] := ~embeddings:semantic{cid | query: q, k: $k, ef: 50, bind_distance: dist},
+            q = vec($vec),
+            *particle{cid, text}
+        :order dist
+        :limit $k";
+
+    let mut params = BTreeMap::new();
+    params.insert(
+        "vec".to_string(),
+        DataValue::List(vec.iter().map(|v| DataValue::from(*v as f64)).collect()),
+    );
+    params.insert("k".to_string(), DataValue::from(limit as i64));
+
+    let rows = db
+        .run_script(script, params, ScriptMutability::Immutable)
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows
+        .rows
+        .iter()
+        .filter_map(|row| {
+            let cid = row.first()?.get_str()?.to_string();
+            let distance = row.get(1)?.get_float()?;
+            let text = row.get(2)?.get_str().unwrap_or_default();
+            Some(SimilarHit {
+                cid,
+                distance,
+                snippet: make_snippet(text, ""),
+            })
+        })
+        .collect())
+}
+
+/// Excerpt of `text` around the first query term it contains, or its start.
+fn make_snippet(text: &str, query: &str) -> String {
+    let lower = text.to_lowercase();
+    let hit = query
+        .split_whitespace()
+        .filter_map(|term| lower.find(&term.to_lowercase()))
+        .min()
+        // Lowercasing can shift byte offsets for some scripts; fall back to the start.
+        .filter(|&pos| text.is_char_boundary(pos))
+        .unwrap_or(0);
+
+    let mut start = hit.saturating_sub(SNIPPET_RADIUS);
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (hit + SNIPPET_RADIUS).min(text.len());
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+
+    let mut snippet = text[start..end].split_whitespace().collect::<Vec<_>>().join(" ");
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < text.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::ensure_schema;
+
+    #[test]
+    fn test_make_snippet() {
+        assert_eq!(make_snippet("short text", "text"), "short text");
+
+        let long = format!("{} needle {}", "a ".repeat(100), "b ".repeat(100));
+        let snippet = make_snippet(&long, "Needle");
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains("needle"));
+
+        // Multi-byte text must not split characters
+        let snippet = make_snippet(&"жж ".repeat(80), "missing");
+        assert!(snippet.ends_with('…'));
+    }
+
+    #[test]
+    fn test_search_particles_ranks_matches() {
+        let db = DbInstance::new("mem", "", Default::default()).unwrap();
+        ensure_schema(&db).unwrap();
+        ensure_indices(&db).unwrap();
+
+        db.run_script(
+            "?[cid, mime, text, blocks, size, size_local, type] <- [
+                ['Qm1', 'text/plain', 'cyber knowledge graph', 1, 1, 1, 'text'],
+                ['Qm2', 'text/plain', 'graph of graph links', 1, 1, 1, 'text'],
+                ['Qm3', 'text/plain', 'unrelated', 1, 1, 1, 'text']
+            ]
+            :put particle { cid => mime, text, blocks, size, size_local, type }",
+            Default::default(),
+            ScriptMutability::Mutable,
+        )
+        .unwrap();
+
+        let hits = search_particles(&db, "graph", 10).unwrap();
+        let cids: Vec<&str> = hits.iter().map(|h| h.cid.as_str()).collect();
+        assert_eq!(cids, vec!["Qm2", "Qm1"]);
+        assert!(search_particles(&db, "  ", 10).unwrap().is_empty());
+
+        // Query syntax in user input is matched literally, not parsed
+        assert_eq!(search_particles(&db, "graph\"", 10).unwrap().len(), 2);
+        assert_eq!(search_particles(&db, "cyber (", 10).unwrap()[0].cid, "Qm1");
+        assert!(search_particles(&db, "()\"*", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_particle() {
+        let db = DbInstance::new("mem", "", Default::default()).unwrap();
+        ensure_schema(&db).unwrap();
+
+        db.run_script(
+            "?[cid, mime, text, blocks, size, size_local, type] <- [
+                ['Qm1', 'text/markdown', '# cyber', 1, 7, 7, 'text']
+            ]
+            :put particle { cid => mime, text, blocks, size, size_local, type }",
+            Default::default(),
+            ScriptMutability::Mutable,
+        )
+        .unwrap();
+
+        let particle = get_particle(&db, "Qm1").unwrap().unwrap();
+        assert_eq!(particle.cid, "Qm1");
+        assert_eq!(particle.mime, "text/markdown");
+        assert_eq!(particle.text, "# cyber");
+        assert_eq!(particle.size, 7);
+        assert!(get_particle(&db, "Qm2").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_fts_query_quotes_words() {
+        assert_eq!(fts_query("a AND (b"), r#""a" "AND" "b""#);
+        assert_eq!(fts_query("foo\""), r#""foo""#);
+        assert_eq!(fts_query("\"( )"), "");
+    }
+
+    #[test]
+    fn test_search_similar_orders_by_distance() {
+        let db = DbInstance::new("mem", "", Default::default()).unwrap();
+        ensure_schema(&db).unwrap();
+        ensure_indices(&db).unwrap();
+
+        let unit = |i: usize| {
+            let mut vec = vec![0.0f32; EMBEDDING_DIM];
+            vec[i] = 1.0;
+            vec
+        };
+        let as_param = |vec: &[f32]| DataValue::List(vec.iter().map(|v| DataValue::from(*v as f64)).collect());
+        db.run_script(
+            "?[cid, mime, text, blocks, size, size_local, type] <- [
+                ['Qm1', 'text/plain', 'first', 1, 1, 1, 'text'],
+                ['Qm2', 'text/plain', 'second', 1, 1, 1, 'text']
+            ]
+            :put particle { cid => mime, text, blocks, size, size_local, type }",
+            Default::default(),
+            ScriptMutability::Mutable,
+        )
+        .unwrap();
+        db.run_script(
+            "?[cid, vec] := cid = 'Qm1', vec = vec($a)
+            ?[cid, vec] := cid = 'Qm2', vec = vec($b)
+            :put embeddings { cid => vec }",
+            BTreeMap::from([("a".to_string(), as_param(&unit(0))), ("b".to_string(), as_param(&unit(1)))]),
+            ScriptMutability::Mutable,
+        )
+        .unwrap();
+
+        let hits = search_similar(&db, &unit(0), 10).unwrap();
+        let cids: Vec<&str> = hits.iter().map(|h| h.cid.as_str()).collect();
+        assert_eq!(cids, vec!["Qm1", "Qm2"]);
+        assert!(hits[0].distance.abs() < 1e-6);
+        // Orthogonal unit vectors are further apart than 1; a `1 - distance`
+        // score would go negative here
+        assert!(hits[1].distance > 1.0);
+        assert_eq!(hits[0].snippet, "first");
+
+        assert!(search_similar(&db, &[0.0; 3], 10).is_err());
+    }
+}
@@ -1,9 +1,17 @@
+use futures_util::{SinkExt, StreamExt};
 use serde::Deserialize;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::sync::mpsc;
 use warp::http::StatusCode;
+use warp::ws::{Message, WebSocket, Ws};
 use warp::Filter;
 
-use crate::db::{run_command, DbState};
+use crate::db::{run_command, DbState, RelationEvent};
+use crate::search::{get_particle, search_particles};
+
+const DEFAULT_SEARCH_LIMIT: usize = 20;
 
 #[derive(Deserialize)]
 struct RunCommandBody {
@@ -11,10 +19,36 @@ struct RunCommandBody {
     immutable: bool,
 }
 
-pub async fn start_server(state: Arc<DbState>) {
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ParticleQuery {
+    cid: String,
+}
+
+/// Client → server message on `/subscribe`, e.g.
+/// `{"action": "subscribe", "relations": ["sync_status", "community"]}`.
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum SubscriptionRequest {
+    Subscribe { relations: Vec<String> },
+    Unsubscribe { relations: Vec<String> },
+}
+
+pub async fn start_server(state: Arc<DbState>, port: u16) {
+    warp::serve(routes(state)).run(([127, 0, 0, 1], port)).await;
+}
+
+fn routes(
+    state: Arc<DbState>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     let cors = warp::cors()
         .allow_any_origin()
-        .allow_methods(vec!["POST"])
+        .allow_methods(vec!["GET", "POST"])
         .allow_headers(vec!["Content-Type"]);
 
     let run_command_route = warp::path("run_command")
@@ -36,7 +70,163 @@ pub async fn start_server(state: Arc<DbState>) {
             }
         });
 
-    let routes = run_command_route.with(cors);
+    let search_route = warp::path("search")
+        .and(warp::get())
+        .and(warp::query::<SearchQuery>())
+        .map({
+            let state = state.clone();
+            move |query: SearchQuery| {
+                let db = state.db.lock().unwrap();
+                let limit = query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+
+                match search_particles(&db, &query.q, limit) {
+                    Ok(results) => warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({ "ok": true, "results": results })),
+                        StatusCode::OK,
+                    ),
+                    Err(error) => warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({ "ok": false, "error": error })),
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                    ),
+                }
+            }
+        });
+
+    let particle_route = warp::path("particle")
+        .and(warp::get())
+        .and(warp::query::<ParticleQuery>())
+        .map({
+            let state = state.clone();
+            move |query: ParticleQuery| {
+                let db = state.db.lock().unwrap();
+
+                match get_particle(&db, &query.cid) {
+                    Ok(Some(particle)) => warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({ "ok": true, "particle": particle })),
+                        StatusCode::OK,
+                    ),
+                    Ok(None) => warp::reply::with_status(
+                        warp::reply::json(
+                            &serde_json::json!({ "ok": false, "error": "particle not found" }),
+                        ),
+                        StatusCode::NOT_FOUND,
+                    ),
+                    Err(error) => warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({ "ok": false, "error": error })),
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                    ),
+                }
+            }
+        });
+
+    let subscribe_route = warp::path("subscribe").and(warp::ws()).map({
+        let state = state.clone();
+        move |ws: Ws| {
+            let state = state.clone();
+            ws.on_upgrade(move |socket| handle_subscription(socket, state))
+        }
+    });
+
+    run_command_route
+        .or(search_route)
+        .or(particle_route)
+        .or(subscribe_route)
+        .with(cors)
+}
+
+/// Stream relation change events to one WebSocket client. Callbacks
+/// registered by this connection are dropped when it closes.
+async fn handle_subscription(socket: WebSocket, state: Arc<DbState>) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<RelationEvent>();
+    let mut callbacks: HashMap<String, u32> = HashMap::new();
+
+    loop {
+        tokio::select! {
+            msg = ws_rx.next() => {
+                let Some(Ok(msg)) = msg else { break };
+                if msg.is_close() {
+                    break;
+                }
+                let Ok(text) = msg.to_str() else { continue };
+
+                let reply = match serde_json::from_str::<SubscriptionRequest>(text) {
+                    Ok(SubscriptionRequest::Subscribe { relations }) => {
+                        for relation in relations {
+                            if let Entry::Vacant(slot) = callbacks.entry(relation) {
+                                let id = state.subscribe(slot.key(), event_tx.clone());
+                                slot.insert(id);
+                            }
+                        }
+                        serde_json::json!({ "ok": true, "subscribed": callbacks.keys().collect::<Vec<_>>() })
+                    }
+                    Ok(SubscriptionRequest::Unsubscribe { relations }) => {
+                        for relation in relations {
+                            if let Some(id) = callbacks.remove(&relation) {
+                                state.unsubscribe(id);
+                            }
+                        }
+                        serde_json::json!({ "ok": true, "subscribed": callbacks.keys().collect::<Vec<_>>() })
+                    }
+                    Err(e) => serde_json::json!({ "ok": false, "error": e.to_string() }),
+                };
+
+                if ws_tx.send(Message::text(reply.to_string())).await.is_err() {
+                    break;
+                }
+            }
+            Some(event) = event_rx.recv() => {
+                let payload = serde_json::json!({ "event": event }).to_string();
+                if ws_tx.send(Message::text(payload)).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    for id in callbacks.into_values() {
+        state.unsubscribe(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DbEngine;
+
+    #[tokio::test]
+    async fn test_particle_route() {
+        let state = Arc::new(DbState::with_engine(DbEngine::Mem).unwrap());
+        state.prepare().unwrap();
+        {
+            let mut db = state.db.lock().unwrap();
+            run_command(
+                &mut db,
+                "?[cid, mime, text, blocks, size, size_local, type] <- [['Qm1', 'text/plain', 'hello', 1, 5, 5, 'text']]
+                :put particle { cid => mime, text, blocks, size, size_local, type }",
+                false,
+            )
+            .unwrap();
+        }
+
+        let found = warp::test::request()
+            .path("/particle?cid=Qm1")
+            .reply(&routes(state.clone()))
+            .await;
+        assert_eq!(found.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_slice(found.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "ok": true,
+                "particle": { "cid": "Qm1", "mime": "text/plain", "text": "hello", "size": 5 }
+            })
+        );
 
-    warp::serve(routes).run(([127, 0, 0, 1], 3031)).await;
+        let missing = warp::test::request()
+            .path("/particle?cid=Qm2")
+            .reply(&routes(state))
+            .await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
 }
@@ -2,11 +2,16 @@ mod agent;
 mod shell;
 mod worlds;
 
+use bevy::log::LogPlugin;
 use bevy::prelude::*;
 use bevy::render::renderer::{RenderDevice, RenderQueue};
 use bevy::render::RenderApp;
 use agent::AgentPlugin;
+use shell::config::ConfigPlugin;
+use shell::env::prepare_process_env;
 use shell::hotkeys::HotkeysPlugin;
+use shell::logs::service_log_layer;
+use shell::services::ServicesPlugin;
 use shell::tray::TrayPlugin;
 use worlds::WorldsPlugin;
 use worlds::interface::InterfaceWorldPlugin;
@@ -34,16 +39,27 @@ impl Plugin for GpuBridgePlugin {
 }
 
 fn main() {
+    prepare_process_env();
+
     App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            primary_window: Some(Window {
-                title: "cyb".into(),
-                resolution: (1280u32, 800u32).into(),
-                ..default()
-            }),
-            ..default()
-        }))
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "cyb".into(),
+                        resolution: (1280u32, 800u32).into(),
+                        ..default()
+                    }),
+                    ..default()
+                })
+                .set(LogPlugin {
+                    custom_layer: service_log_layer,
+                    ..default()
+                }),
+        )
         .add_plugins(GpuBridgePlugin)
+        .add_plugins(ConfigPlugin)
+        .add_plugins(ServicesPlugin)
         .add_plugins(WorldsPlugin)
         .add_plugins(HotkeysPlugin)
         .add_plugins(InterfaceWorldPlugin)
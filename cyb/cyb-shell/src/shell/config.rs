@@ -0,0 +1,44 @@
+use bevy::prelude::*;
+use cyb_services::config::{CybConfig, ConfigWatcher, config_path};
+
+pub struct ConfigPlugin;
+
+/// Current `~/.cyb/config.toml`. Replaced on hot reload, so systems that
+/// support live changes watch it with `resource_changed::<Config>`.
+#[derive(Resource, Deref)]
+pub struct Config(pub CybConfig);
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        let config = match CybConfig::load() {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Config: {}, using defaults", e);
+                CybConfig::default()
+            }
+        };
+        app.insert_resource(Config(config));
+
+        match config_path().and_then(ConfigWatcher::spawn) {
+            Ok(watcher) => {
+                app.insert_non_send_resource(watcher)
+                    .add_systems(PreUpdate, reload_config);
+            }
+            Err(e) => warn!("Config hot reload disabled: {}", e),
+        }
+    }
+}
+
+fn reload_config(watcher: NonSend<ConfigWatcher>, mut config: ResMut<Config>) {
+    match watcher.poll() {
+        Some(Ok(new)) if new != config.0 => {
+            if new.services != config.services {
+                warn!("Config: [services] changes apply after restart");
+            }
+            info!("Config reloaded");
+            config.0 = new;
+        }
+        Some(Err(e)) => error!("Config not reloaded: {}", e),
+        _ => {}
+    }
+}
@@ -0,0 +1,45 @@
+//! Process environment fix-ups, applied before anything else runs.
+
+use std::path::PathBuf;
+
+/// Start in HOME with common tool directories on PATH. Apps launched from
+/// Finder or a DMG get `/` as their working directory and a minimal PATH;
+/// the services look up `ipfs` on PATH and every terminal engine inherits
+/// both.
+///
+/// Must run before any thread is spawned: setting variables races with
+/// other threads reading the environment.
+pub fn prepare_process_env() {
+    let home = home_dir();
+    let _ = std::env::set_current_dir(&home);
+
+    let current_path = std::env::var("PATH").unwrap_or_default();
+    let home_str = home.to_string_lossy();
+    let extra_paths = [
+        format!("{}/.cargo/bin", home_str),
+        "/opt/homebrew/bin".to_string(),
+        "/opt/homebrew/sbin".to_string(),
+        "/usr/local/bin".to_string(),
+        "/usr/local/sbin".to_string(),
+        format!("{}/.local/bin", home_str),
+        format!("{}/go/bin", home_str),
+        format!("{}/.deno/bin", home_str),
+    ];
+    let mut paths: Vec<&str> = extra_paths.iter().map(|s| s.as_str()).collect();
+    for p in current_path.split(':') {
+        if !paths.contains(&p) {
+            paths.push(p);
+        }
+    }
+    // SAFETY: called first thing in `main`, before Bevy or the services
+    // runtime start any threads
+    unsafe {
+        std::env::set_var("PATH", paths.join(":"));
+    }
+}
+
+pub fn home_dir() -> PathBuf {
+    std::env::var("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("/"))
+}
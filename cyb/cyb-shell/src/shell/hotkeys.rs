@@ -1,14 +1,14 @@
 use bevy::prelude::*;
-use global_hotkey::{
-    GlobalHotKeyEvent, GlobalHotKeyManager,
-    hotkey::{Code, HotKey, Modifiers},
-};
+use cyb_services::config::HotkeysConfig;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, hotkey::HotKey};
+use super::config::Config;
 use crate::worlds::WorldState;
 
 pub struct HotkeysPlugin;
 
 struct HotkeyManagerRes {
-    _manager: GlobalHotKeyManager,
+    manager: GlobalHotKeyManager,
+    registered: Vec<HotKey>,
     terminal_id: u32,
     portal_id: u32,
     legacy_id: u32,
@@ -18,33 +18,72 @@ struct HotkeyManagerRes {
 impl Plugin for HotkeysPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, register_hotkeys)
-            .add_systems(Update, poll_hotkey_events);
+            .add_systems(Update, (
+                poll_hotkey_events,
+                reload_hotkeys.run_if(resource_changed::<Config>),
+            ));
     }
 }
 
-fn register_hotkeys(world: &mut World) {
-    let manager = GlobalHotKeyManager::new().expect("Failed to create hotkey manager");
+/// Parse a configured hotkey, falling back to the default binding.
+fn parse_hotkey(name: &str, value: &str, default: &str) -> HotKey {
+    value.parse().unwrap_or_else(|e| {
+        warn!("Invalid hotkey {} = {:?} ({}), using {}", name, value, e, default);
+        default.parse().expect("default hotkey")
+    })
+}
 
-    let mods = Modifiers::SUPER;
-    let hk_terminal = HotKey::new(Some(mods), Code::Digit1);
-    let hk_portal = HotKey::new(Some(mods), Code::Digit2);
-    let hk_legacy = HotKey::new(Some(mods), Code::Digit3);
-    let hk_interface = HotKey::new(Some(mods), Code::Digit4);
+fn bind(manager: &GlobalHotKeyManager, config: &HotkeysConfig) -> (Vec<HotKey>, [u32; 4]) {
+    let defaults = HotkeysConfig::default();
+    let hotkeys = [
+        parse_hotkey("terminal", &config.terminal, &defaults.terminal),
+        parse_hotkey("portal", &config.portal, &defaults.portal),
+        parse_hotkey("legacy", &config.legacy, &defaults.legacy),
+        parse_hotkey("interface", &config.interface, &defaults.interface),
+    ];
 
-    manager.register(hk_terminal).expect("register Cmd+1");
-    manager.register(hk_portal).expect("register Cmd+2");
-    manager.register(hk_legacy).expect("register Cmd+3");
-    manager.register(hk_interface).expect("register Cmd+4");
+    let mut registered = Vec::new();
+    for hotkey in hotkeys {
+        match manager.register(hotkey) {
+            Ok(()) => registered.push(hotkey),
+            Err(e) => warn!("Failed to register hotkey {}: {}", hotkey, e),
+        }
+    }
+    (registered, hotkeys.map(|h| h.id()))
+}
+
+fn register_hotkeys(world: &mut World) {
+    let manager = GlobalHotKeyManager::new().expect("Failed to create hotkey manager");
+    let (registered, [terminal_id, portal_id, legacy_id, interface_id]) =
+        bind(&manager, &world.resource::<Config>().hotkeys);
 
     world.insert_non_send_resource(HotkeyManagerRes {
-        _manager: manager,
-        terminal_id: hk_terminal.id(),
-        portal_id: hk_portal.id(),
-        legacy_id: hk_legacy.id(),
-        interface_id: hk_interface.id(),
+        manager,
+        registered,
+        terminal_id,
+        portal_id,
+        legacy_id,
+        interface_id,
     });
 }
 
+fn reload_hotkeys(hotkeys: Option<NonSendMut<HotkeyManagerRes>>, config: Res<Config>) {
+    let Some(mut hotkeys) = hotkeys else { return };
+    if config.is_added() {
+        return;
+    }
+
+    let _ = hotkeys.manager.unregister_all(&hotkeys.registered);
+    let (registered, [terminal_id, portal_id, legacy_id, interface_id]) =
+        bind(&hotkeys.manager, &config.hotkeys);
+    hotkeys.registered = registered;
+    hotkeys.terminal_id = terminal_id;
+    hotkeys.portal_id = portal_id;
+    hotkeys.legacy_id = legacy_id;
+    hotkeys.interface_id = interface_id;
+    info!("Hotkeys reloaded");
+}
+
 fn poll_hotkey_events(
     hotkeys: NonSend<HotkeyManagerRes>,
     current_state: Res<State<WorldState>>,
@@ -0,0 +1,25 @@
+use bevy::log::BoxedLayer;
+use bevy::log::tracing_subscriber::Registry;
+use bevy::prelude::*;
+use cyb_services::CybConfig;
+use cyb_services::logging::{LogGuard, RecentLogs};
+
+/// Keeps the rotating log file open and exposes recent events to the worlds.
+#[derive(Resource)]
+pub struct ServiceLogs(LogGuard);
+
+impl ServiceLogs {
+    pub fn recent(&self) -> RecentLogs {
+        self.0.recent.clone()
+    }
+}
+
+/// `LogPlugin::custom_layer`: adds the `~/.cyb/logs` file and the recent-events
+/// buffer to Bevy's subscriber, so service and shell events share one sink.
+/// Runs before `ConfigPlugin`, so it reads the config file itself.
+pub fn service_log_layer(app: &mut App) -> Option<BoxedLayer> {
+    let config = CybConfig::load_or_default();
+    let (layer, guard) = cyb_services::logging::layers::<Registry>(&config.logging);
+    app.insert_resource(ServiceLogs(guard));
+    Some(layer)
+}
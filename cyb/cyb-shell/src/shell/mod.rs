@@ -1,2 +1,6 @@
+pub mod config;
+pub mod env;
 pub mod hotkeys;
+pub mod logs;
+pub mod services;
 pub mod tray;
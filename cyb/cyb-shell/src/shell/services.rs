@@ -0,0 +1,182 @@
+use bevy::prelude::*;
+#[cfg(unix)]
+use cyb_services::ServiceState;
+#[cfg(unix)]
+use cyb_services::control::{self, Request};
+use cyb_services::{CybServices, ServiceStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::config::Config;
+
+/// Grace period for services to stop when the app exits.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long startup waits for a running `cyb-servicesd` to answer.
+#[cfg(unix)]
+const ATTACH_TIMEOUT: Duration = Duration::from_millis(500);
+#[cfg(unix)]
+const DAEMON_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct ServicesPlugin;
+
+enum Backend {
+    /// Services run in-process and stop with the app
+    Local(Arc<CybServices>),
+    /// Attached to `cyb-servicesd`, which keeps running after the app exits;
+    /// holds the statuses last polled from its socket. Unix only.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Daemon(Arc<Mutex<Vec<ServiceStatus>>>),
+}
+
+/// IPFS, DB server, mining, etc., either running on a tokio runtime owned by
+/// the app or in an already-running `cyb-servicesd`.
+#[derive(Resource)]
+pub struct Services {
+    backend: Backend,
+    runtime: tokio::runtime::Runtime,
+    stopped: AtomicBool,
+}
+
+impl Services {
+    /// Daemon statuses are refreshed in the background every `DAEMON_POLL_INTERVAL`.
+    pub fn status(&self) -> Vec<ServiceStatus> {
+        match &self.backend {
+            Backend::Local(services) => services.status(),
+            Backend::Daemon(statuses) => statuses.lock().unwrap().clone(),
+        }
+    }
+
+    /// Stop in-process services, waiting up to `SHUTDOWN_TIMEOUT`. Runs once.
+    /// An attached daemon is left running.
+    pub fn shutdown(&self) {
+        if self.stopped.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let Backend::Local(services) = &self.backend else {
+            return;
+        };
+        info!("Stopping services");
+        let services = services.clone();
+        let result = self
+            .runtime
+            .block_on(async { tokio::time::timeout(SHUTDOWN_TIMEOUT, services.stop()).await });
+        if result.is_err() {
+            warn!("Services did not stop within {:?}", SHUTDOWN_TIMEOUT);
+        }
+    }
+}
+
+impl Drop for Services {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl Plugin for ServicesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreStartup, start_services)
+            .add_systems(Last, shutdown_on_exit);
+    }
+}
+
+fn start_services(mut commands: Commands, config: Res<Config>) {
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("cyb-services")
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            error!("Failed to create services runtime: {}", e);
+            return;
+        }
+    };
+
+    // cyb-servicesd's control socket is a Unix domain socket
+    #[cfg(unix)]
+    let attached = attach_daemon(&runtime, &config);
+    #[cfg(not(unix))]
+    let attached = None;
+    let backend = match attached {
+        Some(backend) => backend,
+        None => {
+            let services = Arc::new(CybServices::with_config(config.services.clone()));
+            runtime.spawn({
+                let services = services.clone();
+                async move { services.start().await }
+            });
+            info!("Services starting");
+            Backend::Local(services)
+        }
+    };
+
+    commands.insert_resource(Services {
+        backend,
+        runtime,
+        stopped: AtomicBool::new(false),
+    });
+}
+
+/// Use a `cyb-servicesd` that answers on the configured socket, if any.
+#[cfg(unix)]
+fn attach_daemon(runtime: &tokio::runtime::Runtime, config: &Config) -> Option<Backend> {
+    let daemon = &config.services.daemon;
+    if !daemon.attach {
+        return None;
+    }
+    let socket = control::socket_path(daemon).ok()?;
+    if !socket.exists() {
+        return None;
+    }
+
+    let reply = runtime.block_on(tokio::time::timeout(
+        ATTACH_TIMEOUT,
+        control::request(&socket, &Request::Status),
+    ));
+    let initial: Vec<ServiceStatus> = match reply {
+        Ok(Ok(value)) => serde_json::from_value(value).unwrap_or_default(),
+        Ok(Err(e)) => {
+            warn!("cyb-servicesd not usable ({}), starting services in-process", e);
+            return None;
+        }
+        Err(_) => {
+            warn!("cyb-servicesd did not answer, starting services in-process");
+            return None;
+        }
+    };
+    info!("Attached to cyb-servicesd at {}", socket.display());
+
+    let statuses = Arc::new(Mutex::new(initial));
+    runtime.spawn({
+        let statuses = statuses.clone();
+        async move {
+            loop {
+                tokio::time::sleep(DAEMON_POLL_INTERVAL).await;
+                let reply = control::request(&socket, &Request::Status).await;
+                let mut statuses = statuses.lock().unwrap();
+                match reply {
+                    Ok(value) => *statuses = serde_json::from_value(value).unwrap_or_default(),
+                    // Keep the entries so the tray shows the daemon is gone
+                    Err(e) => {
+                        for status in statuses.iter_mut() {
+                            status.state = ServiceState::Failed;
+                            status.health = None;
+                            status.last_error = Some(format!("cyb-servicesd unreachable: {}", e));
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    Some(Backend::Daemon(statuses))
+}
+
+fn shutdown_on_exit(mut exit: MessageReader<AppExit>, services: Option<Res<Services>>) {
+    if exit.read().next().is_some()
+        && let Some(services) = services
+    {
+        services.shutdown();
+    }
+}
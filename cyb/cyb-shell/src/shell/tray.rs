@@ -1,9 +1,13 @@
 use bevy::prelude::*;
+use cyb_services::{Health, ServiceState, ServiceStatus};
 use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
 use tray_icon::{Icon, TrayIconBuilder};
 
+use super::services::Services;
 use crate::worlds::WorldState;
 
+const SERVICE_REFRESH_SECS: f32 = 2.0;
+
 pub struct TrayPlugin;
 
 struct TrayState {
@@ -13,12 +17,14 @@ struct TrayState {
     legacy_id: String,
     interface_id: String,
     quit_id: String,
+    /// Read-only service state entries, by service name
+    service_items: Vec<(String, MenuItem)>,
 }
 
 impl Plugin for TrayPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, create_tray)
-            .add_systems(Update, poll_tray_events);
+            .add_systems(Update, (poll_tray_events, refresh_service_items));
     }
 }
 
@@ -43,6 +49,22 @@ fn create_tray(world: &mut World) {
     let _ = menu.append(&item_legacy);
     let _ = menu.append(&item_interface);
     let _ = menu.append(&separator);
+
+    let statuses = world
+        .get_resource::<Services>()
+        .map(Services::status)
+        .unwrap_or_default();
+    let service_items: Vec<(String, MenuItem)> = statuses
+        .iter()
+        .map(|status| (status.name.clone(), MenuItem::new(service_label(status), false, None)))
+        .collect();
+    for (_, item) in &service_items {
+        let _ = menu.append(item);
+    }
+    if !service_items.is_empty() {
+        let _ = menu.append(&PredefinedMenuItem::separator());
+    }
+
     let _ = menu.append(&item_quit);
 
     // 16x16 white square as placeholder icon
@@ -63,6 +85,7 @@ fn create_tray(world: &mut World) {
         legacy_id,
         interface_id,
         quit_id,
+        service_items,
     });
 
     info!("Tray icon created");
@@ -101,3 +124,39 @@ fn poll_tray_events(
         }
     }
 }
+
+fn service_label(status: &ServiceStatus) -> String {
+    let state = match (status.state, &status.health) {
+        (ServiceState::Running, Some(Health::Degraded(msg))) => format!("degraded ({})", msg),
+        (ServiceState::Running, _) => "running".to_string(),
+        (ServiceState::Starting, _) => "starting".to_string(),
+        (ServiceState::Stopped, _) => "stopped".to_string(),
+        (ServiceState::Failed, _) => match &status.last_error {
+            Some(e) => format!("failed ({})", e),
+            None => "failed".to_string(),
+        },
+    };
+    format!("{}: {}", status.name, state)
+}
+
+fn refresh_service_items(
+    tray: Option<NonSend<TrayState>>,
+    services: Option<Res<Services>>,
+    time: Res<Time>,
+    mut elapsed: Local<f32>,
+) {
+    *elapsed += time.delta_secs();
+    if *elapsed < SERVICE_REFRESH_SECS {
+        return;
+    }
+    *elapsed = 0.0;
+
+    let (Some(tray), Some(services)) = (tray, services) else {
+        return;
+    };
+    for status in services.status() {
+        if let Some((_, item)) = tray.service_items.iter().find(|(name, _)| *name == status.name) {
+            item.set_text(service_label(&status));
+        }
+    }
+}
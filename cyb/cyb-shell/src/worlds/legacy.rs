@@ -3,12 +3,15 @@ use bevy::window::PrimaryWindow;
 use bevy::winit::WINIT_WINDOWS;
 use wry::{Rect, WebView, WebViewBuilder};
 
-use super::WorldState;
+use super::{OpenRequests, WorldState};
+use crate::shell::config::Config;
 
 pub struct LegacyWorldPlugin;
 
 pub(crate) struct LegacyWebView {
     pub webview: WebView,
+    /// Configured URL the webview was opened with
+    home_url: String,
 }
 
 impl Plugin for LegacyWorldPlugin {
@@ -22,18 +25,39 @@ impl Plugin for LegacyWorldPlugin {
     }
 }
 
+fn legacy_url(world: &World) -> String {
+    let worlds = &world.resource::<Config>().worlds;
+    if cfg!(debug_assertions) {
+        worlds.legacy_dev_url.clone()
+    } else {
+        worlds.legacy_url.clone()
+    }
+}
+
 fn show_legacy(world: &mut World) {
-    if let Some(wv) = world.get_non_send_resource::<LegacyWebView>() {
+    let url = legacy_url(world);
+    let link = world.resource_mut::<OpenRequests>().legacy.take();
+    if let Some(mut wv) = world.get_non_send_resource_mut::<LegacyWebView>() {
+        if let Some(ref link) = link {
+            info!("Legacy loading {}", link);
+            let _ = wv.webview.load_url(link);
+            wv.home_url = url;
+        } else if wv.home_url != url {
+            info!("Legacy URL changed, loading {}", url);
+            let _ = wv.webview.load_url(&url);
+            wv.home_url = url;
+        }
         let _ = wv.webview.set_visible(true);
         update_legacy_bounds(world);
         info!("Legacy WebView shown (persisted)");
         return;
     }
 
-    create_legacy_webview(world);
+    create_legacy_webview(world, url, link);
 }
 
-fn create_legacy_webview(world: &mut World) {
+/// Open the webview on `link` if given, else on the configured `url`.
+fn create_legacy_webview(world: &mut World, url: String, link: Option<String>) {
     let primary_entity = world
         .query_filtered::<Entity, With<PrimaryWindow>>()
         .single(world);
@@ -46,15 +70,10 @@ fn create_legacy_webview(world: &mut World) {
         };
 
         let inner_size = window_wrapper.inner_size();
-
-        let url = if cfg!(debug_assertions) {
-            "https://localhost:3001".to_string()
-        } else {
-            "https://cyb.ai".to_string()
-        };
+        let start_url = link.as_deref().unwrap_or(&url);
 
         match WebViewBuilder::new()
-            .with_url(&url)
+            .with_url(start_url)
             .with_bounds(Rect {
                 position: wry::dpi::PhysicalPosition::new(0, 0).into(),
                 size: wry::dpi::PhysicalSize::new(inner_size.width, inner_size.height).into(),
@@ -63,7 +82,7 @@ fn create_legacy_webview(world: &mut World) {
             .build_as_child(&**window_wrapper)
         {
             Ok(webview) => {
-                info!("Legacy world created, loading {}", url);
+                info!("Legacy world created, loading {}", start_url);
                 Some(webview)
             }
             Err(e) => {
@@ -74,7 +93,7 @@ fn create_legacy_webview(world: &mut World) {
     });
 
     if let Some(webview) = result {
-        world.insert_non_send_resource(LegacyWebView { webview });
+        world.insert_non_send_resource(LegacyWebView { webview, home_url: url });
     }
 }
 
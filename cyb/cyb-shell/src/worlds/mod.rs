@@ -14,10 +14,21 @@ pub enum WorldState {
     Interface, // Cmd+4 (Bevy 3D/2D)
 }
 
+/// Something for a world to show when it is entered next, such as a link
+/// opened from the terminal.
+#[derive(Resource, Default)]
+pub struct OpenRequests {
+    /// URL to load in the Legacy webview
+    pub legacy: Option<String>,
+    /// Particle (IPFS CID) to show in the Portal
+    pub particle: Option<String>,
+}
+
 pub struct WorldsPlugin;
 
 impl Plugin for WorldsPlugin {
     fn build(&self, app: &mut App) {
-        app.init_state::<WorldState>();
+        app.init_state::<WorldState>()
+            .init_resource::<OpenRequests>();
     }
 }
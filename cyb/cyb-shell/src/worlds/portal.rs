@@ -6,14 +6,23 @@ use bevy::window::PrimaryWindow;
 use bevy::winit::WINIT_WINDOWS;
 use wry::{http, Rect, WebView, WebViewBuilder};
 
-use super::WorldState;
+use super::{OpenRequests, WorldState};
+use crate::shell::config::Config;
 
 pub struct PortalWorldPlugin;
 
 struct PortalWebView {
     webview: WebView,
+    /// Scheme and host the app is served from
+    origin: &'static str,
 }
 
+/// Dev fallback when `cyb-portal/dist` hasn't been built.
+const TRUNK_ORIGIN: &str = "http://localhost:8090";
+const PROTOCOL_ORIGIN: &str = "portal://localhost";
+/// Global the app reads the DB server's URL from.
+const SERVICES_URL_GLOBAL: &str = "cybServicesUrl";
+
 impl Plugin for PortalWorldPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(WorldState::Portal), show_portal)
@@ -26,14 +35,23 @@ impl Plugin for PortalWorldPlugin {
 }
 
 fn show_portal(world: &mut World) {
+    let route = world
+        .resource_mut::<OpenRequests>()
+        .particle
+        .take()
+        .map(|cid| format!("/particles?cid={}", cid));
     if let Some(wv) = world.get_non_send_resource::<PortalWebView>() {
+        if let Some(ref route) = route {
+            info!("Portal loading {}", route);
+            let _ = wv.webview.load_url(&format!("{}{}", wv.origin, route));
+        }
         let _ = wv.webview.set_visible(true);
         update_portal_bounds(world);
         info!("Portal WebView shown (persisted)");
         return;
     }
 
-    create_portal_webview(world);
+    create_portal_webview(world, route);
 }
 
 fn portal_dist_dir() -> PathBuf {
@@ -76,12 +94,18 @@ fn mime_from_path(path: &str) -> &'static str {
     }
 }
 
-fn create_portal_webview(world: &mut World) {
+/// Open the app on `route` (a path and query in the app), or its index.
+fn create_portal_webview(world: &mut World, route: Option<String>) {
     let primary_entity = world
         .query_filtered::<Entity, With<PrimaryWindow>>()
         .single(world);
     let Ok(entity) = primary_entity else { return };
 
+    // The app runs on another origin than the DB server, so it is told the
+    // configured port before any of its scripts run
+    let db_port = world.resource::<Config>().services.db.port;
+    let init_script = format!("window.{} = \"http://127.0.0.1:{}\";", SERVICES_URL_GLOBAL, db_port);
+
     let created = WINIT_WINDOWS.with(|ww| {
         let ww = ww.borrow();
         let Some(window_wrapper) = ww.get_window(entity) else {
@@ -93,9 +117,10 @@ fn create_portal_webview(world: &mut World) {
 
         // If no dist dir exists in dev mode, fall back to trunk serve
         if dist_dir.as_os_str().is_empty() {
-            info!("Portal: no dist/, falling back to http://localhost:8090");
+            info!("Portal: no dist/, falling back to {}", TRUNK_ORIGIN);
             return match WebViewBuilder::new()
-                .with_url("http://localhost:8090")
+                .with_url(format!("{}{}", TRUNK_ORIGIN, route.as_deref().unwrap_or("")))
+                .with_initialization_script(init_script.as_str())
                 .with_bounds(Rect {
                     position: wry::dpi::PhysicalPosition::new(0, 0).into(),
                     size: wry::dpi::PhysicalSize::new(inner_size.width, inner_size.height).into(),
@@ -103,7 +128,7 @@ fn create_portal_webview(world: &mut World) {
                 .with_devtools(cfg!(debug_assertions))
                 .build_as_child(&**window_wrapper)
             {
-                Ok(webview) => Some(webview),
+                Ok(webview) => Some((webview, TRUNK_ORIGIN)),
                 Err(e) => {
                     warn!("Failed to create Portal WebView: {}", e);
                     None
@@ -122,6 +147,14 @@ fn create_portal_webview(world: &mut World) {
                     uri_path.trim_start_matches('/').to_string()
                 };
 
+                // App routes (`/particles`) have no file of their own; the
+                // index serves them and the router takes over
+                let path = if dist.join(&path).is_file() || path.contains('.') {
+                    path
+                } else {
+                    "index.html".to_string()
+                };
+
                 let file_path = dist.join(&path);
                 match std::fs::read(&file_path) {
                     Ok(content) => {
@@ -141,7 +174,8 @@ fn create_portal_webview(world: &mut World) {
                     }
                 }
             })
-            .with_url("portal://localhost/index.html")
+            .with_url(format!("{}{}", PROTOCOL_ORIGIN, route.as_deref().unwrap_or("/index.html")))
+            .with_initialization_script(init_script.as_str())
             .with_bounds(Rect {
                 position: wry::dpi::PhysicalPosition::new(0, 0).into(),
                 size: wry::dpi::PhysicalSize::new(inner_size.width, inner_size.height).into(),
@@ -154,7 +188,7 @@ fn create_portal_webview(world: &mut World) {
         {
             Ok(webview) => {
                 info!("Portal WebView created (custom protocol), dist={}", dist_dir.display());
-                Some(webview)
+                Some((webview, PROTOCOL_ORIGIN))
             }
             Err(e) => {
                 warn!("Failed to create Portal WebView: {}", e);
@@ -163,8 +197,8 @@ fn create_portal_webview(world: &mut World) {
         }
     });
 
-    if let Some(webview) = created {
-        world.insert_non_send_resource(PortalWebView { webview });
+    if let Some((webview, origin)) = created {
+        world.insert_non_send_resource(PortalWebView { webview, origin });
     }
 }
 
@@ -0,0 +1,164 @@
+//! Nushell commands exposing cyb itself, registered into the embedded engine.
+
+use chrono::{FixedOffset, TimeZone, Utc};
+use cyb_services::logging::RecentLogs;
+use nu_engine::CallExt;
+use nu_protocol::engine::{Call, Command, EngineState, Stack, StateWorkingSet};
+use nu_protocol::{
+    Category, IntoPipelineData, PipelineData, ShellError, Signature, SyntaxShape, Type, Value, record,
+};
+
+use super::theme::{self, ThemeSwitch};
+
+const DEFAULT_LOG_LINES: i64 = 50;
+/// Target prefix of service events; `--all` also shows the shell's own.
+const SERVICES_TARGET: &str = "cyb_services";
+
+/// Add the `cyb ...` commands to `engine_state`.
+pub(super) fn add_cyb_commands(engine_state: &mut EngineState, logs: Option<RecentLogs>, themes: ThemeSwitch) {
+    let delta = {
+        let mut working_set = StateWorkingSet::new(engine_state);
+        if let Some(logs) = logs {
+            working_set.add_decl(Box::new(CybLogs { logs }));
+        }
+        working_set.add_decl(Box::new(CybTheme { themes }));
+        working_set.render()
+    };
+
+    if let Err(e) = engine_state.merge_delta(delta) {
+        bevy::log::warn!("Failed to register cyb commands: {:?}", e);
+    }
+}
+
+/// `cyb logs`: recent service log events as a table.
+#[derive(Clone)]
+struct CybLogs {
+    logs: RecentLogs,
+}
+
+impl Command for CybLogs {
+    fn name(&self) -> &str {
+        "cyb logs"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .input_output_types(vec![(Type::Nothing, Type::table())])
+            .named("lines", SyntaxShape::Int, "number of events to show (default 50)", Some('n'))
+            .named(
+                "target",
+                SyntaxShape::String,
+                "only events whose target starts with this, e.g. cyb_services::ipfs",
+                Some('t'),
+            )
+            .switch("all", "include events from the shell, not only services", Some('a'))
+            .category(Category::Custom("cyb".into()))
+    }
+
+    fn description(&self) -> &str {
+        "Show recent cyb-services log events."
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        _input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        let head = call.head;
+        let lines: Option<i64> = call.get_flag(engine_state, stack, "lines")?;
+        let target: Option<String> = call.get_flag(engine_state, stack, "target")?;
+        let all = call.has_flag(engine_state, stack, "all")?;
+
+        let target = target.or_else(|| (!all).then(|| SERVICES_TARGET.to_string()));
+        let count = lines.unwrap_or(DEFAULT_LOG_LINES).max(0) as usize;
+
+        let rows = self
+            .logs
+            .tail(count, target.as_deref())
+            .into_iter()
+            .map(|line| {
+                let time = Utc
+                    .timestamp_millis_opt(line.timestamp as i64)
+                    .single()
+                    .unwrap_or_default()
+                    .with_timezone(&FixedOffset::east_opt(0).expect("UTC offset"));
+                Value::record(
+                    record! {
+                        "time" => Value::date(time, head),
+                        "level" => Value::string(line.level, head),
+                        "target" => Value::string(line.target, head),
+                        "message" => Value::string(line.message, head),
+                    },
+                    head,
+                )
+            })
+            .collect();
+
+        Ok(Value::list(rows, head).into_pipeline_data())
+    }
+}
+
+/// `cyb theme`: list the terminal themes, or switch to one.
+#[derive(Clone)]
+struct CybTheme {
+    themes: ThemeSwitch,
+}
+
+impl Command for CybTheme {
+    fn name(&self) -> &str {
+        "cyb theme"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(self.name())
+            .input_output_types(vec![(Type::Nothing, Type::table()), (Type::Nothing, Type::Nothing)])
+            .optional("name", SyntaxShape::String, "theme to switch to; lists the themes when left out")
+            .category(Category::Custom("cyb".into()))
+    }
+
+    fn description(&self) -> &str {
+        "List the terminal color themes, or switch to one until the config's theme changes."
+    }
+
+    fn run(
+        &self,
+        engine_state: &EngineState,
+        stack: &mut Stack,
+        call: &Call,
+        _input: PipelineData,
+    ) -> Result<PipelineData, ShellError> {
+        let head = call.head;
+        let name: Option<String> = call.opt(engine_state, stack, 0)?;
+
+        if let Some(name) = name {
+            theme::load(&name).map_err(|msg| ShellError::GenericError {
+                error: "Can't switch theme".into(),
+                msg,
+                span: Some(head),
+                help: Some("`cyb theme` lists the available themes".into()),
+                inner: vec![],
+            })?;
+            self.themes.request(name);
+            return Ok(PipelineData::empty());
+        }
+
+        let active = self.themes.active();
+        let rows = theme::available()
+            .into_iter()
+            .map(|(name, source)| {
+                Value::record(
+                    record! {
+                        "active" => Value::bool(name == active, head),
+                        "name" => Value::string(name, head),
+                        "source" => Value::string(source, head),
+                    },
+                    head,
+                )
+            })
+            .collect();
+
+        Ok(Value::list(rows, head).into_pipeline_data())
+    }
+}
@@ -0,0 +1,207 @@
+//! Tab completion: candidates from nu-cli's `NuCompleter` against the live
+//! engine (commands, flags, paths, variables, custom completers), shown as
+//! a columnar menu under the input.
+
+use nu_cli::NuCompleter;
+use reedline::{Completer, Suggestion};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::EngineSnapshot;
+
+/// Rows of candidates shown at once; the menu scrolls to keep the
+/// selection visible.
+const MAX_ROWS: usize = 8;
+
+/// Candidates for the token at `pos`. Case sensitivity and matching
+/// algorithm come from `$env.config.completions`.
+pub(super) fn complete(engine: &EngineSnapshot, line: &str, pos: usize) -> Vec<Suggestion> {
+    let mut completer = NuCompleter::new(engine.engine_state.clone(), engine.stack.clone());
+    completer.complete(line, pos)
+}
+
+/// Longest prefix shared by every candidate, when they all replace the same
+/// span.
+pub(super) fn common_prefix(suggestions: &[Suggestion]) -> Option<&str> {
+    let (first, rest) = suggestions.split_first()?;
+    if rest.iter().any(|s| s.span != first.span) {
+        return None;
+    }
+    let mut len = first.value.len();
+    for s in rest {
+        len = first.value[..len]
+            .char_indices()
+            .zip(s.value.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(len.min(s.value.len()), |((i, _), _)| i);
+    }
+    Some(&first.value[..len])
+}
+
+pub(super) struct Menu {
+    suggestions: Vec<Suggestion>,
+    selected: usize,
+    /// Candidates per row at the last render; Up/Down move by this much
+    columns: usize,
+    first_row: usize,
+}
+
+impl Menu {
+    pub fn new(suggestions: Vec<Suggestion>) -> Self {
+        Self {
+            suggestions,
+            selected: 0,
+            columns: 1,
+            first_row: 0,
+        }
+    }
+
+    pub fn selected(&self) -> &Suggestion {
+        &self.suggestions[self.selected]
+    }
+
+    /// Move the selection by `delta` candidates, wrapping around.
+    pub fn step(&mut self, delta: isize) {
+        let len = self.suggestions.len() as isize;
+        self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+    }
+
+    /// Move the selection a row up or down.
+    pub fn step_row(&mut self, down: bool) {
+        let columns = self.columns as isize;
+        self.step(if down { columns } else { -columns });
+    }
+
+    /// Menu lines for a grid `cols` wide, each fitting on one row.
+    pub fn render(&mut self, cols: usize) -> Vec<String> {
+        let cols = cols.max(2);
+        let widest = self.suggestions.iter().map(|s| s.value.width()).max().unwrap_or(0);
+        let col_width = (widest + 2).min(cols);
+        self.columns = (cols / col_width).max(1);
+
+        let rows = self.suggestions.len().div_ceil(self.columns);
+        let selected_row = self.selected / self.columns;
+        if selected_row < self.first_row {
+            self.first_row = selected_row;
+        } else if selected_row >= self.first_row + MAX_ROWS {
+            self.first_row = selected_row + 1 - MAX_ROWS;
+        }
+
+        let mut lines = Vec::new();
+        for row in self.first_row..rows.min(self.first_row + MAX_ROWS) {
+            let mut line = String::new();
+            for col in 0..self.columns {
+                let index = row * self.columns + col;
+                let Some(suggestion) = self.suggestions.get(index) else {
+                    break;
+                };
+                let text = truncate(&suggestion.value, col_width - 1);
+                let style = if index == self.selected { "\x1b[7m" } else { "\x1b[32m" };
+                let pad = col_width - text.width();
+                line.push_str(&format!("{}{}\x1b[0m{}", style, text, " ".repeat(pad)));
+            }
+            lines.push(line);
+        }
+
+        let mut footer = self.selected().description.clone().unwrap_or_default();
+        if rows > MAX_ROWS {
+            footer.push_str(&format!(" ({}/{})", self.selected + 1, self.suggestions.len()));
+        }
+        let footer = truncate(footer.trim(), cols - 1);
+        if !footer.is_empty() {
+            lines.push(format!("\x1b[2m{}\x1b[0m", footer));
+        }
+        lines
+    }
+}
+
+/// Longest prefix of `text` at most `width` columns wide.
+pub(super) fn truncate(text: &str, width: usize) -> String {
+    let mut used = 0;
+    text.chars()
+        .take_while(|c| {
+            used += c.width().unwrap_or(0);
+            used <= width
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reedline::Span;
+
+    fn suggestion(value: &str, span: Span) -> Suggestion {
+        Suggestion {
+            value: value.to_string(),
+            span,
+            ..Default::default()
+        }
+    }
+
+    fn menu(values: &[&str]) -> Menu {
+        Menu::new(values.iter().map(|v| suggestion(v, Span::new(0, 0))).collect())
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let span = Span::new(0, 2);
+        let prefix = |values: &[&str]| {
+            let suggestions: Vec<_> = values.iter().map(|v| suggestion(v, span)).collect();
+            common_prefix(&suggestions).map(str::to_string)
+        };
+        assert_eq!(prefix(&["abc", "ab"]).as_deref(), Some("ab"));
+        assert_eq!(prefix(&["ab", "abc"]).as_deref(), Some("ab"));
+        assert_eq!(prefix(&["héllo", "hélp"]).as_deref(), Some("hél"));
+        // Same first byte, different characters
+        assert_eq!(prefix(&["é", "è"]).as_deref(), Some(""));
+        assert_eq!(prefix(&[]), None);
+
+        // Candidates replacing different spans share nothing to insert
+        let mixed = [suggestion("foo", span), suggestion("foobar", Span::new(1, 2))];
+        assert_eq!(common_prefix(&mixed), None);
+    }
+
+    #[test]
+    fn test_step_row_wraps() {
+        let mut menu = menu(&["a", "b", "c", "d", "e"]);
+        // Three columns of width 3 fit in 9
+        menu.render(9);
+        menu.step_row(true);
+        assert_eq!(menu.selected().value, "d");
+        menu.step_row(true);
+        assert_eq!(menu.selected().value, "b");
+        menu.step_row(false);
+        assert_eq!(menu.selected().value, "d");
+    }
+
+    #[test]
+    fn test_render_scrolls_past_max_rows() {
+        let values: Vec<String> = (0..20).map(|i| format!("s{:02}", i)).collect();
+        let mut menu = menu(&values.iter().map(String::as_str).collect::<Vec<_>>());
+
+        // Two columns of width 5 in 10, so ten rows
+        menu.step(-1);
+        let lines = menu.render(10);
+        assert_eq!(lines.len(), MAX_ROWS + 1);
+        assert!(lines[0].contains("s04") && lines[0].contains("s05"));
+        assert!(lines[MAX_ROWS - 1].contains("\x1b[7ms19"));
+        assert_eq!(lines[MAX_ROWS], "\x1b[2m(20/20)\x1b[0m");
+
+        menu.step(1);
+        let lines = menu.render(10);
+        assert!(lines[0].starts_with("\x1b[7ms00"));
+        assert_eq!(lines[MAX_ROWS], "\x1b[2m(1/20)\x1b[0m");
+    }
+
+    #[test]
+    fn test_render_truncates_to_width() {
+        let mut menu = Menu::new(vec![Suggestion {
+            description: Some("a long description".to_string()),
+            ..suggestion("abcdefghijkl", Span::new(0, 0))
+        }]);
+        assert_eq!(menu.render(6), vec!["\x1b[7mabcde\x1b[0m ", "\x1b[2ma lon\x1b[0m"]);
+
+        assert_eq!(truncate("日本語", 5), "日本");
+        assert_eq!(truncate("日本語", 1), "");
+    }
+}
@@ -0,0 +1,1779 @@
+//! Line editor for the nushell prompt: emacs and vi keymaps over reedline's
+//! `LineBuffer`, with a kill ring and undo. Every change repaints the prompt
+//! and the whole input, so mid-line edits and soft-wrapped lines stay correct.
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::ops::Range;
+
+use bevy::input::keyboard::{Key, KeyCode, KeyboardInput};
+use nu_protocol::Config;
+use nu_protocol::config::{EditBindings, NuCursorShape};
+use reedline::{HistoryItem, LineBuffer, SearchDirection, Suggestion};
+use unicode_width::UnicodeWidthChar;
+
+use super::completion::{self, Menu};
+use super::history::{CommandHistory, Matching};
+use super::pty::Modifiers;
+
+const KILL_RING_SIZE: usize = 16;
+const UNDO_DEPTH: usize = 100;
+
+/// What the terminal should do after a key press.
+pub(super) enum EditAction {
+    /// Key not handled by the editor
+    None,
+    /// Buffer, cursor or mode changed; repaint with [`LineEditor::render`]
+    Redraw,
+    /// Enter: run the line (see [`LineEditor::finish`])
+    Submit,
+    /// Ctrl+C: abandon the line
+    Cancel,
+    /// Ctrl+L: clear the screen and repaint
+    ClearScreen,
+    /// Tab: fetch candidates for [`LineEditor::show_completions`]
+    Complete,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ViMode {
+    Normal,
+    Insert,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+/// `f`/`F`/`t`/`T`.
+#[derive(Clone, Copy)]
+struct Find {
+    forward: bool,
+    till: bool,
+}
+
+/// Vi command waiting for its next key.
+#[derive(Clone, Copy)]
+enum Pending {
+    Operator(Operator),
+    Find(Find, Option<Operator>),
+    Replace,
+}
+
+/// How the previous key changed the buffer: runs of typing undo together,
+/// consecutive kills join in the kill ring, and Alt+Y replaces the last yank.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LastEdit {
+    Other,
+    Insert,
+    Kill,
+    Yank { start: usize, end: usize },
+}
+
+/// Emacs-style kill ring; vi deletes and yanks share it as their register.
+#[derive(Default)]
+struct KillRing {
+    entries: VecDeque<String>,
+    yank_index: usize,
+}
+
+impl KillRing {
+    /// `join` appends to (or, killing backwards, prepends to) the last entry.
+    fn kill(&mut self, text: &str, join: bool, backwards: bool) {
+        match self.entries.front_mut() {
+            Some(last) if join => {
+                if backwards {
+                    last.insert_str(0, text);
+                } else {
+                    last.push_str(text);
+                }
+            }
+            _ => {
+                self.entries.push_front(text.to_string());
+                self.entries.truncate(KILL_RING_SIZE);
+            }
+        }
+        self.yank_index = 0;
+    }
+
+    fn yank(&mut self) -> Option<String> {
+        self.yank_index = 0;
+        self.entries.front().cloned()
+    }
+
+    /// Next older entry, for Alt+Y after a yank.
+    fn rotate(&mut self) -> Option<String> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.yank_index = (self.yank_index + 1) % self.entries.len();
+        self.entries.get(self.yank_index).cloned()
+    }
+}
+
+/// A vi change for `.`: its normal-mode keys and, when they opened insert
+/// mode, the text typed before Escape.
+#[derive(Clone)]
+struct Change {
+    keys: String,
+    inserted: String,
+}
+
+/// `DECSCUSR` sequences for the configured `cursor_shape`s.
+#[derive(Clone, Copy)]
+struct CursorShapes {
+    emacs: &'static str,
+    vi_insert: &'static str,
+    vi_normal: &'static str,
+}
+
+impl Default for CursorShapes {
+    fn default() -> Self {
+        Self {
+            emacs: DEFAULT_CURSOR,
+            vi_insert: DEFAULT_CURSOR,
+            vi_normal: DEFAULT_CURSOR,
+        }
+    }
+}
+
+const DEFAULT_CURSOR: &str = "\x1b[0 q";
+
+fn decscusr(shape: NuCursorShape) -> &'static str {
+    match shape {
+        NuCursorShape::BlinkBlock => "\x1b[1 q",
+        NuCursorShape::Block => "\x1b[2 q",
+        NuCursorShape::BlinkUnderscore => "\x1b[3 q",
+        NuCursorShape::Underscore => "\x1b[4 q",
+        NuCursorShape::BlinkLine => "\x1b[5 q",
+        NuCursorShape::Line => "\x1b[6 q",
+        NuCursorShape::Inherit => DEFAULT_CURSOR,
+    }
+}
+
+/// Ctrl+R reverse incremental search through history.
+struct Search {
+    text: String,
+    /// Entry shown in the buffer
+    found: Option<HistoryItem>,
+    /// No older entry contains `text`
+    failing: bool,
+    /// Line and cursor before the search, restored by Escape or Ctrl+G
+    original: (String, usize),
+}
+
+impl Search {
+    /// Status line painted under the input.
+    fn status(&self, cols: usize) -> String {
+        let label = if self.failing { "failing reverse-i-search" } else { "reverse-i-search" };
+        let text = completion::truncate(&self.text, cols.saturating_sub(label.len() + 4));
+        format!("\x1b[2m({})\x1b[0m {}", label, text)
+    }
+}
+
+/// Prompt text from `$env.PROMPT_*`, evaluated before each line.
+#[derive(Clone, Default)]
+pub(super) struct Prompt {
+    /// `PROMPT_COMMAND`
+    pub command: String,
+    /// `PROMPT_INDICATOR`, right after the command
+    pub indicator: String,
+    /// `PROMPT_COMMAND_RIGHT`, ending at the last column of the first row
+    pub right: String,
+    /// `PROMPT_MULTILINE_INDICATOR`, before each continuation line
+    pub multiline: String,
+}
+
+pub(super) struct LineEditor {
+    line: LineBuffer,
+    /// `None` in emacs mode
+    vi: Option<ViMode>,
+    pending: Option<Pending>,
+    count: Option<usize>,
+    last_find: Option<(Find, char)>,
+    kill_ring: KillRing,
+    last_edit: LastEdit,
+    /// `last_edit` as it was before the key being handled
+    previous: LastEdit,
+    undo: Vec<(String, usize)>,
+    /// A vi insert session is open; its typing undoes as one change
+    insert_group: bool,
+    /// Keys of the vi command being typed
+    command: String,
+    /// The vi command being typed changed the buffer
+    edited: bool,
+    /// Change whose insert session is open, and where its typing started
+    recording: Option<(Change, usize)>,
+    /// Last vi change, repeated by `.`
+    last_change: Option<Change>,
+    history: CommandHistory,
+    /// Entry shown while browsing with Up/Down
+    history_entry: Option<HistoryItem>,
+    /// Line being typed before browsing history; Up/Down only show entries
+    /// starting with it
+    draft: String,
+    shapes: CursorShapes,
+    /// Open completion menu
+    menu: Option<Menu>,
+    /// Open Ctrl+R search
+    search: Option<Search>,
+    /// Rows between the first prompt row and the cursor, as last rendered
+    cursor_row: usize,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self {
+            line: LineBuffer::new(),
+            vi: None,
+            pending: None,
+            count: None,
+            last_find: None,
+            kill_ring: KillRing::default(),
+            last_edit: LastEdit::Other,
+            previous: LastEdit::Other,
+            undo: Vec::new(),
+            insert_group: false,
+            command: String::new(),
+            edited: false,
+            recording: None,
+            last_change: None,
+            history: CommandHistory::in_memory(),
+            history_entry: None,
+            draft: String::new(),
+            shapes: CursorShapes::default(),
+            menu: None,
+            search: None,
+            cursor_row: 0,
+        }
+    }
+
+    /// Pick up `edit_mode` and `cursor_shape`, which `$env.config` can change
+    /// between commands.
+    pub fn configure(&mut self, config: &Config) {
+        let vi = matches!(config.edit_mode, EditBindings::Vi);
+        if vi != self.vi.is_some() {
+            self.vi = vi.then_some(ViMode::Insert);
+            self.pending = None;
+            self.count = None;
+        }
+        self.shapes = CursorShapes {
+            emacs: decscusr(config.cursor_shape.emacs),
+            vi_insert: decscusr(config.cursor_shape.vi_insert),
+            vi_normal: decscusr(config.cursor_shape.vi_normal),
+        };
+    }
+
+    pub fn buffer(&self) -> &str {
+        self.line.get_buffer()
+    }
+
+    pub fn insertion_point(&self) -> usize {
+        self.line.insertion_point()
+    }
+
+    /// Replace the in-memory history with the configured one.
+    pub fn set_history(&mut self, history: CommandHistory) {
+        self.history = history;
+    }
+
+    /// For recording commands as they run and finish.
+    pub fn history_mut(&mut self) -> &mut CommandHistory {
+        &mut self.history
+    }
+
+    /// Enter on incomplete input: continue it on a new line.
+    pub fn insert_newline(&mut self) -> EditAction {
+        self.edit(LastEdit::Other, |line| line.insert_newline())
+    }
+
+    /// Insert clipboard text at the cursor as one undoable change. Line
+    /// breaks stay in the buffer as multiline input instead of submitting.
+    pub fn paste(&mut self, text: &str) -> EditAction {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.menu = None;
+        self.search = None;
+        self.previous = LastEdit::Other;
+        let action = self.edit(LastEdit::Other, |line| line.insert_str(&text));
+        if self.vi == Some(ViMode::Normal) {
+            self.clamp_normal();
+        }
+        action
+    }
+
+    /// Take the submitted line and start over.
+    pub fn take_line(&mut self) -> String {
+        let line = self.line.get_buffer().to_string();
+        self.clear();
+        line
+    }
+
+    /// Drop the line and start over in insert mode.
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.undo.clear();
+        self.insert_group = false;
+        self.recording = None;
+        self.last_edit = LastEdit::Other;
+        self.history_entry = None;
+        self.draft.clear();
+        self.pending = None;
+        self.count = None;
+        self.menu = None;
+        self.search = None;
+        if self.vi.is_some() {
+            self.vi = Some(ViMode::Insert);
+        }
+    }
+
+    // --- Rendering ---
+
+    /// Forget the previous render; the next one starts on the cursor's row.
+    pub fn reset_render(&mut self) {
+        self.cursor_row = 0;
+    }
+
+    /// Bytes that repaint `prompt` and the input from the prompt's first row
+    /// and leave the cursor at the insertion point. `styled` is the buffer
+    /// with highlighting added; it must print the same characters. `cols` is
+    /// the grid width, used to follow soft wraps.
+    pub fn render(&mut self, prompt: &Prompt, styled: &str, cols: usize) -> Vec<u8> {
+        let mut out = self.erase();
+        out.extend_from_slice(self.cursor_style().as_bytes());
+
+        let left = format!("{}{}", prompt.command, prompt.indicator);
+        let buffer = self.line.get_buffer();
+        if let Some(column) = right_prompt_column(&left, buffer, &prompt.right, cols) {
+            let _ = write!(out, "\x1b[{}G{}\x1b[0m\r", column + 1, prompt.right);
+        }
+
+        let mut layout = Layout::new(cols);
+        layout.advance(&left);
+        push_crlf(&mut out, &left);
+
+        // Continuation lines start with the multiline indicator
+        let point = self.line.insertion_point();
+        let mut cursor = layout.position();
+        let mut start = 0;
+        for (i, (line, styled_line)) in buffer.split('\n').zip(styled.split('\n')).enumerate() {
+            if i > 0 {
+                layout.advance("\n");
+                layout.advance(&prompt.multiline);
+                out.extend_from_slice(b"\x1b[0m\r\n");
+                push_crlf(&mut out, &prompt.multiline);
+            }
+            let end = start + line.len();
+            if (start..=end).contains(&point) {
+                layout.advance(&line[..point - start]);
+                cursor = layout.position();
+                layout.advance(&line[point - start..]);
+            } else {
+                layout.advance(line);
+            }
+            out.extend_from_slice(styled_line.as_bytes());
+            start = end + 1;
+        }
+        out.extend_from_slice(b"\x1b[0m");
+
+        // Text that exactly fills the last row leaves the terminal's cursor
+        // waiting to wrap; move it down so rows match the layout
+        if layout.col >= layout.cols {
+            out.extend_from_slice(b"\r\n");
+        }
+        let mut end = layout.position();
+
+        let lines = match (&mut self.menu, &self.search) {
+            (Some(menu), _) => menu.render(cols),
+            (None, Some(search)) => vec![search.status(cols)],
+            (None, None) => Vec::new(),
+        };
+        for line in lines {
+            if end.1 > 0 {
+                out.extend_from_slice(b"\r\n");
+                end.0 += 1;
+            }
+            out.extend_from_slice(line.as_bytes());
+            end.1 = 1;
+        }
+        if end != cursor {
+            if end.0 > cursor.0 {
+                let _ = write!(out, "\x1b[{}A", end.0 - cursor.0);
+            }
+            out.push(b'\r');
+            if cursor.1 > 0 {
+                let _ = write!(out, "\x1b[{}C", cursor.1);
+            }
+        }
+        self.cursor_row = cursor.0;
+        out
+    }
+
+    /// Bytes that clear the prompt and input, leaving the cursor at the start
+    /// of the prompt's first row, e.g. before the grid is resized.
+    pub fn erase(&mut self) -> Vec<u8> {
+        let mut out = vec![b'\r'];
+        if self.cursor_row > 0 {
+            let _ = write!(out, "\x1b[{}A", self.cursor_row);
+        }
+        out.extend_from_slice(b"\x1b[J");
+        self.cursor_row = 0;
+        out
+    }
+
+    /// Repaint with the cursor after the input, then `marker` (e.g. `^C`) and
+    /// a newline, ready for output. The buffer itself is left for
+    /// [`take_line`](Self::take_line) or [`clear`](Self::clear).
+    pub fn finish(&mut self, prompt: &Prompt, styled: &str, cols: usize, marker: &[u8]) -> Vec<u8> {
+        self.menu = None;
+        self.search = None;
+        self.line.move_to_end();
+        let mut out = self.render(prompt, styled, cols);
+        out.extend_from_slice(marker);
+        out.extend_from_slice(b"\r\n");
+        out.extend_from_slice(DEFAULT_CURSOR.as_bytes());
+        self.cursor_row = 0;
+        out
+    }
+
+    fn cursor_style(&self) -> &'static str {
+        match self.vi {
+            None => self.shapes.emacs,
+            Some(ViMode::Insert) => self.shapes.vi_insert,
+            Some(ViMode::Normal) => self.shapes.vi_normal,
+        }
+    }
+
+    // --- Key dispatch ---
+
+    pub fn handle_key(&mut self, event: &KeyboardInput, mods: Modifiers) -> EditAction {
+        let key = &event.logical_key;
+        if matches!(
+            key,
+            Key::Shift | Key::Control | Key::Alt | Key::AltGraph | Key::Super | Key::Meta | Key::CapsLock
+        ) {
+            return EditAction::None;
+        }
+        self.previous = std::mem::replace(&mut self.last_edit, LastEdit::Other);
+        let chord = (mods.ctrl || mods.alt).then(|| chord_char(event)).flatten();
+
+        let menu_closed = match self.menu_key(key, mods) {
+            Some(action) => return action,
+            None => self.menu.take().is_some(),
+        };
+        if let Some(action) = self.search_key(key, chord, mods) {
+            return action;
+        }
+        let action = match self.vi {
+            None => self.emacs_key(key, chord, mods),
+            Some(ViMode::Insert) => self.vi_insert_key(key, chord, mods),
+            Some(ViMode::Normal) => self.vi_normal_key(key, chord, mods),
+        };
+        match action {
+            // Repaint to erase the menu
+            EditAction::None if menu_closed => EditAction::Redraw,
+            action => action,
+        }
+    }
+
+    /// Keys that drive an open completion menu. Anything else closes it and
+    /// is handled as usual.
+    fn menu_key(&mut self, key: &Key, mods: Modifiers) -> Option<EditAction> {
+        let menu = self.menu.as_mut()?;
+        match key {
+            Key::Tab if mods.shift => menu.step(-1),
+            Key::Tab | Key::ArrowRight => menu.step(1),
+            Key::ArrowLeft => menu.step(-1),
+            Key::ArrowDown => menu.step_row(true),
+            Key::ArrowUp => menu.step_row(false),
+            Key::Enter => {
+                let suggestion = menu.selected().clone();
+                self.menu = None;
+                self.accept(&suggestion);
+            }
+            Key::Escape => self.menu = None,
+            _ => return None,
+        }
+        Some(EditAction::Redraw)
+    }
+
+    /// Keys that drive an open Ctrl+R search. Anything else ends it, keeping
+    /// the found line, and is handled as usual.
+    fn search_key(&mut self, key: &Key, chord: Option<char>, mods: Modifiers) -> Option<EditAction> {
+        let search = self.search.as_mut()?;
+        match key {
+            _ if mods.ctrl && !mods.alt && chord == Some('r') => self.search_history(false),
+            _ if mods.ctrl && !mods.alt && chord == Some('g') => self.cancel_search(),
+            Key::Escape => self.cancel_search(),
+            Key::Backspace => {
+                search.text.pop();
+                self.search_history(true);
+            }
+            Key::Character(s) if !mods.ctrl && !mods.alt => {
+                search.text.push_str(s);
+                self.search_history(true);
+            }
+            Key::Space if !mods.ctrl && !mods.alt => {
+                search.text.push(' ');
+                self.search_history(true);
+            }
+            _ => {
+                self.search = None;
+                return None;
+            }
+        }
+        Some(EditAction::Redraw)
+    }
+
+    /// Show the next older entry containing the search text, or the newest
+    /// one when `restart`. When none matches, the last match stays.
+    fn search_history(&mut self, restart: bool) {
+        let Some(search) = &self.search else { return };
+        let text = search.text.clone();
+        if text.is_empty() {
+            self.cancel_search_to(true);
+            return;
+        }
+        let (from, skip) = match (&search.found, restart) {
+            (Some(found), false) => (found.id, found.command_line.clone()),
+            _ => (None, String::new()),
+        };
+        let found = self.history.find(&text, Matching::Substring, from, SearchDirection::Backward, &skip);
+        let search = self.search.as_mut().expect("checked above");
+        search.failing = found.is_none();
+        if let Some(found) = found {
+            let line = found.command_line.clone();
+            let pos = line.find(&text).unwrap_or(0);
+            search.found = Some(found);
+            self.show_history_entry(line);
+            self.line.set_insertion_point(pos);
+        }
+    }
+
+    /// Close the search and bring back the line it started from.
+    fn cancel_search(&mut self) {
+        self.cancel_search_to(false);
+    }
+
+    /// Restore the line the search started from; `keep_open` leaves the
+    /// search running with nothing found, as after erasing its text.
+    fn cancel_search_to(&mut self, keep_open: bool) {
+        let Some(search) = self.search.take() else { return };
+        let (buffer, pos) = search.original.clone();
+        if keep_open {
+            self.search = Some(Search {
+                found: None,
+                failing: false,
+                ..search
+            });
+        }
+        self.show_history_entry(buffer);
+        self.line.set_insertion_point(pos);
+    }
+
+    /// Apply Tab completion candidates. With `quick` a lone candidate is
+    /// inserted directly; with `partial` the candidates' common prefix is
+    /// inserted before the menu opens.
+    pub fn show_completions(&mut self, mut suggestions: Vec<Suggestion>, quick: bool, partial: bool) -> EditAction {
+        let len = self.line.len();
+        suggestions.retain(|s| {
+            let buffer = self.line.get_buffer();
+            s.span.start <= s.span.end
+                && s.span.end <= len
+                && buffer.is_char_boundary(s.span.start)
+                && buffer.is_char_boundary(s.span.end)
+        });
+        match suggestions.as_slice() {
+            [] => return EditAction::None,
+            [only] if quick => {
+                let only = only.clone();
+                self.accept(&only);
+                return EditAction::Redraw;
+            }
+            _ => {}
+        }
+
+        if partial && let Some(prefix) = completion::common_prefix(&suggestions) {
+            let span = suggestions[0].span;
+            let typed = &self.line.get_buffer()[span.start..span.end];
+            if prefix.len() > typed.len() && prefix.starts_with(typed) {
+                let prefix = prefix.to_string();
+                self.edit(LastEdit::Other, |line| {
+                    line.replace_range(span.start..span.end, &prefix);
+                    line.set_insertion_point(span.start + prefix.len());
+                });
+                for s in &mut suggestions {
+                    s.span.end = span.start + prefix.len();
+                }
+            }
+        }
+        self.menu = Some(Menu::new(suggestions));
+        EditAction::Redraw
+    }
+
+    fn accept(&mut self, suggestion: &Suggestion) {
+        let mut value = suggestion.value.clone();
+        if suggestion.append_whitespace {
+            value.push(' ');
+        }
+        let span = suggestion.span;
+        self.edit(LastEdit::Other, |line| {
+            line.replace_range(span.start..span.end, &value);
+            line.set_insertion_point(span.start + value.len());
+        });
+    }
+
+    fn emacs_key(&mut self, key: &Key, chord: Option<char>, mods: Modifiers) -> EditAction {
+        if mods.ctrl && !mods.alt {
+            match chord {
+                Some('b') => return self.motion(|line| line.move_left()),
+                Some('f') => return self.motion(|line| line.move_right()),
+                Some('p') => return self.line_up(),
+                Some('n') => return self.line_down(),
+                Some('k') => return self.kill(self.line.insertion_point()..self.line.find_current_line_end()),
+                Some('y') => return self.yank(),
+                Some('t') => return self.edit(LastEdit::Other, |line| line.swap_graphemes()),
+                Some('_') | Some('/') | Some('z') => return self.undo(),
+                _ => {}
+            }
+        }
+        if mods.alt && !mods.ctrl {
+            match chord {
+                Some('b') => return self.motion(|line| line.move_word_left()),
+                Some('f') => return self.motion(|line| line.move_word_right()),
+                Some('d') => return self.kill(self.line.insertion_point()..self.line.word_right_index()),
+                Some('y') => return self.yank_pop(),
+                Some('u') => return self.edit(LastEdit::Other, |line| line.uppercase_word()),
+                Some('l') => return self.edit(LastEdit::Other, |line| line.lowercase_word()),
+                Some('c') => return self.edit(LastEdit::Other, |line| line.capitalize_char()),
+                Some('t') => return self.edit(LastEdit::Other, |line| line.swap_words()),
+                _ => {}
+            }
+            if matches!(key, Key::Backspace) {
+                return self.kill(self.line.word_left_index()..self.line.insertion_point());
+            }
+        }
+        match key {
+            Key::Escape => {
+                self.clear();
+                EditAction::Redraw
+            }
+            Key::ArrowLeft if mods.ctrl || mods.alt => self.motion(|line| line.move_word_left()),
+            Key::ArrowRight if mods.ctrl || mods.alt => self.motion(|line| line.move_word_right()),
+            _ => self.insert_key(key, chord, mods),
+        }
+    }
+
+    /// Keys shared by emacs mode and vi insert mode.
+    fn insert_key(&mut self, key: &Key, chord: Option<char>, mods: Modifiers) -> EditAction {
+        if mods.ctrl && !mods.alt {
+            return match chord {
+                Some('c') => {
+                    self.line.move_to_end();
+                    EditAction::Cancel
+                }
+                Some('l') => EditAction::ClearScreen,
+                Some('r') => {
+                    let original = (self.line.get_buffer().to_string(), self.line.insertion_point());
+                    self.search = Some(Search {
+                        text: String::new(),
+                        found: None,
+                        failing: false,
+                        original,
+                    });
+                    EditAction::Redraw
+                }
+                Some('a') => self.motion(|line| line.move_to_line_start()),
+                Some('e') => self.motion(|line| line.move_to_line_end()),
+                Some('d') if self.line.is_empty() => EditAction::None,
+                Some('d') => self.edit(LastEdit::Other, |line| line.delete_right_grapheme()),
+                Some('h') => self.edit(LastEdit::Other, |line| line.delete_left_grapheme()),
+                Some('w') => self.kill(self.line.big_word_left_index()..self.line.insertion_point()),
+                Some('u') => {
+                    let start = self.line.current_line_range().start;
+                    self.kill(start..self.line.insertion_point())
+                }
+                _ => EditAction::None,
+            };
+        }
+        match key {
+            Key::Character(s) => self.insert(s),
+            Key::Space => self.insert(" "),
+            Key::Tab => EditAction::Complete,
+            Key::Enter if mods.alt || mods.shift => self.insert("\n"),
+            Key::Enter => EditAction::Submit,
+            Key::Backspace => self.edit(LastEdit::Other, |line| line.delete_left_grapheme()),
+            Key::Delete => self.edit(LastEdit::Other, |line| line.delete_right_grapheme()),
+            Key::ArrowLeft => self.motion(|line| line.move_left()),
+            Key::ArrowRight => self.motion(|line| line.move_right()),
+            Key::Home => self.motion(|line| line.move_to_line_start()),
+            Key::End => self.motion(|line| line.move_to_line_end()),
+            Key::ArrowUp => self.line_up(),
+            Key::ArrowDown => self.line_down(),
+            _ => EditAction::None,
+        }
+    }
+
+    fn vi_insert_key(&mut self, key: &Key, chord: Option<char>, mods: Modifiers) -> EditAction {
+        if matches!(key, Key::Escape) || (mods.ctrl && chord == Some('[')) {
+            self.leave_insert();
+            return EditAction::Redraw;
+        }
+        self.insert_key(key, chord, mods)
+    }
+
+    fn leave_insert(&mut self) {
+        if let Some((mut change, start)) = self.recording.take() {
+            let point = self.line.insertion_point();
+            change.inserted = self.line.get_buffer().get(start..point).unwrap_or_default().to_string();
+            self.last_change = Some(change);
+        }
+        self.vi = Some(ViMode::Normal);
+        self.insert_group = false;
+        if self.undo.last().is_some_and(|(buffer, _)| buffer == self.line.get_buffer()) {
+            self.undo.pop();
+        }
+        // Like vim, leaving insert mode steps back onto the last character
+        if self.line.insertion_point() > self.line.current_line_range().start {
+            self.line.move_left();
+        }
+    }
+
+    fn vi_normal_key(&mut self, key: &Key, chord: Option<char>, mods: Modifiers) -> EditAction {
+        if mods.ctrl {
+            return match chord {
+                Some('c') => {
+                    self.line.move_to_end();
+                    EditAction::Cancel
+                }
+                Some('l') => EditAction::ClearScreen,
+                _ => EditAction::None,
+            };
+        }
+        let action = match key {
+            Key::Character(s) => {
+                let mut action = EditAction::None;
+                for c in s.chars() {
+                    action = self.vi_command(c);
+                }
+                action
+            }
+            Key::Escape => {
+                self.pending = None;
+                self.count = None;
+                EditAction::None
+            }
+            Key::Enter => {
+                self.pending = None;
+                return EditAction::Submit;
+            }
+            Key::Space | Key::ArrowRight => self.vi_command('l'),
+            Key::Backspace | Key::ArrowLeft => self.vi_command('h'),
+            Key::Home => self.vi_command('0'),
+            Key::End => self.vi_command('$'),
+            Key::Delete => self.vi_command('x'),
+            Key::ArrowUp => self.vi_command('k'),
+            Key::ArrowDown => self.vi_command('j'),
+            _ => EditAction::None,
+        };
+        if self.vi == Some(ViMode::Normal) {
+            self.clamp_normal();
+        }
+        action
+    }
+
+    /// Run a normal-mode key, remembering complete commands that change
+    /// the buffer for `.`.
+    fn vi_command(&mut self, c: char) -> EditAction {
+        if c == '.' && self.pending.is_none() {
+            self.count = None;
+            return self.repeat_change();
+        }
+        if self.pending.is_none() && self.count.is_none() {
+            self.command.clear();
+            self.edited = false;
+        }
+        self.command.push(c);
+        let action = self.vi_key(c);
+        if self.pending.is_none() && self.count.is_none() {
+            let change = Change {
+                keys: std::mem::take(&mut self.command),
+                inserted: String::new(),
+            };
+            if self.vi == Some(ViMode::Insert) {
+                self.recording = Some((change, self.line.insertion_point()));
+            } else if self.edited {
+                self.last_change = Some(change);
+            }
+        }
+        action
+    }
+
+    /// `.`: make the last change again at the cursor.
+    fn repeat_change(&mut self) -> EditAction {
+        let Some(change) = self.last_change.clone() else {
+            return EditAction::None;
+        };
+        for c in change.keys.chars() {
+            self.vi_key(c);
+        }
+        if self.vi == Some(ViMode::Insert) {
+            self.insert(&change.inserted);
+            self.leave_insert();
+        }
+        EditAction::Redraw
+    }
+
+    fn vi_key(&mut self, c: char) -> EditAction {
+        if let Some(pending) = self.pending.take() {
+            return self.vi_pending(pending, c);
+        }
+        if c.is_ascii_digit() && (c != '0' || self.count.is_some()) {
+            let digit = c as usize - '0' as usize;
+            self.count = Some(self.count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            return EditAction::None;
+        }
+        let count = self.count.take().unwrap_or(1);
+
+        if let Some(target) = self.vi_motion(c, count) {
+            self.line.set_insertion_point(target.0);
+            return EditAction::Redraw;
+        }
+        let pos = self.line.insertion_point();
+        match c {
+            'i' => self.enter_insert(pos),
+            'a' => self.enter_insert(self.line.grapheme_right_index().min(self.line.find_current_line_end())),
+            'I' => self.enter_insert(self.first_non_blank()),
+            'A' => self.enter_insert(self.line.find_current_line_end()),
+            'x' => {
+                let end = self.repeat(count, |line| line.grapheme_right_index());
+                self.delete(pos..end.min(self.line.find_current_line_end()))
+            }
+            'X' => {
+                let start = self.repeat(count, |line| line.grapheme_left_index());
+                self.delete(start.max(self.line.current_line_range().start)..pos)
+            }
+            's' => {
+                let end = self.repeat(count, |line| line.grapheme_right_index());
+                self.delete(pos..end.min(self.line.find_current_line_end()));
+                self.start_change()
+            }
+            'D' => self.delete(pos..self.line.find_current_line_end()),
+            'C' => {
+                self.delete(pos..self.line.find_current_line_end());
+                self.start_change()
+            }
+            'S' => self.apply_operator(Operator::Change, self.current_line_content()),
+            'd' => self.start_operator(Operator::Delete, count),
+            'c' => self.start_operator(Operator::Change, count),
+            'y' => self.start_operator(Operator::Yank, count),
+            'Y' => self.apply_operator(Operator::Yank, pos..self.line.find_current_line_end()),
+            'p' => self.put(true, count),
+            'P' => self.put(false, count),
+            'u' => self.undo(),
+            'r' => {
+                self.pending = Some(Pending::Replace);
+                EditAction::None
+            }
+            '~' => {
+                let result = self.edit(LastEdit::Other, |line| {
+                    for _ in 0..count {
+                        line.switchcase_char();
+                    }
+                });
+                self.clamp_normal();
+                result
+            }
+            'f' | 'F' | 't' | 'T' => {
+                self.count = Some(count);
+                self.pending = Some(Pending::Find(find_kind(c), None));
+                EditAction::None
+            }
+            'j' => self.repeat_lines(count, Self::line_down),
+            'k' => self.repeat_lines(count, Self::line_up),
+            _ => EditAction::None,
+        }
+    }
+
+    fn vi_pending(&mut self, pending: Pending, c: char) -> EditAction {
+        match pending {
+            Pending::Replace => {
+                let pos = self.line.insertion_point();
+                let end = self.line.grapheme_right_index();
+                if end == pos || end > self.line.find_current_line_end() {
+                    return EditAction::None;
+                }
+                self.edit(LastEdit::Other, |line| {
+                    line.replace_range(pos..end, c.encode_utf8(&mut [0; 4]));
+                    line.set_insertion_point(pos);
+                })
+            }
+            Pending::Find(find, op) => {
+                let count = self.count.take().unwrap_or(1);
+                self.last_find = Some((find, c));
+                let Some(target) = self.find_target(find, c, count) else {
+                    return EditAction::None;
+                };
+                match op {
+                    None => {
+                        self.line.set_insertion_point(target);
+                        EditAction::Redraw
+                    }
+                    Some(op) => {
+                        let range = self.motion_range(target, find.forward);
+                        self.apply_operator(op, range)
+                    }
+                }
+            }
+            Pending::Operator(op) => {
+                if c.is_ascii_digit() && (c != '0' || self.count.is_some()) {
+                    let digit = c as usize - '0' as usize;
+                    self.count = Some(self.count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                    self.pending = Some(pending);
+                    return EditAction::None;
+                }
+                let count = self.count.take().unwrap_or(1);
+                if c == operator_char(op) {
+                    return self.apply_operator(op, self.current_line_content());
+                }
+                if let 'f' | 'F' | 't' | 'T' = c {
+                    self.count = Some(count);
+                    self.pending = Some(Pending::Find(find_kind(c), Some(op)));
+                    return EditAction::None;
+                }
+                // `cw` changes to the end of the word, like vim
+                let c = match c {
+                    'w' if op == Operator::Change && !self.line.on_whitespace() => 'e',
+                    'W' if op == Operator::Change && !self.line.on_whitespace() => 'E',
+                    c => c,
+                };
+                let Some((target, inclusive)) = self.vi_motion(c, count) else {
+                    return EditAction::None;
+                };
+                let range = if inclusive {
+                    self.motion_range(target, true)
+                } else {
+                    let pos = self.line.insertion_point();
+                    pos.min(target)..pos.max(target)
+                };
+                self.apply_operator(op, range)
+            }
+        }
+    }
+
+    /// Target of a vi motion key and whether it includes the target character.
+    fn vi_motion(&mut self, c: char, count: usize) -> Option<(usize, bool)> {
+        let target = match c {
+            'h' => {
+                let start = self.line.current_line_range().start;
+                (self.repeat(count, |line| line.grapheme_left_index()).max(start), false)
+            }
+            'l' => {
+                let end = self.line.find_current_line_end();
+                (self.repeat(count, |line| line.grapheme_right_index()).min(end), false)
+            }
+            'w' => (self.repeat(count, |line| line.word_right_start_index()), false),
+            'W' => (self.repeat(count, |line| line.big_word_right_start_index()), false),
+            'b' => (self.repeat(count, |line| line.word_left_index()), false),
+            'B' => (self.repeat(count, |line| line.big_word_left_index()), false),
+            'e' => (self.repeat(count, |line| line.word_right_end_index()), true),
+            'E' => (self.repeat(count, |line| line.big_word_right_end_index()), true),
+            '0' => (self.line.current_line_range().start, false),
+            '^' => (self.first_non_blank(), false),
+            '$' => (self.line.find_current_line_end(), false),
+            ';' | ',' => {
+                let (find, ch) = self.last_find?;
+                let find = if c == ',' {
+                    Find {
+                        forward: !find.forward,
+                        ..find
+                    }
+                } else {
+                    find
+                };
+                (self.find_target(find, ch, count)?, find.forward)
+            }
+            _ => return None,
+        };
+        Some(target)
+    }
+
+    /// Insertion point after moving `count` times with `step`, leaving the
+    /// cursor where it was.
+    fn repeat(&mut self, count: usize, step: impl Fn(&LineBuffer) -> usize) -> usize {
+        let start = self.line.insertion_point();
+        for _ in 0..count {
+            let next = step(&self.line);
+            self.line.set_insertion_point(next);
+        }
+        let target = self.line.insertion_point();
+        self.line.set_insertion_point(start);
+        target
+    }
+
+    /// Position `count` occurrences of `c` away on the current line, one
+    /// grapheme short of it for `t`/`T`.
+    fn find_target(&mut self, find: Find, c: char, count: usize) -> Option<usize> {
+        let start = self.line.insertion_point();
+        let mut found = None;
+        for _ in 0..count {
+            let next = if find.forward {
+                self.line.find_char_right(c, true)
+            } else {
+                self.line.find_char_left(c, true)
+            };
+            let Some(i) = next else { break };
+            found = Some(i);
+            self.line.set_insertion_point(i);
+        }
+        let target = found.map(|i| {
+            self.line.set_insertion_point(i);
+            match (find.forward, find.till) {
+                (true, true) => self.line.grapheme_left_index(),
+                (false, true) => self.line.grapheme_right_index(),
+                _ => i,
+            }
+        });
+        self.line.set_insertion_point(start);
+        target
+    }
+
+    /// Range between the cursor and `target`, covering the character at
+    /// `target` for inclusive (forward) motions.
+    fn motion_range(&self, target: usize, inclusive: bool) -> Range<usize> {
+        let pos = self.line.insertion_point();
+        if target >= pos {
+            let end = if inclusive {
+                self.line.grapheme_right_index_from_pos(target)
+            } else {
+                target
+            };
+            pos..end
+        } else {
+            target..pos
+        }
+    }
+
+    fn start_operator(&mut self, op: Operator, count: usize) -> EditAction {
+        if count > 1 {
+            self.count = Some(count);
+        }
+        self.pending = Some(Pending::Operator(op));
+        EditAction::None
+    }
+
+    fn apply_operator(&mut self, op: Operator, range: Range<usize>) -> EditAction {
+        match op {
+            Operator::Delete => self.delete(range),
+            Operator::Change => {
+                self.delete(range);
+                self.start_change()
+            }
+            Operator::Yank => {
+                let text = &self.line.get_buffer()[range.clone()];
+                if !text.is_empty() {
+                    self.kill_ring.kill(text, false, false);
+                }
+                self.line.set_insertion_point(range.start);
+                EditAction::Redraw
+            }
+        }
+    }
+
+    /// Delete `range` into the kill ring (vi's unnamed register).
+    fn delete(&mut self, range: Range<usize>) -> EditAction {
+        if range.is_empty() {
+            return EditAction::None;
+        }
+        let text = self.line.get_buffer()[range.clone()].to_string();
+        self.kill_ring.kill(&text, false, false);
+        self.edit(LastEdit::Other, |line| {
+            line.clear_range_safe(range.clone());
+            line.set_insertion_point(range.start);
+        })
+    }
+
+    /// `p`/`P`: paste `count` times after or before the cursor, leaving it on
+    /// the last pasted character.
+    fn put(&mut self, after: bool, count: usize) -> EditAction {
+        let Some(text) = self.kill_ring.yank() else {
+            return EditAction::None;
+        };
+        let text = text.repeat(count);
+        self.edit(LastEdit::Other, |line| {
+            if after && !line.is_empty() {
+                line.move_right();
+            }
+            line.insert_str(&text);
+            line.move_left();
+        })
+    }
+
+    fn enter_insert(&mut self, pos: usize) -> EditAction {
+        self.line.set_insertion_point(pos);
+        self.undo.push((self.line.get_buffer().to_string(), pos));
+        self.start_change()
+    }
+
+    /// Switch to insert mode, grouping what is typed with the change (or
+    /// snapshot) just made.
+    fn start_change(&mut self) -> EditAction {
+        self.vi = Some(ViMode::Insert);
+        self.insert_group = true;
+        EditAction::Redraw
+    }
+
+    fn current_line_content(&self) -> Range<usize> {
+        let range = self.line.current_line_range();
+        let mut line = self.line.clone();
+        line.set_insertion_point(range.start);
+        range.start..line.find_current_line_end()
+    }
+
+    fn first_non_blank(&self) -> usize {
+        let range = self.current_line_content();
+        let text = &self.line.get_buffer()[range.clone()];
+        range.start + text.len() - text.trim_start().len()
+    }
+
+    /// Normal mode rests on a character, never past the end of the line.
+    fn clamp_normal(&mut self) {
+        let start = self.line.current_line_range().start;
+        let end = self.line.find_current_line_end();
+        if self.line.insertion_point() >= end && end > start {
+            let mut line = self.line.clone();
+            line.set_insertion_point(end);
+            self.line.set_insertion_point(line.grapheme_left_index());
+        }
+    }
+
+    // --- Editing primitives ---
+
+    fn insert(&mut self, text: &str) -> EditAction {
+        self.edit(LastEdit::Insert, |line| line.insert_str(text))
+    }
+
+    fn motion(&mut self, f: impl FnOnce(&mut LineBuffer)) -> EditAction {
+        f(&mut self.line);
+        EditAction::Redraw
+    }
+
+    /// Apply a change, saving an undo snapshot first unless it continues a
+    /// run of typing.
+    fn edit(&mut self, kind: LastEdit, f: impl FnOnce(&mut LineBuffer)) -> EditAction {
+        let snapshot = (self.line.get_buffer().to_string(), self.line.insertion_point());
+        f(&mut self.line);
+        if self.line.get_buffer() != snapshot.0 {
+            self.edited = true;
+            let continues = self.insert_group || (kind == LastEdit::Insert && self.previous == LastEdit::Insert);
+            if !continues {
+                self.undo.push(snapshot);
+                if self.undo.len() > UNDO_DEPTH {
+                    self.undo.remove(0);
+                }
+            }
+            self.history_entry = None;
+        }
+        self.last_edit = kind;
+        EditAction::Redraw
+    }
+
+    fn undo(&mut self) -> EditAction {
+        let Some((buffer, pos)) = self.undo.pop() else {
+            return EditAction::None;
+        };
+        self.line.set_buffer(buffer);
+        self.line.set_insertion_point(pos);
+        EditAction::Redraw
+    }
+
+    /// Kill `range` into the kill ring, joining with an immediately
+    /// preceding kill.
+    fn kill(&mut self, range: Range<usize>) -> EditAction {
+        if range.is_empty() {
+            return EditAction::None;
+        }
+        let backwards = range.end <= self.line.insertion_point();
+        let text = self.line.get_buffer()[range.clone()].to_string();
+        self.kill_ring.kill(&text, self.previous == LastEdit::Kill, backwards);
+        self.edit(LastEdit::Kill, |line| line.clear_range_safe(range))
+    }
+
+    fn yank(&mut self) -> EditAction {
+        let Some(text) = self.kill_ring.yank() else {
+            return EditAction::None;
+        };
+        let start = self.line.insertion_point();
+        let action = self.edit(LastEdit::Other, |line| line.insert_str(&text));
+        self.last_edit = LastEdit::Yank {
+            start,
+            end: start + text.len(),
+        };
+        action
+    }
+
+    /// Alt+Y: replace the text just yanked with the next older kill.
+    fn yank_pop(&mut self) -> EditAction {
+        let LastEdit::Yank { start, end } = self.previous else {
+            return EditAction::None;
+        };
+        let Some(text) = self.kill_ring.rotate() else {
+            return EditAction::None;
+        };
+        let action = self.edit(LastEdit::Other, |line| {
+            line.replace_range(start..end, &text);
+            line.set_insertion_point(start + text.len());
+        });
+        self.last_edit = LastEdit::Yank {
+            start,
+            end: start + text.len(),
+        };
+        action
+    }
+
+    // --- History ---
+
+    /// Up: the row above in multiline input, or the previous history entry
+    /// from the first row.
+    fn line_up(&mut self) -> EditAction {
+        if self.line.is_cursor_at_first_line() {
+            return self.history_prev();
+        }
+        self.motion(|line| line.move_line_up())
+    }
+
+    /// Down: the row below in multiline input, or the next history entry
+    /// from the last row.
+    fn line_down(&mut self) -> EditAction {
+        if self.line.is_cursor_at_last_line() {
+            return self.history_next();
+        }
+        self.motion(|line| line.move_line_down())
+    }
+
+    /// vi `j`/`k` with a count, keeping the cursor on a character.
+    fn repeat_lines(&mut self, count: usize, step: fn(&mut Self) -> EditAction) -> EditAction {
+        let mut action = EditAction::None;
+        for _ in 0..count {
+            match step(self) {
+                EditAction::None => break,
+                next => action = next,
+            }
+        }
+        self.clamp_normal();
+        action
+    }
+
+    /// Up: the previous entry starting with the line typed before browsing.
+    fn history_prev(&mut self) -> EditAction {
+        if self.history_entry.is_none() {
+            self.draft = self.line.get_buffer().to_string();
+        }
+        let from = self.history_entry.as_ref().and_then(|entry| entry.id);
+        let shown = self.line.get_buffer().to_string();
+        let Some(entry) = self.history.find(&self.draft, Matching::Prefix, from, SearchDirection::Backward, &shown) else {
+            return EditAction::None;
+        };
+        let line = entry.command_line.clone();
+        self.history_entry = Some(entry);
+        self.show_history_entry(line)
+    }
+
+    /// Down: the next matching entry, then back to the typed line.
+    fn history_next(&mut self) -> EditAction {
+        let Some(from) = self.history_entry.as_ref().and_then(|entry| entry.id) else {
+            return EditAction::None;
+        };
+        let shown = self.line.get_buffer().to_string();
+        match self.history.find(&self.draft, Matching::Prefix, Some(from), SearchDirection::Forward, &shown) {
+            Some(entry) => {
+                let line = entry.command_line.clone();
+                self.history_entry = Some(entry);
+                self.show_history_entry(line)
+            }
+            None => {
+                self.history_entry = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.show_history_entry(draft)
+            }
+        }
+    }
+
+    fn show_history_entry(&mut self, entry: String) -> EditAction {
+        self.line.set_buffer(entry);
+        self.undo.clear();
+        if self.vi == Some(ViMode::Normal) {
+            self.clamp_normal();
+        }
+        EditAction::Redraw
+    }
+}
+
+fn find_kind(c: char) -> Find {
+    Find {
+        forward: c.is_ascii_lowercase(),
+        till: c.eq_ignore_ascii_case(&'t'),
+    }
+}
+
+fn operator_char(op: Operator) -> char {
+    match op {
+        Operator::Delete => 'd',
+        Operator::Change => 'c',
+        Operator::Yank => 'y',
+    }
+}
+
+/// Character of a Ctrl/Alt chord, lowercased. Falls back to the physical key
+/// when the layout turns the chord into another symbol (macOS Option+B types
+/// `∫`).
+fn chord_char(event: &KeyboardInput) -> Option<char> {
+    if let Key::Character(s) = &event.logical_key {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next())
+            && c.is_ascii_graphic()
+        {
+            return Some(c.to_ascii_lowercase());
+        }
+    }
+    Some(match event.key_code {
+        KeyCode::KeyB => 'b',
+        KeyCode::KeyC => 'c',
+        KeyCode::KeyD => 'd',
+        KeyCode::KeyF => 'f',
+        KeyCode::KeyL => 'l',
+        KeyCode::KeyT => 't',
+        KeyCode::KeyU => 'u',
+        KeyCode::KeyY => 'y',
+        _ => return None,
+    })
+}
+
+/// Column where `right` starts so it ends at the last column of the first
+/// row, or `None` when it would not fit beside the prompt and input there.
+fn right_prompt_column(left: &str, buffer: &str, right: &str, cols: usize) -> Option<usize> {
+    if right.is_empty() || right.contains('\n') {
+        return None;
+    }
+    let mut width = Layout::new(usize::MAX);
+    width.advance(right);
+
+    // A multi-line prompt keeps its first line; otherwise the input shares
+    // the row
+    let mut first_row = Layout::new(cols);
+    match left.split_once('\n') {
+        Some((first, _)) => first_row.advance(first),
+        None => {
+            first_row.advance(left);
+            first_row.advance(buffer.split('\n').next().unwrap_or(""));
+        }
+    }
+    let column = cols.checked_sub(width.col)?;
+    // Keep a space between the input and the right prompt
+    (first_row.row == 0 && first_row.col < column).then_some(column)
+}
+
+fn push_crlf(out: &mut Vec<u8>, text: &str) {
+    let mut prev = 0u8;
+    for &b in text.as_bytes() {
+        if b == b'\n' && prev != b'\r' {
+            out.push(b'\r');
+        }
+        out.push(b);
+        prev = b;
+    }
+}
+
+/// Where printed text leaves the cursor, following the grid's soft wraps.
+/// `col == cols` is the terminal's pending-wrap state after filling a row.
+struct Layout {
+    cols: usize,
+    row: usize,
+    col: usize,
+}
+
+impl Layout {
+    fn new(cols: usize) -> Self {
+        Self {
+            cols: cols.max(1),
+            row: 0,
+            col: 0,
+        }
+    }
+
+    fn advance(&mut self, text: &str) {
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\n' => {
+                    self.row += 1;
+                    self.col = 0;
+                }
+                '\r' => self.col = 0,
+                '\t' => {
+                    self.wrap_pending();
+                    self.col = ((self.col / 8 + 1) * 8).min(self.cols - 1);
+                }
+                // Escape sequences in the prompt take no space
+                '\x1b' => match chars.next() {
+                    Some('[') => {
+                        for c in chars.by_ref() {
+                            if ('\x40'..='\x7e').contains(&c) {
+                                break;
+                            }
+                        }
+                    }
+                    Some(']') => {
+                        while let Some(c) = chars.next() {
+                            if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                                break;
+                            }
+                        }
+                    }
+                    Some('(' | ')') => {
+                        chars.next();
+                    }
+                    _ => {}
+                },
+                c => {
+                    let width = c.width().unwrap_or(0);
+                    if width == 0 {
+                        continue;
+                    }
+                    if self.col + width > self.cols {
+                        self.row += 1;
+                        self.col = 0;
+                    }
+                    self.col += width;
+                }
+            }
+        }
+    }
+
+    fn wrap_pending(&mut self) {
+        if self.col >= self.cols {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+
+    /// `(row, col)` where the next character would be drawn.
+    fn position(&self) -> (usize, usize) {
+        if self.col >= self.cols {
+            (self.row + 1, 0)
+        } else {
+            (self.row, self.col)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::entity::Entity;
+    use bevy::input::ButtonState;
+    use bevy::input::keyboard::NativeKeyCode;
+
+    use super::*;
+
+    const NONE: Modifiers = Modifiers {
+        shift: false,
+        alt: false,
+        ctrl: false,
+    };
+    const CTRL: Modifiers = Modifiers {
+        shift: false,
+        alt: false,
+        ctrl: true,
+    };
+    const ALT: Modifiers = Modifiers {
+        shift: false,
+        alt: true,
+        ctrl: false,
+    };
+
+    fn press(editor: &mut LineEditor, key: Key, mods: Modifiers) -> EditAction {
+        let event = KeyboardInput {
+            key_code: KeyCode::Unidentified(NativeKeyCode::Unidentified),
+            logical_key: key,
+            state: ButtonState::Pressed,
+            text: None,
+            repeat: false,
+            window: Entity::PLACEHOLDER,
+        };
+        editor.handle_key(&event, mods)
+    }
+
+    fn chord(editor: &mut LineEditor, mods: Modifiers, c: char) -> EditAction {
+        press(editor, Key::Character(c.to_string().into()), mods)
+    }
+
+    /// Type `text` one key at a time; in vi normal mode these are commands.
+    fn keys(editor: &mut LineEditor, text: &str) {
+        for c in text.chars() {
+            let key = match c {
+                ' ' => Key::Space,
+                c => Key::Character(c.to_string().into()),
+            };
+            press(editor, key, NONE);
+        }
+    }
+
+    fn vi_editor() -> LineEditor {
+        let mut editor = LineEditor::new();
+        editor.vi = Some(ViMode::Insert);
+        editor
+    }
+
+    fn render(editor: &mut LineEditor, prompt: &Prompt, cols: usize) -> Vec<u8> {
+        let buffer = editor.buffer().to_string();
+        editor.render(prompt, &buffer, cols)
+    }
+
+    #[test]
+    fn test_emacs_motions() {
+        let mut editor = LineEditor::new();
+        keys(&mut editor, "echo foo bar");
+        chord(&mut editor, CTRL, 'a');
+        assert_eq!(editor.insertion_point(), 0);
+        chord(&mut editor, CTRL, 'e');
+        assert_eq!(editor.insertion_point(), 12);
+        chord(&mut editor, ALT, 'b');
+        assert_eq!(editor.insertion_point(), 9);
+        chord(&mut editor, ALT, 'b');
+        assert_eq!(editor.insertion_point(), 5);
+        chord(&mut editor, ALT, 'f');
+        assert_eq!(editor.insertion_point(), 8);
+    }
+
+    #[test]
+    fn test_emacs_kills_join_in_kill_ring() {
+        let mut editor = LineEditor::new();
+        keys(&mut editor, "echo foo bar");
+        chord(&mut editor, CTRL, 'w');
+        assert_eq!(editor.buffer(), "echo foo ");
+        chord(&mut editor, CTRL, 'w');
+        assert_eq!(editor.buffer(), "echo ");
+        chord(&mut editor, CTRL, 'y');
+        assert_eq!(editor.buffer(), "echo foo bar");
+
+        // Ctrl+K then Ctrl+U take the whole line back as one entry
+        chord(&mut editor, ALT, 'b');
+        chord(&mut editor, ALT, 'b');
+        chord(&mut editor, CTRL, 'k');
+        assert_eq!(editor.buffer(), "echo ");
+        chord(&mut editor, CTRL, 'u');
+        assert_eq!(editor.buffer(), "");
+        chord(&mut editor, CTRL, 'y');
+        assert_eq!(editor.buffer(), "echo foo bar");
+        assert_eq!(editor.insertion_point(), 12);
+    }
+
+    #[test]
+    fn test_emacs_yank_pop() {
+        let mut editor = LineEditor::new();
+        keys(&mut editor, "one two");
+        chord(&mut editor, CTRL, 'w');
+        chord(&mut editor, CTRL, 'a');
+        // Not joined with the Ctrl+W, since a motion came between them
+        chord(&mut editor, CTRL, 'k');
+        assert_eq!(editor.buffer(), "");
+
+        chord(&mut editor, CTRL, 'y');
+        assert_eq!(editor.buffer(), "one ");
+        chord(&mut editor, ALT, 'y');
+        assert_eq!(editor.buffer(), "two");
+        assert_eq!(editor.insertion_point(), 3);
+        chord(&mut editor, ALT, 'y');
+        assert_eq!(editor.buffer(), "one ");
+
+        // Alt+Y only follows a yank
+        keys(&mut editor, "x");
+        assert!(matches!(chord(&mut editor, ALT, 'y'), EditAction::None));
+        assert_eq!(editor.buffer(), "one x");
+    }
+
+    #[test]
+    fn test_vi_insert_and_normal_modes() {
+        let mut editor = vi_editor();
+        keys(&mut editor, "ls");
+        press(&mut editor, Key::Escape, NONE);
+        assert!(editor.vi == Some(ViMode::Normal));
+        assert_eq!(editor.insertion_point(), 1);
+
+        // Normal mode keys are commands, not text
+        keys(&mut editor, "i-");
+        assert!(editor.vi == Some(ViMode::Insert));
+        assert_eq!(editor.buffer(), "l-s");
+        press(&mut editor, Key::Escape, NONE);
+        assert_eq!(editor.insertion_point(), 1);
+
+        keys(&mut editor, "A -a");
+        assert_eq!(editor.buffer(), "l-s -a");
+        press(&mut editor, Key::Escape, NONE);
+        assert_eq!(editor.insertion_point(), 5);
+
+        keys(&mut editor, "0x$");
+        assert_eq!(editor.buffer(), "-s -a");
+        assert_eq!(editor.insertion_point(), 4);
+    }
+
+    #[test]
+    fn test_vi_operators_repeat_and_undo() {
+        let mut editor = vi_editor();
+        keys(&mut editor, "echo foo bar baz");
+        press(&mut editor, Key::Escape, NONE);
+
+        keys(&mut editor, "0w");
+        assert_eq!(editor.insertion_point(), 5);
+        keys(&mut editor, "dw");
+        assert_eq!(editor.buffer(), "echo bar baz");
+        keys(&mut editor, ".");
+        assert_eq!(editor.buffer(), "echo baz");
+        keys(&mut editor, "u");
+        assert_eq!(editor.buffer(), "echo bar baz");
+        assert_eq!(editor.insertion_point(), 5);
+
+        // `cw` stops at the end of the word, and `.` repeats the typing too
+        keys(&mut editor, "cwqux");
+        assert_eq!(editor.buffer(), "echo qux baz");
+        press(&mut editor, Key::Escape, NONE);
+        assert_eq!(editor.insertion_point(), 7);
+        keys(&mut editor, "w.");
+        assert_eq!(editor.buffer(), "echo qux qux");
+        assert!(editor.vi == Some(ViMode::Normal));
+        assert_eq!(editor.insertion_point(), 11);
+
+        // Each change, typing included, undoes in one step
+        keys(&mut editor, "u");
+        assert_eq!(editor.buffer(), "echo qux baz");
+        assert_eq!(editor.insertion_point(), 9);
+        keys(&mut editor, "u");
+        assert_eq!(editor.buffer(), "echo bar baz");
+
+        // Motions and undo leave the change to repeat alone
+        keys(&mut editor, "0.");
+        assert_eq!(editor.buffer(), "qux bar baz");
+    }
+
+    #[test]
+    fn test_render_mid_line_insert_at_wrap() {
+        let prompt = Prompt {
+            command: "> ".into(),
+            ..Default::default()
+        };
+        let mut editor = LineEditor::new();
+        keys(&mut editor, "abcdefg");
+        for _ in 0..3 {
+            press(&mut editor, Key::ArrowLeft, NONE);
+        }
+
+        // "> abcdXefg" fills the row exactly, leaving the terminal waiting
+        // to wrap: step down so the cursor movement matches the layout
+        keys(&mut editor, "X");
+        assert_eq!(
+            render(&mut editor, &prompt, 10),
+            b"\r\x1b[J\x1b[0 q> abcdXefg\x1b[0m\r\n\x1b[1A\r\x1b[7C"
+        );
+
+        // One more character wraps the "g" onto the second row
+        keys(&mut editor, "Y");
+        assert_eq!(
+            render(&mut editor, &prompt, 10),
+            b"\r\x1b[J\x1b[0 q> abcdXYefg\x1b[0m\x1b[1A\r\x1b[8C"
+        );
+
+        // With the cursor on the second row, the next render starts a row up
+        press(&mut editor, Key::End, NONE);
+        assert_eq!(render(&mut editor, &prompt, 10), b"\r\x1b[J\x1b[0 q> abcdXYefg\x1b[0m");
+        press(&mut editor, Key::Backspace, NONE);
+        assert_eq!(
+            render(&mut editor, &prompt, 10),
+            b"\r\x1b[1A\x1b[J\x1b[0 q> abcdXYef\x1b[0m\r\n"
+        );
+    }
+
+    #[test]
+    fn test_newline_continues_incomplete_input() {
+        let mut editor = LineEditor::new();
+        keys(&mut editor, "ls | each {");
+        assert!(matches!(editor.insert_newline(), EditAction::Redraw));
+        keys(&mut editor, "  $in");
+        assert_eq!(editor.buffer(), "ls | each {\n  $in");
+
+        // Alt+Enter always continues; plain Enter is left to the caller
+        press(&mut editor, Key::Enter, ALT);
+        keys(&mut editor, "}");
+        assert_eq!(editor.buffer(), "ls | each {\n  $in\n}");
+        assert!(matches!(press(&mut editor, Key::Enter, NONE), EditAction::Submit));
+    }
+
+    #[test]
+    fn test_layout_wraps_and_skips_escapes() {
+        let mut layout = Layout::new(10);
+        layout.advance("\x1b[32m> \x1b[0m\x1b]0;title\x07");
+        assert_eq!(layout.position(), (0, 2));
+        layout.advance("abcdefgh");
+        assert_eq!((layout.row, layout.col), (0, 10));
+        assert_eq!(layout.position(), (1, 0));
+        layout.advance("\n::: ");
+        assert_eq!(layout.position(), (1, 4));
+
+        // A wide character that doesn't fit moves to the next row whole
+        let mut layout = Layout::new(5);
+        layout.advance("ab日本");
+        assert_eq!(layout.position(), (1, 2));
+    }
+
+    #[test]
+    fn test_render_continuation_lines() {
+        let prompt = Prompt {
+            command: "> ".into(),
+            multiline: "::: ".into(),
+            ..Default::default()
+        };
+        let mut editor = LineEditor::new();
+        keys(&mut editor, "ls |");
+        editor.insert_newline();
+        keys(&mut editor, "where x");
+        assert_eq!(
+            render(&mut editor, &prompt, 20),
+            b"\r\x1b[J\x1b[0 q> ls |\x1b[0m\r\n::: where x\x1b[0m"
+        );
+
+        // Up keeps the column, up to the end of a shorter row
+        press(&mut editor, Key::ArrowUp, NONE);
+        assert_eq!(editor.insertion_point(), 4);
+        assert_eq!(
+            render(&mut editor, &prompt, 20),
+            b"\r\x1b[1A\x1b[J\x1b[0 q> ls |\x1b[0m\r\n::: where x\x1b[0m\x1b[1A\r\x1b[6C"
+        );
+
+        // Line motions and kills stay on the cursor's row
+        chord(&mut editor, CTRL, 'a');
+        assert_eq!(editor.insertion_point(), 0);
+        chord(&mut editor, CTRL, 'e');
+        assert_eq!(editor.insertion_point(), 4);
+        chord(&mut editor, CTRL, 'a');
+        press(&mut editor, Key::ArrowDown, NONE);
+        assert_eq!(editor.insertion_point(), 5);
+        assert!(matches!(press(&mut editor, Key::ArrowDown, NONE), EditAction::None));
+        chord(&mut editor, CTRL, 'e');
+        assert_eq!(editor.insertion_point(), 12);
+
+        // A continuation line that wraps counts its indicator
+        assert_eq!(
+            render(&mut editor, &prompt, 10),
+            b"\r\x1b[J\x1b[0 q> ls |\x1b[0m\r\n::: where x\x1b[0m"
+        );
+        press(&mut editor, Key::Home, NONE);
+        assert_eq!(
+            render(&mut editor, &prompt, 10),
+            b"\r\x1b[2A\x1b[J\x1b[0 q> ls |\x1b[0m\r\n::: where x\x1b[0m\x1b[1A\r\x1b[4C"
+        );
+
+        chord(&mut editor, CTRL, 'k');
+        assert_eq!(editor.buffer(), "ls |\n");
+    }
+}
@@ -0,0 +1,188 @@
+//! Fonts: `[terminal.font]` mapped onto sugarloaf's `SugarloafFonts` (rio's
+//! `[fonts]` model), and the zoom shortcuts that change the size at runtime.
+
+use bevy::input::keyboard::Key;
+use bevy::log::warn;
+use cyb_services::config::{expand_path, FontConfig, FontFace};
+use sugarloaf::font::fonts::{self, SugarloafFont, SugarloafFonts};
+use sugarloaf::font::FontLibrary;
+
+use super::pty::Modifiers;
+
+/// Points one zoom step adds or takes away.
+const ZOOM_STEP: f32 = 1.0;
+
+/// Sizes zooming stays within, the range `terminal.font_size` allows.
+const MIN_FONT_SIZE: f32 = 4.0;
+const MAX_FONT_SIZE: f32 = 200.0;
+
+/// A font library for `config` at `size`, and the OpenType features to
+/// shape with. Faces that can't be found are reported; sugarloaf falls back
+/// to its bundled font for them.
+pub(super) fn load(config: &FontConfig, size: f32) -> (FontLibrary, Option<Vec<String>>) {
+    let (library, errors) = FontLibrary::new(spec(config, size));
+    for font in errors.map(|errors| errors.fonts_not_found).unwrap_or_default() {
+        warn!("Font {:?} (weight {:?}) not found, using the default", font.family, font.weight);
+    }
+
+    let mut features = config.features.clone();
+    if !config.ligatures {
+        features.extend(["-liga".to_string(), "-calt".to_string()]);
+    }
+    (library, (!features.is_empty()).then_some(features))
+}
+
+fn spec(config: &FontConfig, size: f32) -> SugarloafFonts {
+    // sugarloaf's own `family` would override the per-style faces, so the
+    // family goes into each face instead
+    let face = |default: SugarloafFont, face: &Option<FontFace>| {
+        let face = face.as_ref();
+        SugarloafFont {
+            family: face
+                .and_then(|face| face.family.clone())
+                .or_else(|| config.family.clone())
+                .unwrap_or(default.family),
+            weight: face.and_then(|face| face.weight).or(default.weight),
+            ..default
+        }
+    };
+    let symbol_map = config
+        .symbol_map
+        .iter()
+        .map(|map| fonts::SymbolMap {
+            start: map.start.clone(),
+            end: map.end.clone(),
+            font_family: map.family.clone(),
+        })
+        .collect::<Vec<_>>();
+    let additional_dirs = config
+        .additional_dirs
+        .iter()
+        .filter_map(|dir| expand_path(dir).ok())
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    SugarloafFonts {
+        size,
+        hinting: config.hinting,
+        regular: face(fonts::default_font_regular(), &config.regular),
+        bold: face(fonts::default_font_bold(), &config.bold),
+        italic: face(fonts::default_font_italic(), &config.italic),
+        bold_italic: face(fonts::default_font_bold_italic(), &config.bold_italic),
+        extras: config
+            .extras
+            .iter()
+            .map(|family| SugarloafFont { family: family.clone(), ..Default::default() })
+            .collect(),
+        use_drawable_chars: config.drawable_chars,
+        symbol_map: (!symbol_map.is_empty()).then_some(symbol_map),
+        additional_dirs: (!additional_dirs.is_empty()).then_some(additional_dirs),
+        ..Default::default()
+    }
+}
+
+/// Font size change requested by a key press.
+#[derive(Clone, Copy)]
+pub(super) enum Zoom {
+    In,
+    Out,
+    /// Back to `terminal.font_size`
+    Reset,
+}
+
+impl Zoom {
+    /// The size after zooming from `current`; `configured` is the size
+    /// reset goes back to.
+    pub fn apply(self, current: f32, configured: f32) -> f32 {
+        let size = match self {
+            Zoom::In => current + ZOOM_STEP,
+            Zoom::Out => current - ZOOM_STEP,
+            Zoom::Reset => configured,
+        };
+        size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE)
+    }
+}
+
+/// Cmd+= / Cmd+- / Cmd+0 on macOS, Ctrl+= / Ctrl+- / Ctrl+0 elsewhere, as
+/// in rio; `+` works as well as `=`. Ctrl+Shift+- stays Ctrl+_ (undo).
+pub(super) fn zoom_shortcut(key: &Key, cmd: bool, mods: Modifiers) -> Option<Zoom> {
+    let chord = if cfg!(target_os = "macos") { cmd && !mods.ctrl } else { mods.ctrl && !cmd };
+    if !chord || mods.alt {
+        return None;
+    }
+    match key {
+        Key::Character(c) if c == "=" || c == "+" => Some(Zoom::In),
+        Key::Character(c) if c == "-" && !mods.shift => Some(Zoom::Out),
+        Key::Character(c) if c == "0" && !mods.shift => Some(Zoom::Reset),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sugarloaf::font::fonts::SugarloafFontStyle;
+
+    use super::*;
+
+    /// The zoom chord without Shift: Cmd on macOS, Ctrl elsewhere.
+    fn chord(shift: bool) -> (bool, Modifiers) {
+        let macos = cfg!(target_os = "macos");
+        (macos, Modifiers { shift, alt: false, ctrl: !macos })
+    }
+
+    fn key(c: &str) -> Key {
+        Key::Character(c.into())
+    }
+
+    #[test]
+    fn test_zoom_clamps() {
+        assert_eq!(Zoom::In.apply(16.0, 16.0), 17.0);
+        assert_eq!(Zoom::Out.apply(16.0, 12.0), 15.0);
+        assert_eq!(Zoom::Reset.apply(30.0, 12.0), 12.0);
+
+        assert_eq!(Zoom::In.apply(199.5, 16.0), 200.0);
+        assert_eq!(Zoom::In.apply(200.0, 16.0), 200.0);
+        assert_eq!(Zoom::Out.apply(4.5, 16.0), 4.0);
+        assert_eq!(Zoom::Out.apply(4.0, 16.0), 4.0);
+        assert_eq!(Zoom::Reset.apply(16.0, 300.0), 200.0);
+    }
+
+    #[test]
+    fn test_zoom_shortcut() {
+        let (cmd, mods) = chord(false);
+        assert!(matches!(zoom_shortcut(&key("="), cmd, mods), Some(Zoom::In)));
+        assert!(matches!(zoom_shortcut(&key("-"), cmd, mods), Some(Zoom::Out)));
+        assert!(matches!(zoom_shortcut(&key("0"), cmd, mods), Some(Zoom::Reset)));
+        assert!(zoom_shortcut(&key("="), false, Modifiers::default()).is_none());
+        assert!(zoom_shortcut(&key("="), cmd, Modifiers { alt: true, ..mods }).is_none());
+
+        // Shift+= is +, but Ctrl+Shift+- is Ctrl+_, undo in the line editor
+        let (cmd, mods) = chord(true);
+        assert!(matches!(zoom_shortcut(&key("+"), cmd, mods), Some(Zoom::In)));
+        assert!(zoom_shortcut(&key("-"), cmd, mods).is_none());
+        assert!(zoom_shortcut(&key("_"), cmd, mods).is_none());
+        assert!(zoom_shortcut(&key("0"), cmd, mods).is_none());
+    }
+
+    #[test]
+    fn test_spec_faces_override_family() {
+        let config = FontConfig {
+            family: Some("Mono".to_string()),
+            bold: Some(FontFace { family: Some("Mono Heavy".to_string()), weight: Some(700) }),
+            italic: Some(FontFace { family: None, weight: Some(500) }),
+            ..Default::default()
+        };
+        let faces = spec(&config, 14.0);
+        assert_eq!(faces.size, 14.0);
+        assert_eq!((faces.regular.family.as_str(), faces.regular.weight), ("Mono", Some(400)));
+        assert_eq!((faces.bold.family.as_str(), faces.bold.weight), ("Mono Heavy", Some(700)));
+        assert_eq!((faces.italic.family.as_str(), faces.italic.weight), ("Mono", Some(500)));
+        assert_eq!(faces.italic.style, SugarloafFontStyle::Italic);
+        assert_eq!(faces.bold_italic.family, "Mono");
+        assert!(faces.symbol_map.is_none());
+        assert!(faces.additional_dirs.is_none());
+
+        let faces = spec(&FontConfig::default(), 14.0);
+        assert_eq!(faces.regular.family, fonts::default_font_regular().family);
+    }
+}
@@ -0,0 +1,89 @@
+//! Syntax highlighting of the input: nu-cli's `NuHighlighter` colors, with
+//! parse error spans underlined so mistakes show before Enter.
+
+use std::ops::Range;
+
+use nu_cli::NuHighlighter;
+use nu_parser::parse;
+use nu_protocol::ParseError;
+use nu_protocol::engine::StateWorkingSet;
+use reedline::Highlighter;
+
+use super::EngineSnapshot;
+
+/// `line` with ANSI styling; prints the same characters as `line`, with
+/// every newline outside escape sequences.
+pub(super) fn highlight(engine: &EngineSnapshot, line: &str, cursor: usize) -> String {
+    let highlighter = NuHighlighter {
+        engine_state: engine.engine_state.clone(),
+        stack: engine.stack.clone(),
+    };
+    let styled = highlighter.highlight(line, cursor);
+    if styled.buffer.iter().map(|(_, text)| text.len()).sum::<usize>() != line.len() {
+        return line.to_string();
+    }
+
+    let errors = parse_errors(engine, line);
+    let in_error = |offset: usize| errors.iter().any(|range| range.contains(&offset));
+
+    let mut out = String::new();
+    let mut offset = 0;
+    for (style, text) in &styled.buffer {
+        // Newlines stay outside the escapes, so the editor can start each
+        // continuation line with the multiline indicator
+        let mut paint = |part: &str, underline: bool| {
+            let style = if underline { style.underline() } else { *style };
+            for (i, piece) in part.split('\n').enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                }
+                if !piece.is_empty() {
+                    out.push_str(&style.paint(piece).to_string());
+                }
+            }
+        };
+        let mut run: Option<(usize, bool)> = None;
+        for (i, _) in text.char_indices() {
+            let underline = in_error(offset + i);
+            match run {
+                Some((start, current)) if current != underline => {
+                    paint(&text[start..i], current);
+                    run = Some((i, underline));
+                }
+                Some(_) => {}
+                None => run = Some((i, underline)),
+            }
+        }
+        if let Some((start, underline)) = run {
+            paint(&text[start..], underline);
+        }
+        offset += text.len();
+    }
+    out
+}
+
+/// Byte ranges of `line` covered by parse errors, widened to at least one
+/// character. Unexpected end of input is skipped: the line is still being
+/// typed.
+fn parse_errors(engine: &EngineSnapshot, line: &str) -> Vec<Range<usize>> {
+    let mut working_set = StateWorkingSet::new(&engine.engine_state);
+    let base = working_set.next_span_start();
+    parse(&mut working_set, Some("input"), line.as_bytes(), false);
+
+    working_set
+        .parse_errors
+        .iter()
+        .filter(|err| !matches!(err, ParseError::UnexpectedEof(..)))
+        .filter_map(|err| {
+            let span = err.span();
+            let start = span.start.checked_sub(base)?.min(line.len());
+            let end = span.end.saturating_sub(base).clamp(start, line.len());
+            if start < end {
+                return Some(start..end);
+            }
+            // Point at the character before an empty span at the end
+            let (i, c) = line[..start].char_indices().next_back()?;
+            Some(i..i + c.len_utf8())
+        })
+        .collect()
+}
@@ -0,0 +1,378 @@
+//! Hints: URLs, IPFS CIDs and file paths in the visible cells, after
+//! rio-backend's `config::hints` model of regex rules with an action each.
+//! With the hint modifier held, the hint under the pointer is underlined and
+//! a click opens it; Ctrl+Shift+O labels every hint on screen for the
+//! keyboard instead.
+
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::{Boundary, Column, Direction, Line, Point};
+use alacritty_terminal::term::search::{RegexIter, RegexSearch};
+use alacritty_terminal::Term;
+use bevy::input::keyboard::Key;
+use bevy::log::warn;
+
+use super::pty::Modifiers;
+
+/// Characters hint labels are made of, home row first (rio's default).
+const DEFAULT_HINTS_ALPHABET: &str = "jfkdls;ahgurieowpq";
+
+/// rio's (and Alacritty's) URL rule.
+const DEFAULT_URL_REGEX: &str = "(ipfs:|ipns:|magnet:|mailto:|gemini://|gopher://|https://|http://|news:|file:|git://|ssh:|ftp://)[^\u{0000}-\u{001F}\u{007F}-\u{009F}<>\"\\s{-}\\^⟨⟩`\\\\]+";
+
+/// Bare IPFS CIDs: v0 (base58 `Qm...`) and v1 in base32 (`baf...`).
+const CID_REGEX: &str = "Qm[1-9A-HJ-NP-Za-km-z]{44}|baf[a-z2-7]{56,}";
+
+/// Absolute, home, dot-relative and `dir/file` paths. Colons end them, so
+/// `src/main.rs:12:5` from a compiler finds the file.
+const PATH_REGEX: &str = "(~|\\.\\.?)?/[^\u{0000}-\u{0020}\u{007F}\"'`<>|;:()\\[\\]{}]+|[A-Za-z0-9_.-]+/[^\u{0000}-\u{0020}\u{007F}\"'`<>|;:()\\[\\]{}]*";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum HintKind {
+    Url,
+    Cid,
+    Path,
+}
+
+/// What opening a hint does.
+#[derive(Clone)]
+pub(super) enum HintAction {
+    /// `http(s)://` URLs load in the Legacy world's webview
+    Legacy(String),
+    /// `ipfs:` URLs and bare CIDs show as a particle in the Portal world
+    Particle(String),
+    /// Other schemes (`mailto:`, `magnet:`, `ssh:`, ...) go to the system opener
+    System(String),
+    /// Paths run `cd` (directories) or `open` (files) at the prompt
+    Command(String),
+}
+
+/// A hint found on screen.
+#[derive(Clone)]
+pub(super) struct Hint {
+    pub range: RangeInclusive<Point>,
+    pub action: HintAction,
+}
+
+/// The compiled rules, tried in order; an earlier rule wins where matches
+/// overlap, so a URL isn't also taken apart as a path.
+pub(super) struct Hints {
+    rules: Vec<(HintKind, RegexSearch)>,
+}
+
+impl Hints {
+    pub fn new() -> Self {
+        let rules = [(HintKind::Url, DEFAULT_URL_REGEX), (HintKind::Cid, CID_REGEX), (HintKind::Path, PATH_REGEX)]
+            .into_iter()
+            .filter_map(|(kind, regex)| match RegexSearch::new(regex) {
+                Ok(regex) => Some((kind, regex)),
+                Err(e) => {
+                    warn!("Invalid hint regex {:?}: {}", regex, e);
+                    None
+                }
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Hints on screen in grid order. Paths count only when they exist;
+    /// relative ones need `cwd`.
+    pub fn visible<T>(&mut self, term: &Term<T>, cwd: Option<&Path>) -> Vec<Hint> {
+        let offset = term.grid().display_offset() as i32;
+        let start = Point::new(Line(-offset), Column(0));
+        let end = Point::new(Line(term.screen_lines() as i32 - 1 - offset), Column(term.columns() - 1));
+
+        let mut hints: Vec<Hint> = Vec::new();
+        for (kind, regex) in &mut self.rules {
+            for found in RegexIter::new(start, end, Direction::Right, term, regex) {
+                let text = term.bounds_to_string(*found.start(), *found.end());
+                let chars = text.chars().count();
+                let dropped = trailing_junk(&text);
+                if dropped == chars {
+                    continue;
+                }
+                let range = *found.start()..=found.end().sub(term, Boundary::Grid, dropped);
+                if hints.iter().any(|hint| overlaps(&hint.range, &range)) {
+                    continue;
+                }
+                let text: String = text.chars().take(chars - dropped).collect();
+                if let Some(action) = action(*kind, &text, cwd) {
+                    hints.push(Hint { range, action });
+                }
+            }
+        }
+        hints.sort_by_key(|hint| *hint.range.start());
+        hints
+    }
+
+    /// The hint covering `point`, if any.
+    pub fn at<T>(&mut self, term: &Term<T>, point: Point, cwd: Option<&Path>) -> Option<Hint> {
+        self.visible(term, cwd).into_iter().find(|hint| hint.range.contains(&point))
+    }
+}
+
+/// Keyboard hint mode: each hint on screen gets a label; typing one opens it.
+pub(super) struct HintMode {
+    hints: Vec<(String, Hint)>,
+    typed: String,
+}
+
+/// What hint mode did with a key.
+pub(super) enum HintModeAction {
+    /// Typed label is still a prefix of some labels
+    Continue,
+    /// A label was completed
+    Open(HintAction),
+    /// Escape, or nothing to label
+    Exit,
+}
+
+impl HintMode {
+    pub fn new(hints: Vec<Hint>) -> Self {
+        let labels = labels(hints.len(), DEFAULT_HINTS_ALPHABET);
+        Self {
+            hints: labels.into_iter().zip(hints).collect(),
+            typed: String::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+
+    pub fn handle_key(&mut self, key: &Key) -> HintModeAction {
+        match key {
+            Key::Escape => return HintModeAction::Exit,
+            Key::Backspace => {
+                self.typed.pop();
+            }
+            Key::Character(c) => {
+                self.typed.push_str(&c.to_lowercase());
+                let mut matching = self.hints.iter().filter(|(label, _)| label.starts_with(&self.typed));
+                match (matching.next(), matching.next()) {
+                    (None, _) => {
+                        self.typed.truncate(self.typed.len() - c.to_lowercase().len());
+                    }
+                    (Some((label, hint)), None) if *label == self.typed => {
+                        return HintModeAction::Open(hint.action.clone());
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        HintModeAction::Continue
+    }
+
+    /// Label characters still to type, drawn over the start of each hint.
+    pub fn label_cells<T>(&self, term: &Term<T>) -> Vec<(Point, char)> {
+        let mut cells = Vec::new();
+        for (label, hint) in &self.hints {
+            let Some(rest) = label.strip_prefix(self.typed.as_str()) else { continue };
+            for (i, c) in rest.chars().enumerate() {
+                cells.push((hint.range.start().add(term, Boundary::Grid, i), c));
+            }
+        }
+        cells
+    }
+}
+
+/// Rio's default hint binding, Ctrl+Shift+O.
+pub(super) fn is_hint_mode_shortcut(key: &Key, mods: Modifiers) -> bool {
+    mods.ctrl && mods.shift && matches!(key, Key::Character(c) if c.eq_ignore_ascii_case("o"))
+}
+
+/// Rio's default hint mouse modifier: Cmd on macOS, Alt elsewhere.
+pub(super) fn mouse_modifier_held(cmd: bool, mods: Modifiers) -> bool {
+    if cfg!(target_os = "macos") { cmd } else { mods.alt }
+}
+
+/// Open `target` with `open` on macOS or `xdg-open` elsewhere, rio's
+/// default URL command.
+pub(super) fn open_with_system(target: &str) {
+    let opener = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+    if let Err(e) = std::process::Command::new(opener).arg(target).spawn() {
+        warn!("{} {}: {}", opener, target, e);
+    }
+}
+
+fn action(kind: HintKind, text: &str, cwd: Option<&Path>) -> Option<HintAction> {
+    match kind {
+        HintKind::Url => Some(if let Some(rest) = text.strip_prefix("ipfs:") {
+            let cid = rest.trim_start_matches('/').split(['/', '?', '#']).next()?;
+            HintAction::Particle(cid.to_string())
+        } else if text.starts_with("https://") || text.starts_with("http://") {
+            HintAction::Legacy(text.to_string())
+        } else {
+            HintAction::System(text.to_string())
+        }),
+        HintKind::Cid => Some(HintAction::Particle(text.to_string())),
+        HintKind::Path => {
+            let path = resolve(text, cwd)?;
+            let verb = if std::fs::metadata(&path).ok()?.is_dir() { "cd" } else { "open" };
+            Some(HintAction::Command(format!("{} {}", verb, nu_quote(&path.to_string_lossy()))))
+        }
+    }
+}
+
+fn resolve(text: &str, cwd: Option<&Path>) -> Option<PathBuf> {
+    if text == "~" || text.starts_with("~/") {
+        let home = std::env::var("HOME").ok()?;
+        return Some(Path::new(&home).join(text.trim_start_matches('~').trim_start_matches('/')));
+    }
+    let path = Path::new(text);
+    if path.is_absolute() { Some(path.to_path_buf()) } else { Some(cwd?.join(path)) }
+}
+
+/// `text` as a nushell string literal: raw single quotes when possible.
+fn nu_quote(text: &str) -> String {
+    if !text.contains('\'') {
+        format!("'{}'", text)
+    } else if !text.contains('`') {
+        format!("`{}`", text)
+    } else {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Characters at the end of a match that end the sentence rather than the
+/// link: punctuation, quotes, and closing brackets without an opener.
+fn trailing_junk(text: &str) -> usize {
+    let mut kept = text;
+    let mut dropped = 0;
+    while let Some(c) = kept.chars().next_back() {
+        let junk = match c {
+            '.' | ',' | ':' | ';' | '?' | '!' | '\'' | '"' => true,
+            ')' => kept.matches(')').count() > kept.matches('(').count(),
+            ']' => kept.matches(']').count() > kept.matches('[').count(),
+            _ => false,
+        };
+        if !junk {
+            break;
+        }
+        kept = &kept[..kept.len() - c.len_utf8()];
+        dropped += 1;
+    }
+    dropped
+}
+
+fn overlaps(a: &RangeInclusive<Point>, b: &RangeInclusive<Point>) -> bool {
+    a.start() <= b.end() && b.start() <= a.end()
+}
+
+/// `count` distinct labels: single characters while the alphabet lasts,
+/// otherwise pairs of them, so no label is a prefix of another.
+fn labels(count: usize, alphabet: &str) -> Vec<String> {
+    let chars: Vec<char> = alphabet.chars().collect();
+    if count <= chars.len() {
+        return chars.iter().take(count).map(|c| c.to_string()).collect();
+    }
+    chars
+        .iter()
+        .flat_map(|a| chars.iter().map(move |b| format!("{}{}", a, b)))
+        .take(count)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use alacritty_terminal::term::test::mock_term;
+
+    use super::*;
+
+    fn qm() -> String {
+        format!("Qm{}", "Y".repeat(44))
+    }
+
+    fn bafy() -> String {
+        format!("bafy{}", "b".repeat(55))
+    }
+
+    #[test]
+    fn test_trailing_junk() {
+        assert_eq!(trailing_junk("https://x.y/a_(b)."), 1);
+        assert_eq!(trailing_junk("https://x.y/a_b)."), 2);
+        assert_eq!(trailing_junk("https://x.y/[a]]\"!"), 3);
+        assert_eq!(trailing_junk("src/main.rs"), 0);
+        assert_eq!(trailing_junk(".,;"), 3);
+    }
+
+    #[test]
+    fn test_labels() {
+        let alphabet_len = DEFAULT_HINTS_ALPHABET.chars().count();
+        assert_eq!(alphabet_len, 18);
+        assert_eq!(labels(3, DEFAULT_HINTS_ALPHABET), ["j", "f", "k"]);
+        assert_eq!(labels(18, DEFAULT_HINTS_ALPHABET).last().map(String::as_str), Some("q"));
+
+        let pairs = labels(19, DEFAULT_HINTS_ALPHABET);
+        assert_eq!(pairs.len(), 19);
+        assert_eq!(pairs[..3], ["jj", "jf", "jk"]);
+        // Same length and distinct, so none is a prefix of another
+        assert!(pairs.iter().all(|label| label.chars().count() == 2));
+        let mut distinct = pairs.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), pairs.len());
+    }
+
+    #[test]
+    fn test_nu_quote() {
+        assert_eq!(nu_quote("/tmp/a b"), "'/tmp/a b'");
+        assert_eq!(nu_quote("/tmp/it's"), "`/tmp/it's`");
+        assert_eq!(nu_quote("/tmp/it's `x` \"y\" \\"), "\"/tmp/it's `x` \\\"y\\\" \\\\\"");
+    }
+
+    #[test]
+    fn test_url_actions() {
+        let particle = |text: &str| match action(HintKind::Url, text, None) {
+            Some(HintAction::Particle(cid)) => Some(cid),
+            _ => None,
+        };
+        assert_eq!(particle(&format!("ipfs://{}/path", qm())), Some(qm()));
+        assert_eq!(particle(&format!("ipfs:{}?filename=a", bafy())), Some(bafy()));
+        let url = action(HintKind::Url, "https://x.y/a", None);
+        assert!(matches!(url, Some(HintAction::Legacy(url)) if url == "https://x.y/a"));
+        let mailto = action(HintKind::Url, "mailto:a@x.y", None);
+        assert!(matches!(mailto, Some(HintAction::System(_))));
+        assert!(matches!(action(HintKind::Cid, &bafy(), None), Some(HintAction::Particle(cid)) if cid == bafy()));
+    }
+
+    #[test]
+    fn test_visible_hints() {
+        let cwd = std::env::temp_dir().join(format!("cyb-hints-test-{}", std::process::id()));
+        std::fs::create_dir_all(cwd.join("src")).unwrap();
+        std::fs::write(cwd.join("src/main.rs"), "").unwrap();
+
+        let screen = format!(
+            "see https://x.y/a_(b). now\r\nipfs://{}/path\r\ncid {} ok\r\nsrc/main.rs:12:5 nope/x",
+            qm(),
+            bafy()
+        );
+        let term = mock_term(&screen);
+        let hints = Hints::new().visible(&term, Some(&cwd));
+        let _ = std::fs::remove_dir_all(&cwd);
+
+        assert_eq!(hints.len(), 4);
+        let at = |line: i32, column: usize| Point::new(Line(line), Column(column));
+
+        assert_eq!(hints[0].range, at(0, 4)..=at(0, 20));
+        assert!(matches!(&hints[0].action, HintAction::Legacy(url) if url == "https://x.y/a_(b)"));
+
+        // The URL rule wins over the CID and path inside it
+        assert_eq!(*hints[1].range.start(), at(1, 0));
+        assert!(matches!(&hints[1].action, HintAction::Particle(cid) if *cid == qm()));
+
+        assert_eq!(hints[2].range, at(2, 4)..=at(2, 62));
+        assert!(matches!(&hints[2].action, HintAction::Particle(cid) if *cid == bafy()));
+
+        // The colon ends the path; `nope/x` doesn't exist
+        assert_eq!(hints[3].range, at(3, 0)..=at(3, 10));
+        let open = format!("open '{}'", cwd.join("src/main.rs").display());
+        assert!(matches!(&hints[3].action, HintAction::Command(command) if *command == open));
+
+        // Relative paths need a directory to resolve against
+        assert_eq!(Hints::new().visible(&term, None).len(), 3);
+    }
+}
@@ -0,0 +1,240 @@
+//! Command history on nushell's backends, as `$env.config.history` asks:
+//! reedline's `SqliteBackedHistory`, where each entry keeps its cwd, exit
+//! status, duration and start time, or a plain text file.
+
+use std::path::Path;
+use std::time::Instant;
+
+use bevy::prelude::*;
+use nu_protocol::config::{HistoryConfig, HistoryFileFormat};
+use reedline::{
+    CommandLineSearch, FileBackedHistory, History, HistoryItem, HistoryItemId, HistorySessionId,
+    Reedline, SearchDirection, SearchFilter, SearchQuery, SqliteBackedHistory,
+};
+
+/// How an entry must match the searched text.
+#[derive(Clone, Copy)]
+pub(super) enum Matching {
+    /// Up/Down: entries starting with the line typed so far
+    Prefix,
+    /// Ctrl+R: entries containing the text
+    Substring,
+}
+
+pub(super) struct CommandHistory {
+    backend: Box<dyn History>,
+    session: Option<HistorySessionId>,
+    /// Only this session's entries are searched (`history.isolation`)
+    isolation: bool,
+    sync_on_enter: bool,
+    /// The backend stores cwd, exit status and duration
+    detailed: bool,
+    /// Entry of the command running now, completed by [`finish`](Self::finish)
+    running: Option<(HistoryItemId, Instant)>,
+}
+
+impl CommandHistory {
+    /// Kept in memory only; used until the configured file is opened, or
+    /// when it can't be.
+    pub fn in_memory() -> Self {
+        Self {
+            backend: Box::<FileBackedHistory>::default(),
+            session: None,
+            isolation: false,
+            sync_on_enter: false,
+            detailed: false,
+            running: None,
+        }
+    }
+
+    /// `history.sqlite3` or `history.txt` in `dir`, per `config.file_format`.
+    /// The plain text file holds at most `config.max_size` lines.
+    pub fn open(config: &HistoryConfig, dir: &Path) -> reedline::Result<Self> {
+        let session = Reedline::create_history_session_id();
+        let (backend, detailed): (Box<dyn History>, bool) = match config.file_format {
+            HistoryFileFormat::Sqlite => {
+                let file = dir.join("history.sqlite3");
+                (Box::new(SqliteBackedHistory::with_file(file, session, Some(chrono::Utc::now()))?), true)
+            }
+            HistoryFileFormat::Plaintext => {
+                let capacity = config.max_size.max(0) as usize;
+                (Box::new(FileBackedHistory::with_file(capacity, dir.join("history.txt"))?), false)
+            }
+        };
+        Ok(Self {
+            backend,
+            session,
+            isolation: config.isolation,
+            sync_on_enter: config.sync_on_enter,
+            detailed,
+            running: None,
+        })
+    }
+
+    /// Record `line` as started now in `cwd`.
+    pub fn start(&mut self, line: &str, cwd: Option<String>) {
+        let item = HistoryItem {
+            start_timestamp: Some(chrono::Utc::now()),
+            session_id: self.session,
+            cwd,
+            ..HistoryItem::from_command_line(line)
+        };
+        self.running = match self.backend.save(item) {
+            Ok(item) => item.id.map(|id| (id, Instant::now())),
+            Err(e) => {
+                warn!("History: {}", e);
+                None
+            }
+        };
+        if self.sync_on_enter && let Err(e) = self.backend.sync() {
+            warn!("History: {}", e);
+        }
+    }
+
+    /// Complete the running command's entry with its exit status and how
+    /// long it took. Does nothing when no command was started.
+    pub fn finish(&mut self, exit_status: i64) {
+        let Some((id, started)) = self.running.take() else {
+            return;
+        };
+        if !self.detailed {
+            return;
+        }
+        let duration = started.elapsed();
+        let update = move |item: HistoryItem| HistoryItem {
+            duration: Some(duration),
+            exit_status: Some(exit_status),
+            ..item
+        };
+        if let Err(e) = self.backend.update(id, &update) {
+            warn!("History: {}", e);
+        }
+    }
+
+    /// The next entry from `from` (the newest or oldest end when `None`)
+    /// in `direction` that matches `text`, skipping ones equal to `skip` so
+    /// repeated commands show once.
+    pub fn find(
+        &self,
+        text: &str,
+        matching: Matching,
+        from: Option<HistoryItemId>,
+        direction: SearchDirection,
+        skip: &str,
+    ) -> Option<HistoryItem> {
+        let session = if self.isolation { self.session } else { None };
+        let mut from = from;
+        loop {
+            let search = match matching {
+                Matching::Prefix => CommandLineSearch::Prefix(text.to_string()),
+                Matching::Substring => CommandLineSearch::Substring(text.to_string()),
+            };
+            let mut query = SearchQuery::last_with_search(SearchFilter::from_text_search(search, session));
+            query.direction = direction;
+            query.start_id = from;
+            let item = match self.backend.search(query) {
+                Ok(mut items) => items.pop()?,
+                Err(e) => {
+                    warn!("History: {}", e);
+                    return None;
+                }
+            };
+            if item.command_line != skip {
+                return Some(item);
+            }
+            from = Some(item.id?);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sqlite_history(lines: &[&str]) -> CommandHistory {
+        let mut history = CommandHistory {
+            backend: Box::new(SqliteBackedHistory::in_memory().unwrap()),
+            session: None,
+            isolation: false,
+            sync_on_enter: false,
+            detailed: true,
+            running: None,
+        };
+        for line in lines {
+            history.start(line, None);
+            history.finish(0);
+        }
+        history
+    }
+
+    /// Newest match older than `from`, as Up and Ctrl+R search.
+    fn older(history: &CommandHistory, text: &str, matching: Matching, from: Option<HistoryItemId>) -> Option<String> {
+        let item = history.find(text, matching, from, SearchDirection::Backward, "")?;
+        Some(item.command_line)
+    }
+
+    #[test]
+    fn test_find_prefix_and_substring() {
+        let history = sqlite_history(&["git status", "ls", "git commit", "echo git"]);
+
+        assert_eq!(
+            older(&history, "git", Matching::Prefix, None).as_deref(),
+            Some("git commit")
+        );
+        assert_eq!(
+            older(&history, "git", Matching::Substring, None).as_deref(),
+            Some("echo git")
+        );
+        assert_eq!(older(&history, "com", Matching::Prefix, None), None);
+
+        let oldest = history
+            .find("git", Matching::Prefix, None, SearchDirection::Forward, "")
+            .unwrap();
+        assert_eq!(oldest.command_line, "git status");
+        let next = history
+            .find("git", Matching::Prefix, oldest.id, SearchDirection::Forward, "")
+            .unwrap();
+        assert_eq!(next.command_line, "git commit");
+        assert_eq!(
+            older(&history, "git", Matching::Prefix, next.id).as_deref(),
+            Some("git status")
+        );
+    }
+
+    #[test]
+    fn test_find_skips_duplicates() {
+        let history = sqlite_history(&["git status", "git commit", "git commit", "git commit"]);
+        let newest = history
+            .find("git", Matching::Prefix, None, SearchDirection::Backward, "")
+            .unwrap();
+        assert_eq!(newest.command_line, "git commit");
+
+        let skip = newest.command_line.as_str();
+        let next = history
+            .find("git", Matching::Prefix, newest.id, SearchDirection::Backward, skip)
+            .unwrap();
+        assert_eq!(next.command_line, "git status");
+        // Every match equals the skipped line
+        assert!(
+            history
+                .find("git c", Matching::Substring, None, SearchDirection::Backward, skip)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_finish_records_exit_status_and_duration() {
+        let mut history = sqlite_history(&[]);
+        history.start("sleep 1", Some("/tmp".to_string()));
+        history.finish(2);
+        // Nothing is running any more, so this is ignored
+        history.finish(0);
+
+        let item = history
+            .find("sleep", Matching::Prefix, None, SearchDirection::Backward, "")
+            .unwrap();
+        assert_eq!(item.exit_status, Some(2));
+        assert!(item.duration.is_some());
+        assert_eq!(item.cwd.as_deref(), Some("/tmp"));
+    }
+}
@@ -0,0 +1,2364 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use alacritty_terminal::event::{Event, EventListener};
+use alacritty_terminal::grid::{Dimensions, Scroll};
+use alacritty_terminal::index::Point;
+use alacritty_terminal::sync::FairMutex;
+use alacritty_terminal::term::cell::Flags;
+use alacritty_terminal::term::Config as TermConfig;
+use alacritty_terminal::term::{TermDamage, TermMode};
+use alacritty_terminal::vte::ansi::{Color, CursorShape, NamedColor, Processor, Rgb};
+use alacritty_terminal::Term;
+
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::mouse::{AccumulatedMouseScroll, MouseScrollUnit};
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowTheme, WindowThemeChanged};
+
+use nu_cli::{gather_parent_env_vars, eval_source, NuValidator};
+use nu_cmd_lang::create_default_context;
+use nu_command::add_shell_command_context;
+use nu_engine::eval_block;
+use nu_parser::parse;
+use nu_protocol::engine::{EngineState, Redirection, Stack, StateWorkingSet};
+use nu_protocol::debugger::WithoutDebug;
+use nu_protocol::{ByteStream, ByteStreamSource, OutDest, PipelineData, Signals, Span, Value};
+use nu_engine::ClosureEvalOnce;
+use nu_std::load_standard_library;
+use reedline::{ValidationResult, Validator};
+
+use sugarloaf::{
+    FragmentStyle, FragmentStyleDecoration, Object, RichText, Sugarloaf,
+    SugarloafWindowSize, UnderlineInfo, UnderlineShape,
+};
+use sugarloaf::context::Context as SugarloafContext;
+use sugarloaf::layout::RootStyle;
+
+use super::{OpenRequests, WorldState};
+use crate::shell::config::Config;
+use crate::shell::env::home_dir;
+use crate::shell::logs::ServiceLogs;
+use cyb_services::config::{expand_path, AdaptiveTheme, FontConfig, TerminalConfig};
+use cyb_services::logging::RecentLogs;
+
+mod commands;
+mod completion;
+mod editor;
+mod fonts;
+mod highlight;
+mod hints;
+mod history;
+mod pty;
+mod search;
+mod selection;
+mod tabs;
+mod target;
+mod theme;
+
+use editor::{EditAction, LineEditor, Prompt};
+use hints::{Hint, HintAction, HintMode, HintModeAction, Hints};
+use history::CommandHistory;
+use pty::{GridSize, Modifiers, PtySession};
+use search::{ScrollbackSearch, SearchAction};
+use selection::{ClipboardAction, MouseSelection};
+use tabs::{Rect, SessionId, TabAction, Tabs};
+use target::RenderTarget;
+use theme::{Theme, ThemeSwitch};
+
+const NU_ENV_SOURCE: &str = include_str!("../../../assets/nu-config/env.nu");
+const NU_CONFIG_SOURCE: &str = include_str!("../../../assets/nu-config/config.nu");
+
+pub struct TerminalWorldPlugin;
+
+#[derive(Component)]
+struct TerminalMarker;
+
+impl Plugin for TerminalWorldPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(WorldState::Terminal), setup_terminal)
+            .add_systems(OnExit(WorldState::Terminal), destroy_terminal)
+            .add_systems(
+                Update,
+                (
+                    terminal_update,
+                )
+                    .run_if(in_state(WorldState::Terminal)),
+            )
+            .init_resource::<SystemTheme>()
+            .add_systems(
+                Update,
+                (
+                    track_system_theme,
+                    apply_terminal_config.run_if(resource_changed::<Config>.or(resource_changed::<SystemTheme>)),
+                )
+                    .chain(),
+            );
+        target::build(app);
+    }
+}
+
+/// The system's light or dark appearance, as the primary window last
+/// reported it; picks the theme under `terminal.adaptive_theme`.
+#[derive(Resource, Default)]
+struct SystemTheme(Option<WindowTheme>);
+
+fn track_system_theme(
+    mut changes: MessageReader<WindowThemeChanged>,
+    primary: Query<(), With<PrimaryWindow>>,
+    mut system_theme: ResMut<SystemTheme>,
+) {
+    for change in changes.read() {
+        if primary.contains(change.window) && system_theme.0 != Some(change.theme) {
+            system_theme.0 = Some(change.theme);
+        }
+    }
+}
+
+// --- Colors ---
+
+/// `color` as the grid cell shows it: colors set by the program (OSC 4/10/11)
+/// win over the theme's.
+fn resolve_color(color: Color, colors: &alacritty_terminal::term::color::Colors, theme: &Theme) -> Rgb {
+    match color {
+        Color::Spec(rgb) => rgb,
+        Color::Named(named) => colors[named].unwrap_or_else(|| theme.named(named)),
+        Color::Indexed(idx) => {
+            if let Some(rgb) = colors[idx as usize] {
+                return rgb;
+            }
+            if idx < 16 {
+                theme.ansi[idx as usize]
+            } else if idx < 232 {
+                let i = idx - 16;
+                let r = (i / 36) % 6;
+                let g = (i / 6) % 6;
+                let b = i % 6;
+                let to_byte = |v: u8| -> u8 { if v == 0 { 0 } else { 55 + 40 * v } };
+                Rgb { r: to_byte(r), g: to_byte(g), b: to_byte(b) }
+            } else {
+                let level = 8 + 10 * (idx - 232);
+                Rgb { r: level, g: level, b: level }
+            }
+        }
+    }
+}
+
+fn rgb_to_f32(rgb: Rgb) -> [f32; 4] {
+    [rgb.r as f32 / 255.0, rgb.g as f32 / 255.0, rgb.b as f32 / 255.0, 1.0]
+}
+
+fn rgb_to_color(rgb: Rgb) -> bevy::color::Color {
+    bevy::color::Color::srgb_u8(rgb.r, rgb.g, rgb.b)
+}
+
+// --- Event listener for alacritty_terminal ---
+
+#[derive(Clone)]
+struct BevyEventProxy {
+    /// Replies to terminal queries (cursor position, device attributes),
+    /// forwarded to the running PTY program
+    pty_replies: Arc<Mutex<Vec<u8>>>,
+}
+
+impl EventListener for BevyEventProxy {
+    fn send_event(&self, event: Event) {
+        match event {
+            Event::Title(title) => debug!("Terminal title: {}", title),
+            Event::Bell => debug!("Terminal bell"),
+            Event::PtyWrite(text) => self.pty_replies.lock().unwrap().extend_from_slice(text.as_bytes()),
+            _ => {}
+        }
+    }
+}
+
+// --- Dimensions impl ---
+
+struct TermDimensions {
+    cols: usize,
+    lines: usize,
+}
+
+impl Dimensions for TermDimensions {
+    fn total_lines(&self) -> usize { self.lines }
+    fn screen_lines(&self) -> usize { self.lines }
+    fn columns(&self) -> usize { self.cols }
+}
+
+// --- Nushell engine wrapper ---
+
+struct NuShellEngine {
+    engine_state: EngineState,
+    stack: Stack,
+}
+
+/// The engine as of the last prompt, shared with completion and
+/// highlighting while a line is edited.
+struct EngineSnapshot {
+    engine_state: Arc<EngineState>,
+    stack: Arc<Stack>,
+}
+
+// --- Messages from the eval thread ---
+
+/// Output is sent as the command produces it; `Done` hands the engine back.
+enum EvalMessage {
+    Output(Vec<u8>),
+    Done {
+        error: Option<String>,
+        engine: NuShellEngine,
+    },
+}
+
+/// Chunks queued by the eval thread before it waits for the terminal to
+/// catch up, so a flood of output can't grow memory without bound.
+const EVAL_CHANNEL_BOUND: usize = 64;
+
+/// Output chunks fed into the grid per frame.
+const EVAL_CHUNKS_PER_FRAME: usize = 32;
+
+/// Read size for byte streams.
+const STREAM_CHUNK_SIZE: usize = 8192;
+
+/// Chunks a stream's reader thread reads ahead of the eval thread.
+const STREAM_READ_AHEAD: usize = 16;
+
+/// How often the eval thread checks for Ctrl+C while a stream is quiet.
+const INTERRUPT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+// --- Sessions ---
+
+/// One shell: its own nushell engine, grid, line editor and history, shown
+/// in a pane. Sessions in hidden tabs and unfocused panes keep running.
+struct Session {
+    nu_engine: Option<NuShellEngine>,
+    term: Arc<FairMutex<Term<BevyEventProxy>>>,
+    processor: Processor,
+    editor: LineEditor,
+    /// Last evaluated prompt, repainted with the input on every edit
+    prompt: Prompt,
+    snapshot: Option<EngineSnapshot>,
+    /// Grid size, following the session's pane
+    cols: usize,
+    rows: usize,
+    /// Cell size in physical pixels, reported to PTY programs
+    cell: Vec2,
+    rich_text_id: usize,
+    eval_rx: Option<std::sync::mpsc::Receiver<EvalMessage>>,
+    eval_in_progress: bool,
+    ctrlc_flag: Arc<AtomicBool>,
+    /// External command running on a PTY; keystrokes go to it while set
+    pty: Option<PtySession>,
+    pty_replies: Arc<Mutex<Vec<u8>>>,
+    mouse: MouseSelection,
+    /// Open scrollback search; the keyboard goes to it first
+    search: Option<ScrollbackSearch>,
+    hints: Hints,
+    /// Cell under the pointer while the hint modifier is held
+    hover_cell: Option<Point>,
+    /// Hint under the pointer, underlined
+    hover: Option<Hint>,
+    /// Keyboard hint mode, with a label on every hint
+    hint_mode: Option<HintMode>,
+    /// Tab bar name: the running command, or the directory at the prompt
+    title: String,
+    /// Repaint even without grid damage (selection, search, hints changed)
+    dirty: bool,
+}
+
+// --- NonSend terminal state (contains !Sync types) ---
+
+struct TerminalNonSendState {
+    sessions: HashMap<SessionId, Session>,
+    next_session_id: SessionId,
+    tabs: Tabs,
+    /// Tab titles across the top row, shown while there is more than one tab
+    tab_bar_id: usize,
+    /// Session the left button went down in; dragging keeps selecting there
+    drag_session: Option<SessionId>,
+    sugarloaf: Sugarloaf<'static>,
+    image_handle: Handle<Image>,
+    key_cursor: bevy::ecs::message::MessageCursor<KeyboardInput>,
+    last_width: u32,
+    last_height: u32,
+    force_full_render: bool,
+    /// Size in effect, zoomed or not; every session uses it
+    font_size: f32,
+    /// `terminal.font_size`, which resetting the zoom goes back to
+    config_font_size: f32,
+    /// `[terminal.font]` as last loaded
+    font_config: FontConfig,
+    /// Backs `cyb logs`; kept to register it in new and rebuilt engines
+    service_logs: Option<RecentLogs>,
+    theme: Theme,
+    /// Backs `cyb theme`, shared with every engine
+    theme_switch: ThemeSwitch,
+    /// Theme picked with `cyb theme`; wins over the config until its theme
+    /// settings change
+    theme_override: Option<String>,
+    /// `theme` and `adaptive_theme` as last applied from the config
+    theme_settings: (String, Option<AdaptiveTheme>),
+    /// System clipboard; `None` when it can't be opened
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl TerminalNonSendState {
+    fn focused(&mut self) -> &mut Session {
+        let id = self.tabs.focused();
+        self.sessions.get_mut(&id).expect("focused session exists")
+    }
+
+    /// Cell size in physical pixels; every session uses the same font.
+    fn cell_size(&mut self) -> Vec2 {
+        let dims = self.sugarloaf.get_rich_text_dimensions(&self.tab_bar_id);
+        Vec2::new(
+            if dims.width > 0.0 { dims.width } else { 9.0 },
+            if dims.height > 0.0 { dims.height } else { 18.0 },
+        )
+    }
+
+    /// Cells the panes share: the window, less the top row for the tab bar
+    /// when there is more than one tab.
+    fn pane_area(&mut self) -> Rect {
+        let cell = self.cell_size();
+        let cols = (self.last_width as f32 / cell.x).floor().max(2.0) as usize;
+        let rows = (self.last_height as f32 / cell.y).floor().max(1.0) as usize;
+        let tab_bar = usize::from(self.tabs.len() > 1 && rows > 1);
+        Rect { col: 0, row: tab_bar, cols, rows: rows - tab_bar }
+    }
+}
+
+// --- Nushell engine initialization ---
+
+/// A fresh engine with `cwd` as its `PWD`.
+fn init_nushell_engine(service_logs: Option<RecentLogs>, theme_switch: ThemeSwitch, cwd: &Path) -> NuShellEngine {
+    let engine_state = create_default_context();
+    let mut engine_state = add_shell_command_context(engine_state);
+    commands::add_cyb_commands(&mut engine_state, service_logs, theme_switch);
+
+    gather_parent_env_vars(&mut engine_state, cwd);
+
+    if let Err(e) = load_standard_library(&mut engine_state) {
+        warn!("Failed to load nu standard library: {:?}", e);
+    }
+
+    let mut stack = Stack::new();
+
+    eval_source(
+        &mut engine_state,
+        &mut stack,
+        NU_ENV_SOURCE.as_bytes(),
+        "env.nu",
+        PipelineData::empty(),
+        false,
+    );
+
+    eval_source(
+        &mut engine_state,
+        &mut stack,
+        NU_CONFIG_SOURCE.as_bytes(),
+        "config.nu",
+        PipelineData::empty(),
+        false,
+    );
+
+    // Force ANSI coloring
+    {
+        let mut config: nu_protocol::Config = (*engine_state.get_config()).as_ref().clone();
+        config.use_ansi_coloring = nu_protocol::config::UseAnsiColoring::True;
+        engine_state.set_config(config);
+    }
+
+    if let Err(e) = nu_engine::env::convert_env_values(&mut engine_state, &mut stack) {
+        warn!("Failed to convert env values: {:?}", e);
+    }
+
+    info!("Nushell engine initialized");
+    NuShellEngine { engine_state, stack }
+}
+
+fn wire_ctrlc_signal(engine: &mut NuShellEngine, flag: Arc<AtomicBool>) {
+    engine.engine_state.set_signals(Signals::new(flag));
+}
+
+// --- Window resize handling ---
+
+/// Resize sugarloaf + term grids. Returns true if dimensions actually changed.
+fn handle_resize(state: &mut TerminalNonSendState, new_width: u32, new_height: u32) -> bool {
+    if (new_width, new_height) == (state.last_width, state.last_height) {
+        return false;
+    }
+    if new_width == 0 || new_height == 0 {
+        return false; // minimized
+    }
+
+    // 1. Update sugarloaf context dimensions
+    state.sugarloaf.resize(new_width, new_height);
+
+    // 2. Update stored dimensions
+    state.last_width = new_width;
+    state.last_height = new_height;
+
+    // 3. Fit every session's grid to its pane
+    layout_sessions(state);
+    info!("Terminal resized to {}x{}px", new_width, new_height);
+    true
+}
+
+/// Size each session's grid to its pane in the current window, for hidden
+/// tabs too. Called after a resize and whenever panes are added or closed.
+fn layout_sessions(state: &mut TerminalNonSendState) {
+    let cell = state.cell_size();
+    let area = state.pane_area();
+    for (id, rect) in state.tabs.all(area) {
+        if let Some(session) = state.sessions.get_mut(&id) {
+            resize_session(session, rect.cols, rect.rows, cell);
+        }
+    }
+    state.force_full_render = true;
+}
+
+fn resize_session(session: &mut Session, cols: usize, rows: usize, cell: Vec2) {
+    let (cols, rows) = (cols.max(2), rows.max(1));
+    if (cols, rows, cell) == (session.cols, session.rows, session.cell) {
+        return;
+    }
+    session.cell = cell;
+
+    // At the prompt, the prompt and input are cleared first and repainted at
+    // the new width (the right prompt moves to the new last column) rather
+    // than reflowed.
+    let at_prompt = !session.eval_in_progress && session.pty.is_none();
+    if at_prompt {
+        let erase = session.editor.erase();
+        feed_term(&session.term, &mut session.processor, &erase);
+    }
+    session.term.lock().resize(TermDimensions { cols, lines: rows });
+    session.cols = cols;
+    session.rows = rows;
+    debug!("Session resized to {}x{}", cols, rows);
+    if at_prompt {
+        redraw_input(session);
+    }
+
+    // Tell a running PTY program (TIOCSWINSZ → SIGWINCH)
+    if let Some(ref pty) = session.pty {
+        pty.resize(GridSize {
+            cols,
+            rows,
+            cell_width: cell.x,
+            cell_height: cell.y,
+        });
+    }
+}
+
+fn check_resize(world: &mut World) {
+    let win_dims: Option<(u32, u32, f32, f32)> = world
+        .query_filtered::<Entity, With<PrimaryWindow>>()
+        .single(world)
+        .ok()
+        .map(|e| {
+            let w = world.get::<Window>(e).unwrap();
+            (w.physical_width(), w.physical_height(), w.width(), w.height())
+        });
+    let Some((phys_w, phys_h, logical_w, logical_h)) = win_dims else { return };
+
+    // Phase 1: resize sugarloaf + term (only borrows NonSend, uses physical dims)
+    let (resized, image_handle) = {
+        let state = world.get_non_send_resource_mut::<TerminalNonSendState>().unwrap().into_inner();
+        let resized = handle_resize(state, phys_w, phys_h);
+        (resized, state.image_handle.clone())
+    };
+
+    if !resized { return; }
+
+    // Phase 2: resize Bevy Image to physical size; the render world
+    // reallocates its texture (borrows Assets<Image>)
+    {
+        let mut images = world.resource_mut::<Assets<Image>>();
+        if let Some(image) = images.get_mut(&image_handle) {
+            *image = target::image(phys_w, phys_h);
+        }
+    }
+
+    // Phase 3: update Sprite custom_size at logical size (borrows ECS components)
+    {
+        let mut sprites = world.query_filtered::<&mut Sprite, With<TerminalMarker>>();
+        for mut sprite in sprites.iter_mut(world) {
+            sprite.custom_size = Some(Vec2::new(logical_w, logical_h));
+        }
+    }
+}
+
+/// Apply hot-reloaded `[terminal]` settings and system appearance changes:
+/// the theme repaints, the font size re-lays out the grid.
+fn apply_terminal_config(world: &mut World) {
+    let config = world.resource::<Config>().terminal.clone();
+    let system_theme = world.resource::<SystemTheme>().0;
+
+    let Some(mut state) = world.get_non_send_resource_mut::<TerminalNonSendState>() else {
+        return;
+    };
+    let settings = (config.theme.clone(), config.adaptive_theme.clone());
+    if state.theme_settings != settings {
+        state.theme_settings = settings;
+        state.theme_override = None;
+    }
+    let theme_name = state
+        .theme_override
+        .clone()
+        .unwrap_or_else(|| configured_theme(&config, system_theme).to_string());
+    if state.theme.name != theme_name {
+        switch_theme(world, &theme_name);
+    }
+
+    let Some(state) = world.get_non_send_resource_mut::<TerminalNonSendState>() else { return };
+    let state = state.into_inner();
+    if state.font_config != config.font {
+        reload_fonts(state, &config.font);
+    }
+    // A new configured size also undoes any zoom
+    if state.config_font_size != config.font_size {
+        state.config_font_size = config.font_size;
+        set_font_size(state, config.font_size);
+    }
+}
+
+/// Load the `[terminal.font]` faces and features and reshape every session
+/// with them; the cell size follows the new font.
+fn reload_fonts(state: &mut TerminalNonSendState, config: &FontConfig) {
+    let (font_library, features) = fonts::load(config, state.font_size);
+    state.sugarloaf.set_font_features(&features);
+    state.sugarloaf.update_font(&font_library);
+    state.font_config = config.clone();
+    layout_sessions(state);
+    info!("Terminal fonts reloaded");
+}
+
+/// Resize the text of every session and the tab bar, then re-lay out the
+/// grids for the new cell size as a window resize does.
+fn set_font_size(state: &mut TerminalNonSendState, size: f32) {
+    if state.font_size == size {
+        return;
+    }
+    let rt_ids = state.sessions.values().map(|session| session.rich_text_id);
+    for rt_id in rt_ids.chain([state.tab_bar_id]).collect::<Vec<_>>() {
+        state.sugarloaf.set_rich_text_font_size(&rt_id, size);
+    }
+    state.font_size = size;
+    layout_sessions(state);
+    info!("Terminal font size set to {}", size);
+}
+
+/// Theme the config asks for: under `adaptive_theme`, the one for the
+/// system's appearance, dark when it is unknown.
+fn configured_theme(config: &TerminalConfig, system_theme: Option<WindowTheme>) -> &str {
+    match config.adaptive_theme {
+        Some(ref adaptive) if system_theme == Some(WindowTheme::Light) => &adaptive.light,
+        Some(ref adaptive) => &adaptive.dark,
+        None => &config.theme,
+    }
+}
+
+/// Switch to the theme a `cyb theme` command asked for.
+fn apply_theme_request(world: &mut World) {
+    let Some(mut state) = world.get_non_send_resource_mut::<TerminalNonSendState>() else { return };
+    let Some(name) = state.theme_switch.take_request() else { return };
+    state.theme_override = Some(name.clone());
+    switch_theme(world, &name);
+}
+
+/// Load the theme `name` and repaint every pane with it; a theme that fails
+/// to load is reported and the current one kept.
+fn switch_theme(world: &mut World, name: &str) {
+    let theme = match theme::load(name) {
+        Ok(theme) => theme,
+        Err(e) => {
+            warn!("Can't load terminal theme: {}", e);
+            return;
+        }
+    };
+    let background = theme.background;
+
+    let Some(state) = world.get_non_send_resource_mut::<TerminalNonSendState>() else { return };
+    let state = state.into_inner();
+    set_sugarloaf_background(&mut state.sugarloaf, background);
+    state.theme_switch.set_active(&theme.name);
+    state.theme = theme;
+    state.force_full_render = true;
+
+    let mut cameras = world.query_filtered::<&mut Camera, With<TerminalMarker>>();
+    for mut camera in cameras.iter_mut(world) {
+        camera.clear_color = ClearColorConfig::Custom(rgb_to_color(background));
+    }
+    info!("Terminal theme set to {}", name);
+}
+
+fn set_sugarloaf_background(sugarloaf: &mut Sugarloaf<'_>, rgb: Rgb) {
+    let [r, g, b, a] = rgb_to_f32(rgb);
+    sugarloaf.set_background_color(Some(sugarloaf::wgpu::Color {
+        r: r as f64,
+        g: g as f64,
+        b: b as f64,
+        a: a as f64,
+    }));
+}
+
+// --- Evaluate nushell command and stream its output ---
+
+fn evaluate_and_stream(engine: &mut NuShellEngine, input: &str, emit: &mut dyn FnMut(Vec<u8>)) -> Result<(), String> {
+    let input_bytes = input.as_bytes();
+
+    // Parse
+    let mut working_set = StateWorkingSet::new(&engine.engine_state);
+    let block = parse(&mut working_set, Some("input"), input_bytes, false);
+
+    if let Some(err) = working_set.parse_errors.first() {
+        return Err(format!("Parse error: {:?}", err));
+    }
+
+    let delta = working_set.render();
+    if let Err(e) = engine.engine_state.merge_delta(delta) {
+        return Err(format!("Merge error: {:?}", e));
+    }
+
+    // Redirect stdout/stderr to Pipe so external commands output through PipelineData
+    // (instead of writing to real stdout/stderr)
+    let pipeline_data = {
+        let mut guard = engine.stack.push_redirection(
+            Some(Redirection::Pipe(OutDest::Pipe)),
+            Some(Redirection::Pipe(OutDest::Pipe)),
+        );
+
+        let result = eval_block::<WithoutDebug>(
+            &engine.engine_state,
+            &mut guard,
+            &block,
+            PipelineData::empty(),
+        );
+
+        match result {
+            Ok(exec_data) => exec_data.body,
+            Err(e) => return Err(format!("{:?}", e)),
+        }
+        // guard drops here, restoring stack redirection state
+    };
+
+    // Stream output — structured data goes through `table` for proper formatting
+    let streamed = stream_pipeline_output(pipeline_data, engine, emit);
+
+    // Merge env changes (cd, export, etc.)
+    if let Err(e) = engine.engine_state.merge_env(&mut engine.stack) {
+        warn!("Failed to merge env: {:?}", e);
+    }
+
+    streamed
+}
+
+fn stream_pipeline_output(
+    data: PipelineData,
+    engine: &mut NuShellEngine,
+    emit: &mut dyn FnMut(Vec<u8>),
+) -> Result<(), String> {
+    let signals = engine.engine_state.signals().clone();
+    match data {
+        PipelineData::Empty => Ok(()),
+        PipelineData::Value(Value::Nothing { .. }, _) => Ok(()),
+        PipelineData::ByteStream(stream, _) => stream_bytes(stream, &signals, emit),
+        PipelineData::Value(Value::String { val, .. }, _) => {
+            emit(val.into_bytes());
+            Ok(())
+        }
+        // For structured data (records, lists, tables) — pipe through `table` command
+        other => pipe_through_table(other, engine, emit),
+    }
+}
+
+/// Forward a byte stream in chunks as they arrive. An external command's
+/// exit status is checked once its output ends, unless it was interrupted.
+fn stream_bytes(stream: ByteStream, signals: &Signals, emit: &mut dyn FnMut(Vec<u8>)) -> Result<(), String> {
+    let span = stream.span();
+    let type_ = stream.type_();
+    match stream.into_source() {
+        ByteStreamSource::Child(mut child) => {
+            if let Some(stdout) = child.stdout.take() {
+                forward_reader(stdout, signals, emit)?;
+            }
+            if signals.interrupted() {
+                return Ok(());
+            }
+            child.wait().map_err(|e| format!("{:?}", e))
+        }
+        source => match ByteStream::new(source, span, signals.clone(), type_).reader() {
+            Some(reader) => forward_reader(reader, signals, emit),
+            None => Ok(()),
+        },
+    }
+}
+
+/// Read `reader` to the end, or until Ctrl+C, emitting each read.
+///
+/// Reads block on a thread of their own, so Ctrl+C returns at once with the
+/// output so far even when the command is quiet (`sleep 60`, `tail -f`).
+/// The abandoned thread drops the pipe after the command's next write, which
+/// then fails with `SIGPIPE` and ends the command.
+fn forward_reader(
+    mut reader: impl Read + Send + 'static,
+    signals: &Signals,
+    emit: &mut dyn FnMut(Vec<u8>),
+) -> Result<(), String> {
+    let (tx, rx) = std::sync::mpsc::sync_channel(STREAM_READ_AHEAD);
+    let reader_signals = signals.clone();
+    std::thread::spawn(move || {
+        let mut buf = vec![0u8; STREAM_CHUNK_SIZE];
+        loop {
+            let chunk = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => Ok(buf[..n].to_vec()),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                    if reader_signals.interrupted() {
+                        break;
+                    }
+                    continue;
+                }
+                Err(e) => Err(e.to_string()),
+            };
+            let failed = chunk.is_err();
+            if tx.send(chunk).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    while !signals.interrupted() {
+        match rx.recv_timeout(INTERRUPT_POLL_INTERVAL) {
+            Ok(Ok(bytes)) => emit(bytes),
+            Ok(Err(e)) => return Err(e),
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Ok(())
+}
+
+fn pipe_through_table(
+    data: PipelineData,
+    engine: &mut NuShellEngine,
+    emit: &mut dyn FnMut(Vec<u8>),
+) -> Result<(), String> {
+    let signals = engine.engine_state.signals().clone();
+
+    // Find the `table` command declaration
+    if let Some(decl_id) = engine.engine_state.table_decl_id {
+        let command = engine.engine_state.get_decl(decl_id);
+        if command.block_id().is_none() {
+            let call = nu_protocol::ast::Call::new(nu_protocol::Span::new(0, 0));
+            return match command.run(
+                &engine.engine_state,
+                &mut engine.stack,
+                &(&call).into(),
+                data,
+            ) {
+                // A list stream comes back as a byte stream of table pages,
+                // rendered as items arrive
+                Ok(PipelineData::ByteStream(stream, _)) => stream_bytes(stream, &signals, emit),
+                Ok(table_output) => {
+                    let config = (*engine.engine_state.get_config()).as_ref().clone();
+                    let s = table_output
+                        .collect_string("\n", &config)
+                        .map_err(|e| format!("Table error: {}", e))?;
+                    emit(s.into_bytes());
+                    Ok(())
+                }
+                Err(e) => Err(format!("Table error: {}", e)),
+            };
+        }
+    }
+
+    // Fallback: use to_expanded_string, one item at a time
+    let config = engine.engine_state.get_config().clone();
+    for item in data {
+        if signals.interrupted() {
+            break;
+        }
+        let mut s = item.to_expanded_string("\n", &config);
+        s.push('\n');
+        emit(s.into_bytes());
+    }
+    Ok(())
+}
+
+// --- Evaluate prompt closures ---
+
+fn evaluate_prompt(engine: &NuShellEngine) -> Prompt {
+    Prompt {
+        command: prompt_part(engine, "PROMPT_COMMAND").unwrap_or_else(|| "> ".to_string()),
+        indicator: prompt_part(engine, "PROMPT_INDICATOR").unwrap_or_default(),
+        right: prompt_part(engine, "PROMPT_COMMAND_RIGHT").unwrap_or_default(),
+        multiline: prompt_part(engine, "PROMPT_MULTILINE_INDICATOR").unwrap_or_else(|| "::: ".to_string()),
+    }
+}
+
+/// The prompt a submitted line keeps in scrollback: each
+/// `TRANSIENT_PROMPT_*` variable that is set replaces its part of `prompt`.
+fn transient_prompt(engine: &NuShellEngine, prompt: &Prompt) -> Prompt {
+    let part = |var_name: &str, current: &String| prompt_part(engine, var_name).unwrap_or_else(|| current.clone());
+    Prompt {
+        command: part("TRANSIENT_PROMPT_COMMAND", &prompt.command),
+        indicator: part("TRANSIENT_PROMPT_INDICATOR", &prompt.indicator),
+        right: part("TRANSIENT_PROMPT_COMMAND_RIGHT", &prompt.right),
+        multiline: part("TRANSIENT_PROMPT_MULTILINE_INDICATOR", &prompt.multiline),
+    }
+}
+
+/// `$env.<var_name>` as prompt text: a closure's output or a plain string.
+fn prompt_part(engine: &NuShellEngine, var_name: &str) -> Option<String> {
+    let val = engine.stack.get_env_var(&engine.engine_state, var_name)
+        .or_else(|| engine.engine_state.get_env_var(var_name))?;
+
+    match val {
+        Value::Closure { val, .. } => {
+            match ClosureEvalOnce::new(&engine.engine_state, &engine.stack, *val.clone())
+                .run_with_input(PipelineData::empty())
+            {
+                Ok(data) => {
+                    let config = (*engine.engine_state.get_config()).clone();
+                    data.collect_string("", &config).ok()
+                }
+                Err(e) => {
+                    warn!("{} error: {:?}", var_name, e);
+                    None
+                }
+            }
+        }
+        Value::String { val, .. } => Some(val.clone()),
+        _ => None,
+    }
+}
+
+// --- Feed bytes into alacritty Term ---
+
+fn feed_term(term: &Arc<FairMutex<Term<BevyEventProxy>>>, processor: &mut Processor, bytes: &[u8]) {
+    let mut t = term.lock();
+    processor.advance(&mut *t, bytes);
+}
+
+// --- Setup ---
+
+fn setup_terminal(world: &mut World) {
+    // If terminal state already exists, restore rendering and return (persist state)
+    if world.get_non_send_resource::<TerminalNonSendState>().is_some() {
+        // Handle resize if window changed while terminal was inactive
+        check_resize(world);
+
+        // Get logical window size for sprite
+        let logical_size: Option<(f32, f32)> = world
+            .query_filtered::<Entity, With<PrimaryWindow>>()
+            .single(world)
+            .ok()
+            .map(|e| {
+                let w = world.get::<Window>(e).unwrap();
+                (w.width(), w.height())
+            });
+
+        // Read current state for spawning entities
+        let (image_handle, background) = {
+            let state = world.get_non_send_resource_mut::<TerminalNonSendState>().unwrap().into_inner();
+            state.force_full_render = true;
+            (state.image_handle.clone(), state.theme.background)
+        };
+
+        let (lw, lh) = logical_size.unwrap_or((1280.0, 800.0));
+
+        // Re-spawn camera + sprite for this world
+        world.spawn((
+            TerminalMarker,
+            Camera2d,
+            Camera {
+                clear_color: ClearColorConfig::Custom(rgb_to_color(background)),
+                ..default()
+            },
+            Tonemapping::None,
+        ));
+        world.spawn((
+            TerminalMarker,
+            Sprite {
+                image: image_handle,
+                custom_size: Some(Vec2::new(lw, lh)),
+                ..default()
+            },
+        ));
+        info!("Terminal resumed (state persisted)");
+        return;
+    }
+
+    let primary_entity = world
+        .query_filtered::<Entity, With<PrimaryWindow>>()
+        .single(world);
+    let Ok(entity) = primary_entity else { return };
+
+    let (win_w, win_h, logical_w, logical_h, scale_factor) = {
+        let window = world.get::<Window>(entity).unwrap();
+        (
+            window.physical_width(),
+            window.physical_height(),
+            window.width(),
+            window.height(),
+            window.scale_factor(),
+        )
+    };
+
+    // Get Bevy's GPU resources (cloned into main world by GpuBridgePlugin)
+    let device = world.resource::<bevy::render::renderer::RenderDevice>().wgpu_device().clone();
+    let queue: sugarloaf::wgpu::Queue = {
+        let rq = world.resource::<bevy::render::renderer::RenderQueue>();
+        let inner: &sugarloaf::wgpu::Queue = &**rq;
+        inner.clone()
+    };
+
+    // Create Sugarloaf with Bevy's device/queue (no separate GPU stack)
+    let surface_format = target::FORMAT;
+    let ctx = SugarloafContext::new_external(
+        device,
+        queue,
+        surface_format,
+        SugarloafWindowSize {
+            width: win_w as f32,
+            height: win_h as f32,
+        },
+        scale_factor,
+    );
+
+    let config = world.resource::<Config>().terminal.clone();
+    let font_size = config.font_size;
+    let theme_name = configured_theme(&config, world.resource::<SystemTheme>().0);
+    let theme = theme::load(theme_name).unwrap_or_else(|e| {
+        warn!("Can't load terminal theme: {}; using the default", e);
+        Theme::default_theme()
+    });
+    let (font_library, font_features) = fonts::load(&config.font, font_size);
+    let layout = RootStyle::new(scale_factor, font_size, 1.0);
+
+    let mut sugarloaf = match Sugarloaf::new_with_context(ctx, &font_library, layout) {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("Failed to create Sugarloaf: {:?}", e);
+            return;
+        }
+    };
+
+    sugarloaf.set_font_features(&font_features);
+
+    // Measures the cell size and holds the tab bar; each session adds its own
+    let tab_bar_id = sugarloaf.create_rich_text();
+    set_sugarloaf_background(&mut sugarloaf, theme.background);
+
+    let dims = sugarloaf.get_rich_text_dimensions(&tab_bar_id);
+    let cell_w = if dims.width > 0.0 { dims.width } else { 9.0 };
+    let cell_h = if dims.height > 0.0 { dims.height } else { 18.0 };
+
+    let cols = (win_w as f32 / cell_w).floor().max(2.0) as usize;
+    let rows = (win_h as f32 / cell_h).floor().max(1.0) as usize;
+
+    // Initialize the first session's nushell engine
+    let service_logs = world.get_resource::<ServiceLogs>().map(ServiceLogs::recent);
+    let theme_switch = ThemeSwitch::default();
+    theme_switch.set_active(&theme.name);
+    let session = new_session(
+        &mut sugarloaf,
+        font_size,
+        service_logs.clone(),
+        theme_switch.clone(),
+        &home_dir(),
+        cols,
+        rows,
+    );
+
+    // Create Bevy Image for sugarloaf to render into; until the first frame
+    // lands the sprite is transparent over the camera's clear color
+    let image_handle = world.resource_mut::<Assets<Image>>().add(target::image(win_w, win_h));
+    world.resource::<RenderTarget>().track(image_handle.id());
+
+    // Spawn Camera2d + fullscreen Sprite (tonemapping disabled for accurate terminal colors)
+    world.spawn((
+        TerminalMarker,
+        Camera2d,
+        Camera {
+            clear_color: ClearColorConfig::Custom(rgb_to_color(theme.background)),
+            ..default()
+        },
+        Tonemapping::None,
+    ));
+    world.spawn((
+        TerminalMarker,
+        Sprite {
+            image: image_handle.clone(),
+            custom_size: Some(Vec2::new(logical_w, logical_h)),
+            ..default()
+        },
+    ));
+
+    world.insert_non_send_resource(TerminalNonSendState {
+        sessions: HashMap::from([(0, session)]),
+        next_session_id: 1,
+        tabs: Tabs::new(0),
+        tab_bar_id,
+        drag_session: None,
+        sugarloaf,
+        image_handle,
+        key_cursor: Default::default(),
+        last_width: win_w,
+        last_height: win_h,
+        force_full_render: true,
+        font_size,
+        config_font_size: font_size,
+        font_config: config.font.clone(),
+        service_logs,
+        theme,
+        theme_switch,
+        theme_override: None,
+        theme_settings: (config.theme.clone(), config.adaptive_theme.clone()),
+        clipboard: open_clipboard(),
+    });
+
+    info!(
+        "Terminal created ({}x{}) cell={:.0}x{:.0} (embedded nushell + sugarloaf → Bevy Sprite)",
+        cols, rows, cell_w, cell_h
+    );
+}
+
+/// A shell in `cwd` on a `cols`x`rows` grid, with its own engine, rich text
+/// and history session, showing its first prompt.
+fn new_session(
+    sugarloaf: &mut Sugarloaf<'static>,
+    font_size: f32,
+    service_logs: Option<RecentLogs>,
+    theme_switch: ThemeSwitch,
+    cwd: &Path,
+    cols: usize,
+    rows: usize,
+) -> Session {
+    let rich_text_id = sugarloaf.create_rich_text();
+    sugarloaf.set_rich_text_font_size(&rich_text_id, font_size);
+    let dims = sugarloaf.get_rich_text_dimensions(&rich_text_id);
+    let cell = Vec2::new(
+        if dims.width > 0.0 { dims.width } else { 9.0 },
+        if dims.height > 0.0 { dims.height } else { 18.0 },
+    );
+
+    // Create alacritty terminal grid. Nushell output is fed in directly;
+    // external commands get their own PTY per run (see `pty`).
+    let config = TermConfig::default();
+    let term_dims = TermDimensions { cols, lines: rows };
+    let pty_replies = Arc::new(Mutex::new(Vec::new()));
+    let proxy = BevyEventProxy {
+        pty_replies: pty_replies.clone(),
+    };
+    let term = Arc::new(FairMutex::new(Term::new(config, &term_dims, proxy)));
+
+    let ctrlc_flag = Arc::new(AtomicBool::new(false));
+    let mut nu_engine = init_nushell_engine(service_logs, theme_switch, cwd);
+    wire_ctrlc_signal(&mut nu_engine, ctrlc_flag.clone());
+
+    let mut editor = LineEditor::new();
+    editor.set_history(open_history(&nu_engine));
+
+    let mut session = Session {
+        nu_engine: Some(nu_engine),
+        term,
+        processor: Processor::new(),
+        editor,
+        prompt: Prompt::default(),
+        snapshot: None,
+        cols,
+        rows,
+        cell,
+        rich_text_id,
+        eval_rx: None,
+        eval_in_progress: false,
+        ctrlc_flag,
+        pty: None,
+        pty_replies,
+        mouse: MouseSelection::default(),
+        search: None,
+        hints: Hints::new(),
+        hover_cell: None,
+        hover: None,
+        hint_mode: None,
+        title: String::new(),
+        dirty: true,
+    };
+    show_prompt(&mut session);
+    session
+}
+
+// --- Tabs and splits ---
+
+/// Create, close or switch tabs and panes. New sessions start in the
+/// focused session's directory.
+fn apply_tab_action(state: &mut TerminalNonSendState, action: TabAction) {
+    match action {
+        TabAction::NewTab => {
+            let id = spawn_session(state);
+            state.tabs.open_tab(id);
+        }
+        TabAction::Split(axis) => {
+            let area = state.pane_area();
+            if !state.tabs.can_split(axis, area) {
+                debug!("Pane too small to split");
+                return;
+            }
+            let id = spawn_session(state);
+            state.tabs.split(id, axis);
+        }
+        TabAction::ClosePane => close_session(state, state.tabs.focused()),
+        TabAction::SelectTab(offset) => state.tabs.select_tab(offset),
+        TabAction::FocusPane(offset) => state.tabs.focus_pane(offset),
+    }
+    state.drag_session = None;
+    layout_sessions(state);
+}
+
+/// Start a session in the focused one's directory, not yet in any tab.
+fn spawn_session(state: &mut TerminalNonSendState) -> SessionId {
+    let cwd = state
+        .focused()
+        .nu_engine
+        .as_ref()
+        .and_then(current_dir)
+        .map(PathBuf::from)
+        .unwrap_or_else(home_dir);
+    let area = state.pane_area();
+    let session = new_session(
+        &mut state.sugarloaf,
+        state.font_size,
+        state.service_logs.clone(),
+        state.theme_switch.clone(),
+        &cwd,
+        area.cols,
+        area.rows,
+    );
+    let id = state.next_session_id;
+    state.next_session_id += 1;
+    state.sessions.insert(id, session);
+    id
+}
+
+/// Stop the session `id` and take its pane away. Closing the last pane
+/// opens a fresh session in its place.
+fn close_session(state: &mut TerminalNonSendState, id: SessionId) {
+    if !state.tabs.close(id) {
+        let fresh = spawn_session(state);
+        state.tabs.open_tab(fresh);
+        state.tabs.close(id);
+    }
+    let Some(session) = state.sessions.remove(&id) else { return };
+    // Interrupt a running eval; its thread ends and the engine is dropped.
+    // A PTY program is killed when its session drops.
+    session.ctrlc_flag.store(true, Ordering::Relaxed);
+    state.sugarloaf.remove_rich_text(session.rich_text_id);
+    info!("Session {} closed", id);
+}
+
+// --- Single exclusive update system (handles input, poll, render) ---
+
+fn terminal_update(world: &mut World) {
+    // Deferred init: if OnEnter fired before window was ready, retry here
+    if world.get_non_send_resource::<TerminalNonSendState>().is_none() {
+        setup_terminal(world);
+        return; // skip this frame, render next frame
+    }
+
+    // Check for window resize
+    check_resize(world);
+
+    // Process keyboard input
+    process_keyboard_input(world);
+
+    // Process mouse wheel scroll
+    process_scroll_input(world);
+
+    // Select text with the mouse
+    process_mouse_input(world);
+
+    // Pump a running PTY program
+    poll_pty(world);
+
+    // Poll eval results
+    poll_eval_results(world);
+
+    // Switch themes picked with `cyb theme`
+    apply_theme_request(world);
+
+    // Render
+    render_terminal(world);
+}
+
+fn process_scroll_input(world: &mut World) {
+    let scroll_delta = {
+        let accumulated = world.resource::<AccumulatedMouseScroll>();
+        if accumulated.delta.y == 0.0 {
+            return;
+        }
+        let lines = match accumulated.unit {
+            MouseScrollUnit::Line => accumulated.delta.y as i32,
+            MouseScrollUnit::Pixel => (accumulated.delta.y / 20.0) as i32,
+        };
+        lines
+    };
+    let position = cursor_position(world);
+
+    let Some(state) = world.get_non_send_resource_mut::<TerminalNonSendState>() else { return };
+    let state = state.into_inner();
+    // The pane under the pointer scrolls, or the focused one
+    let id = position
+        .and_then(|position| pane_at(state, position))
+        .map_or_else(|| state.tabs.focused(), |(id, _)| id);
+    if let Some(session) = state.sessions.get(&id) {
+        session.term.lock().scroll_display(Scroll::Delta(scroll_delta));
+    }
+}
+
+/// Pointer position in physical pixels from the window's top-left.
+fn cursor_position(world: &mut World) -> Option<Vec2> {
+    world
+        .query_filtered::<&Window, With<PrimaryWindow>>()
+        .single(world)
+        .ok()
+        .and_then(Window::physical_cursor_position)
+}
+
+/// The visible pane under `position` and its cells.
+fn pane_at(state: &mut TerminalNonSendState, position: Vec2) -> Option<(SessionId, Rect)> {
+    let cell = state.cell_size();
+    let (col, row) = ((position.x / cell.x) as usize, (position.y / cell.y) as usize);
+    let area = state.pane_area();
+    let (panes, _) = state.tabs.visible(area);
+    panes.into_iter().find(|(_, rect)| rect.contains(col, row))
+}
+
+/// Left button: click-drag selects cells, a double click a word, a triple
+/// click a line; Shift+click extends the selection. A press focuses the pane
+/// it lands in, and a drag keeps selecting there. With the hint modifier
+/// held, the hint under the pointer is underlined and a click opens it.
+fn process_mouse_input(world: &mut World) {
+    let (pressed, held, released) = {
+        let buttons = world.resource::<ButtonInput<MouseButton>>();
+        (
+            buttons.just_pressed(MouseButton::Left),
+            buttons.pressed(MouseButton::Left),
+            buttons.just_released(MouseButton::Left),
+        )
+    };
+    let (shift, hint_held) = {
+        let keys = world.resource::<ButtonInput<KeyCode>>();
+        let cmd = keys.pressed(KeyCode::SuperLeft) || keys.pressed(KeyCode::SuperRight);
+        let mods = Modifiers {
+            shift: keys.pressed(KeyCode::ShiftLeft) || keys.pressed(KeyCode::ShiftRight),
+            alt: keys.pressed(KeyCode::AltLeft) || keys.pressed(KeyCode::AltRight),
+            ctrl: keys.pressed(KeyCode::ControlLeft) || keys.pressed(KeyCode::ControlRight),
+        };
+        (mods.shift, hints::mouse_modifier_held(cmd, mods))
+    };
+    let position = cursor_position(world);
+
+    let Some(state) = world.get_non_send_resource_mut::<TerminalNonSendState>() else { return };
+    let state = state.into_inner();
+    let cell = state.cell_size();
+    let area = state.pane_area();
+    let dragging = state.drag_session.filter(|_| !pressed && (held || released));
+    let target = position.and_then(|position| {
+        let (id, rect) = match dragging {
+            Some(id) => state.tabs.visible(area).0.into_iter().find(|(pane, _)| *pane == id)?,
+            None => pane_at(state, position)?,
+        };
+        let origin = Vec2::new(rect.col as f32 * cell.x, rect.row as f32 * cell.y);
+        Some((id, position - origin))
+    });
+    let cell_under = target.and_then(|(id, local)| {
+        let session = state.sessions.get(&id)?;
+        let display_offset = session.term.lock().grid().display_offset();
+        Some((id, selection::cell_at(local, cell, session.cols, session.rows, display_offset)))
+    });
+
+    let hovered = cell_under.filter(|_| hint_held);
+    for (id, session) in state.sessions.iter_mut() {
+        update_hover(session, hovered.filter(|(pane, _)| pane == id).map(|(_, (point, _))| point));
+    }
+    if pressed && let Some((id, _)) = hovered && let Some(hint) = state.sessions[&id].hover.clone() {
+        state.tabs.focus(id);
+        open_hint(world, hint.action);
+        return;
+    }
+    if !(pressed || held || released) {
+        return;
+    }
+
+    if pressed && let Some((id, _)) = cell_under {
+        if id != state.tabs.focused() {
+            state.tabs.focus(id);
+            state.force_full_render = true;
+        }
+        state.drag_session = Some(id);
+    }
+    let Some(id) = state.drag_session else { return };
+    if released {
+        state.drag_session = None;
+    }
+    let Some(session) = state.sessions.get_mut(&id) else { return };
+
+    let mut term = session.term.lock();
+    let changed = match cell_under {
+        Some((_, (point, side))) if pressed => {
+            session.mouse.press(&mut term, point, side, shift);
+            true
+        }
+        Some((_, (point, side))) => session.mouse.drag(&mut term, point, side),
+        None => false,
+    };
+    if released {
+        session.mouse.release(&mut term);
+    }
+    drop(term);
+    if changed || released {
+        session.dirty = true;
+    }
+}
+
+/// Find the hint under `point` when the pointer moves to another cell;
+/// `None` when the hint modifier isn't held or the pointer is elsewhere.
+fn update_hover(session: &mut Session, point: Option<Point>) {
+    if point == session.hover_cell {
+        return;
+    }
+    session.hover_cell = point;
+    let cwd = session.nu_engine.as_ref().and_then(current_dir).map(PathBuf::from);
+    let hover = point.and_then(|point| session.hints.at(&session.term.lock(), point, cwd.as_deref()));
+    let range = |hint: &Option<Hint>| hint.as_ref().map(|hint| hint.range.clone());
+    if range(&hover) != range(&session.hover) {
+        session.dirty = true;
+    }
+    session.hover = hover;
+}
+
+/// Open an activated hint: web links in the Legacy world, particles in the
+/// Portal world, other URLs with the system opener, and paths as `cd` or
+/// `open` at the focused session's prompt.
+fn open_hint(world: &mut World, action: HintAction) {
+    match action {
+        HintAction::Legacy(url) => {
+            info!("Opening {} in Legacy", url);
+            world.resource_mut::<OpenRequests>().legacy = Some(url);
+            world.resource_mut::<NextState<WorldState>>().set(WorldState::Legacy);
+        }
+        HintAction::Particle(cid) => {
+            info!("Opening particle {} in Portal", cid);
+            world.resource_mut::<OpenRequests>().particle = Some(cid);
+            world.resource_mut::<NextState<WorldState>>().set(WorldState::Portal);
+        }
+        HintAction::System(target) => hints::open_with_system(&target),
+        HintAction::Command(command) => {
+            let use_pty = world.resource::<Config>().terminal.pty;
+            let Some(state) = world.get_non_send_resource_mut::<TerminalNonSendState>() else { return };
+            let session = state.into_inner().focused();
+            if session.eval_in_progress || session.pty.is_some() {
+                return;
+            }
+            // Running it would submit the line being typed along with it
+            if !session.editor.buffer().is_empty() {
+                info!("Not running {:?} over the input being typed", command);
+                return;
+            }
+            clear_selection(session);
+            session.editor.paste(&command);
+            submit_input(session, use_pty);
+        }
+    }
+}
+
+fn open_clipboard() -> Option<arboard::Clipboard> {
+    arboard::Clipboard::new()
+        .map_err(|e| warn!("Clipboard unavailable: {}", e))
+        .ok()
+}
+
+/// Put the selected text on the clipboard.
+fn copy_selection(session: &mut Session, clipboard: &mut Option<arboard::Clipboard>) {
+    let Some(text) = session.term.lock().selection_to_string() else { return };
+    let Some(clipboard) = clipboard else { return };
+    if let Err(e) = clipboard.set_text(text) {
+        warn!("Copy failed: {}", e);
+    }
+}
+
+/// Paste the clipboard into the running PTY program, bracketed when it
+/// asked for that, or into the line editor at the prompt.
+fn paste_clipboard(session: &mut Session, clipboard: &mut Option<arboard::Clipboard>) {
+    let Some(clipboard) = clipboard else { return };
+    let text = match clipboard.get_text() {
+        Ok(text) if !text.is_empty() => text,
+        Ok(_) => return,
+        Err(e) => {
+            warn!("Paste failed: {}", e);
+            return;
+        }
+    };
+
+    if let Some(ref mut pty) = session.pty {
+        let bracketed = session.term.lock().mode().contains(TermMode::BRACKETED_PASTE);
+        pty.write(&selection::paste_bytes(&text, bracketed));
+    } else if !session.eval_in_progress {
+        session.editor.paste(&text);
+        redraw_input(session);
+    } else {
+        return;
+    }
+    clear_selection(session);
+}
+
+/// Drop the selection and jump back to the live screen, as typing does.
+fn clear_selection(session: &mut Session) {
+    let mut term = session.term.lock();
+    term.scroll_display(Scroll::Bottom);
+    if term.selection.take().is_some() {
+        session.dirty = true;
+    }
+}
+
+fn process_keyboard_input(world: &mut World) {
+    // Clone cursor from state, read new messages, update cursor back
+    let Some(state_ref) = world.get_non_send_resource::<TerminalNonSendState>() else { return };
+    let mut cursor = state_ref.key_cursor.clone();
+    drop(state_ref);
+
+    let events: Vec<KeyboardInput> = {
+        let messages = world.resource::<bevy::ecs::message::Messages<KeyboardInput>>();
+        cursor.read(messages).cloned().collect()
+    };
+
+    // Check modifier keys
+    let (cmd_held, mods) = {
+        let keys = world.resource::<ButtonInput<KeyCode>>();
+        (
+            keys.pressed(KeyCode::SuperLeft) || keys.pressed(KeyCode::SuperRight),
+            Modifiers {
+                shift: keys.pressed(KeyCode::ShiftLeft) || keys.pressed(KeyCode::ShiftRight),
+                alt: keys.pressed(KeyCode::AltLeft) || keys.pressed(KeyCode::AltRight),
+                ctrl: keys.pressed(KeyCode::ControlLeft) || keys.pressed(KeyCode::ControlRight),
+            },
+        )
+    };
+    let ctrl_held = mods.ctrl;
+    let use_pty = world.resource::<Config>().terminal.pty;
+
+    let Some(state) = world.get_non_send_resource_mut::<TerminalNonSendState>() else { return };
+    let state = state.into_inner();
+    state.key_cursor = cursor;
+
+    let mut opened = None;
+    for event in &events {
+        if !event.state.is_pressed() {
+            continue;
+        }
+
+        if let Some(action) = tabs::shortcut(event, cmd_held, mods) {
+            apply_tab_action(state, action);
+            continue;
+        }
+        if let Some(zoom) = fonts::zoom_shortcut(&event.logical_key, cmd_held, mods) {
+            set_font_size(state, zoom.apply(state.font_size, state.config_font_size));
+            continue;
+        }
+
+        // Everything else goes to the focused session
+        let focused = state.tabs.focused();
+        let Some(session) = state.sessions.get_mut(&focused) else { continue };
+
+        match selection::clipboard_shortcut(&event.logical_key, cmd_held, mods) {
+            Some(ClipboardAction::Copy) => {
+                copy_selection(session, &mut state.clipboard);
+                continue;
+            }
+            Some(ClipboardAction::Paste) => {
+                paste_clipboard(session, &mut state.clipboard);
+                continue;
+            }
+            None => {}
+        }
+
+        if search::is_search_shortcut(&event.logical_key, cmd_held, mods) {
+            session.search = match session.search {
+                Some(_) => None,
+                None => Some(ScrollbackSearch::open(&session.term.lock())),
+            };
+            session.dirty = true;
+            continue;
+        }
+        if let Some(ref mut search) = session.search {
+            match search.handle_key(event, mods, &mut session.term.lock()) {
+                SearchAction::Redraw => {
+                    session.dirty = true;
+                    continue;
+                }
+                SearchAction::Close => {
+                    session.search = None;
+                    session.dirty = true;
+                    continue;
+                }
+                SearchAction::None => {}
+            }
+        }
+
+        if hints::is_hint_mode_shortcut(&event.logical_key, mods) {
+            let cwd = session.nu_engine.as_ref().and_then(current_dir).map(PathBuf::from);
+            let mode = HintMode::new(session.hints.visible(&session.term.lock(), cwd.as_deref()));
+            session.hint_mode = (!mode.is_empty()).then_some(mode);
+            session.dirty = true;
+            continue;
+        }
+        if let Some(ref mut mode) = session.hint_mode {
+            match mode.handle_key(&event.logical_key) {
+                HintModeAction::Continue => {}
+                HintModeAction::Open(action) => {
+                    session.hint_mode = None;
+                    opened = Some(action);
+                }
+                HintModeAction::Exit => session.hint_mode = None,
+            }
+            session.dirty = true;
+            continue;
+        }
+
+        // Skip when Cmd is held — these are hotkey combos, not terminal input
+        if cmd_held {
+            continue;
+        }
+
+        // A PTY program owns the keyboard until it exits
+        if session.pty.is_some() {
+            let app_cursor = session.term.lock().mode().contains(TermMode::APP_CURSOR);
+            if let Some(bytes) = pty::encode_key(&event.logical_key, mods, app_cursor) {
+                clear_selection(session);
+                if let Some(ref mut pty) = session.pty {
+                    pty.write(&bytes);
+                }
+            }
+            continue;
+        }
+
+        if session.eval_in_progress {
+            // Only handle Ctrl+C during eval — interrupt the running command
+            if ctrl_held {
+                if let Key::Character(c) = &event.logical_key {
+                    if c.as_str() == "c" {
+                        session.ctrlc_flag.store(true, Ordering::Relaxed);
+                        info!("Ctrl+C: signaling interrupt to nushell eval");
+                    }
+                }
+            }
+            continue;
+        }
+
+        // Scrollback navigation stays with the view
+        match &event.logical_key {
+            Key::PageUp => {
+                session.term.lock().scroll_display(Scroll::PageUp);
+                continue;
+            }
+            Key::PageDown => {
+                session.term.lock().scroll_display(Scroll::PageDown);
+                continue;
+            }
+            Key::Home if mods.shift => {
+                session.term.lock().scroll_display(Scroll::Top);
+                continue;
+            }
+            Key::End if mods.shift => {
+                session.term.lock().scroll_display(Scroll::Bottom);
+                continue;
+            }
+            _ => {}
+        }
+
+        let action = session.editor.handle_key(event, mods);
+        if !matches!(action, EditAction::None) {
+            clear_selection(session);
+        }
+        match action {
+            EditAction::None => {}
+            EditAction::Redraw => redraw_input(session),
+            EditAction::Submit => {
+                if session.snapshot.as_ref().is_some_and(|snapshot| is_incomplete(snapshot, session.editor.buffer())) {
+                    session.editor.insert_newline();
+                    redraw_input(session);
+                    continue;
+                }
+                submit_input(session, use_pty);
+            }
+            EditAction::Cancel => {
+                let styled = styled_input(session);
+                let prompt = finished_prompt(session);
+                let bytes = session.editor.finish(&prompt, &styled, session.cols, b"^C");
+                feed_term(&session.term, &mut session.processor, &bytes);
+                session.editor.clear();
+                show_prompt(session);
+            }
+            EditAction::ClearScreen => {
+                feed_term(&session.term, &mut session.processor, b"\x1b[H\x1b[2J");
+                session.editor.reset_render();
+                redraw_input(session);
+            }
+            EditAction::Complete => {
+                let Some(ref snapshot) = session.snapshot else { continue };
+                let suggestions =
+                    completion::complete(snapshot, session.editor.buffer(), session.editor.insertion_point());
+                let options = &snapshot.engine_state.get_config().completions;
+                let action = session.editor.show_completions(suggestions, options.quick, options.partial);
+                if matches!(action, EditAction::Redraw) {
+                    redraw_input(session);
+                }
+            }
+        }
+    }
+
+    if let Some(action) = opened {
+        open_hint(world, action);
+    }
+}
+
+/// Leave the input in scrollback under the finished prompt and run it, on a
+/// PTY when `use_pty` and it is a lone external command.
+fn submit_input(session: &mut Session, use_pty: bool) {
+    let styled = styled_input(session);
+    let prompt = finished_prompt(session);
+    let bytes = session.editor.finish(&prompt, &styled, session.cols, b"");
+    feed_term(&session.term, &mut session.processor, &bytes);
+
+    let input = session.editor.take_line();
+    if input.trim().is_empty() {
+        show_prompt(session);
+        return;
+    }
+    let cwd = session.nu_engine.as_ref().and_then(current_dir);
+    session.editor.history_mut().start(&input, cwd);
+    session.title = input.lines().next().unwrap_or_default().trim().to_string();
+    session.dirty = true;
+    if !(use_pty && try_spawn_pty(session, &input)) {
+        dispatch_eval(session, input);
+    }
+}
+
+/// Evaluate the prompt and start a fresh input line under it. The command
+/// that just ran, if any, gets its exit status in history.
+fn show_prompt(session: &mut Session) {
+    if let Some(ref mut engine) = session.nu_engine {
+        session.editor.history_mut().finish(last_exit_code(engine));
+        session.prompt = evaluate_prompt(engine);
+        session.title = current_dir(engine).map(|dir| dir_title(&dir)).unwrap_or_default();
+        session.editor.configure(engine.engine_state.get_config());
+        session.snapshot = Some(EngineSnapshot {
+            engine_state: Arc::new(engine.engine_state.clone()),
+            stack: Arc::new(engine.stack.clone()),
+        });
+    }
+    session.dirty = true;
+    session.editor.reset_render();
+    redraw_input(session);
+}
+
+/// History as `$env.config.history` asks, in `~/.cyb`; in memory only if
+/// the file can't be opened.
+fn open_history(engine: &NuShellEngine) -> CommandHistory {
+    let config = &engine.engine_state.get_config().history;
+    let opened = expand_path("~/.cyb")
+        .map_err(|e| e.to_string())
+        .and_then(|dir| CommandHistory::open(config, &dir).map_err(|e| e.to_string()));
+    match opened {
+        Ok(history) => history,
+        Err(e) => {
+            warn!("History: {}, keeping it in memory", e);
+            CommandHistory::in_memory()
+        }
+    }
+}
+
+fn current_dir(engine: &NuShellEngine) -> Option<String> {
+    let pwd = engine.stack.get_env_var(&engine.engine_state, "PWD")?;
+    pwd.as_str().ok().map(str::to_string)
+}
+
+/// Last component of `dir`, or `~` for the home directory.
+fn dir_title(dir: &str) -> String {
+    if Path::new(dir) == home_dir() {
+        return "~".to_string();
+    }
+    match Path::new(dir).file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => dir.to_string(),
+    }
+}
+
+fn last_exit_code(engine: &NuShellEngine) -> i64 {
+    engine
+        .stack
+        .get_env_var(&engine.engine_state, "LAST_EXIT_CODE")
+        .and_then(|code| code.as_int().ok())
+        .unwrap_or(0)
+}
+
+/// Repaint the prompt and input after an edit.
+fn redraw_input(session: &mut Session) {
+    let styled = styled_input(session);
+    let bytes = session.editor.render(&session.prompt, &styled, session.cols);
+    feed_term(&session.term, &mut session.processor, &bytes);
+}
+
+/// The prompt to leave above a finished line, transient if configured.
+fn finished_prompt(session: &Session) -> Prompt {
+    match session.nu_engine {
+        Some(ref engine) => transient_prompt(engine, &session.prompt),
+        None => session.prompt.clone(),
+    }
+}
+
+/// Whether the parser wants more input (an unclosed block, record, string
+/// or pipeline), so Enter should continue on a new line.
+fn is_incomplete(snapshot: &EngineSnapshot, input: &str) -> bool {
+    let validator = NuValidator {
+        engine_state: snapshot.engine_state.clone(),
+    };
+    matches!(validator.validate(input), ValidationResult::Incomplete)
+}
+
+/// The input with syntax highlighting, or as typed before the engine is up.
+fn styled_input(session: &Session) -> String {
+    let buffer = session.editor.buffer();
+    match session.snapshot {
+        Some(ref snapshot) => highlight::highlight(snapshot, buffer, session.editor.insertion_point()),
+        None => buffer.to_string(),
+    }
+}
+
+/// Run `input` on a PTY if it is a lone external command. Returns false to
+/// fall back to the captured eval path.
+fn try_spawn_pty(session: &mut Session, input: &str) -> bool {
+    let size = GridSize {
+        cols: session.cols,
+        rows: session.rows,
+        cell_width: session.cell.x,
+        cell_height: session.cell.y,
+    };
+    let Some(engine) = session.nu_engine.as_mut() else {
+        return false;
+    };
+
+    let mut working_set = StateWorkingSet::new(&engine.engine_state);
+    let block = parse(&mut working_set, Some("input"), input.as_bytes(), false);
+    if !working_set.parse_errors.is_empty() || pty::lone_external(&block).is_none() {
+        return false;
+    }
+    let delta = working_set.render();
+
+    let spawned = engine
+        .engine_state
+        .merge_delta(delta)
+        .map_err(|e| format!("Merge error: {:?}", e))
+        .and_then(|_| {
+            let (head, args) = pty::lone_external(&block).expect("checked above");
+            pty::eval_argv(engine, head, args).map_err(|e| format!("{:?}", e))
+        })
+        .and_then(|argv| {
+            let env = nu_engine::env::env_to_strings(&engine.engine_state, &engine.stack)
+                .map_err(|e| format!("{:?}", e))?;
+            info!("Running {:?} on a PTY", argv[0]);
+            PtySession::spawn(&argv, &env, size)
+        });
+
+    match spawned {
+        Ok(pty) => session.pty = Some(pty),
+        Err(err) => {
+            engine.stack.set_last_exit_code(1, Span::unknown());
+            let err_msg = format!("\x1b[31mError: {}\x1b[0m\r\n", err);
+            feed_term(&session.term, &mut session.processor, err_msg.as_bytes());
+            show_prompt(session);
+        }
+    }
+    true
+}
+
+/// Pump every session's PTY program, focused or not.
+fn poll_pty(world: &mut World) {
+    let Some(state) = world.get_non_send_resource_mut::<TerminalNonSendState>() else { return };
+    for session in state.into_inner().sessions.values_mut() {
+        poll_session_pty(session);
+    }
+}
+
+/// Forward terminal replies to the PTY program, feed its output into the
+/// grid, and return to the prompt once it exits.
+fn poll_session_pty(session: &mut Session) {
+    let replies = std::mem::take(&mut *session.pty_replies.lock().unwrap());
+    let Some(ref mut pty) = session.pty else { return };
+    if !replies.is_empty() {
+        pty.write(&replies);
+    }
+
+    let output = pty.read_output();
+    if !output.is_empty() {
+        feed_term(&session.term, &mut session.processor, &output);
+    }
+
+    // Dropped only once its output is drained, so the tail of a short
+    // command isn't lost with the reader
+    let Some(exit_code) = pty.finished() else { return };
+    let rest = pty.read_output();
+    feed_term(&session.term, &mut session.processor, &rest);
+    session.pty = None;
+
+    start_fresh_line(session);
+    if let Some(ref mut engine) = session.nu_engine {
+        engine.stack.set_last_exit_code(exit_code, Span::unknown());
+    }
+    show_prompt(session);
+}
+
+/// Move to the start of the next row unless the cursor is already in the
+/// first column, so the prompt doesn't share a row with command output.
+fn start_fresh_line(session: &mut Session) {
+    let column = session.term.lock().grid().cursor.point.column.0;
+    if column > 0 {
+        feed_term(&session.term, &mut session.processor, b"\r\n");
+    }
+}
+
+fn dispatch_eval(session: &mut Session, input: String) {
+    let Some(engine) = session.nu_engine.take() else {
+        warn!("No nushell engine available for eval");
+        return;
+    };
+
+    session.eval_in_progress = true;
+
+    let (tx, rx) = std::sync::mpsc::sync_channel(EVAL_CHANNEL_BOUND);
+    session.eval_rx = Some(rx);
+
+    std::thread::spawn(move || {
+        let mut engine = engine;
+        let mut emit = |bytes: Vec<u8>| {
+            let _ = tx.send(EvalMessage::Output(bytes));
+        };
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            evaluate_and_stream(&mut engine, &input, &mut emit)
+        })) {
+            Ok(result) => {
+                let _ = tx.send(EvalMessage::Done { error: result.err(), engine });
+            }
+            Err(panic_info) => {
+                let panic_msg = if let Some(s) = panic_info.downcast_ref::<&str>() {
+                    s.to_string()
+                } else if let Some(s) = panic_info.downcast_ref::<String>() {
+                    s.clone()
+                } else {
+                    "Unknown panic".to_string()
+                };
+                let error = Some(format!("Internal panic: {}", panic_msg));
+                let _ = tx.send(EvalMessage::Done { error, engine });
+            }
+        }
+    });
+}
+
+/// Feed every session's eval output into its grid, focused or not.
+fn poll_eval_results(world: &mut World) {
+    let Some(state) = world.get_non_send_resource_mut::<TerminalNonSendState>() else { return };
+    let state = state.into_inner();
+    for session in state.sessions.values_mut() {
+        poll_session_eval(session, &state.service_logs, &state.theme_switch);
+    }
+}
+
+fn poll_session_eval(session: &mut Session, service_logs: &Option<RecentLogs>, theme_switch: &ThemeSwitch) {
+    if !session.eval_in_progress {
+        return;
+    }
+
+    let mut done = None;
+    for _ in 0..EVAL_CHUNKS_PER_FRAME {
+        let Some(ref rx) = session.eval_rx else { return };
+        match rx.try_recv() {
+            Ok(EvalMessage::Output(bytes)) => {
+                feed_term(&session.term, &mut session.processor, &convert_lf_to_crlf(&bytes));
+            }
+            Ok(EvalMessage::Done { error, engine }) => {
+                done = Some((error, engine));
+                break;
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => return,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                warn!("Eval thread lost — rebuilding nushell engine");
+                session.eval_in_progress = false;
+                session.eval_rx = None;
+                session.ctrlc_flag.store(false, Ordering::Relaxed);
+
+                feed_term(
+                    &session.term,
+                    &mut session.processor,
+                    b"\x1b[31mError: command execution failed, engine restarted\x1b[0m\r\n",
+                );
+
+                let mut engine = init_nushell_engine(service_logs.clone(), theme_switch.clone(), &home_dir());
+                wire_ctrlc_signal(&mut engine, session.ctrlc_flag.clone());
+                session.nu_engine = Some(engine);
+                show_prompt(session);
+                return;
+            }
+        }
+    }
+    let Some((error, mut engine)) = done else { return };
+
+    session.eval_rx = None;
+    session.eval_in_progress = false;
+    let interrupted = session.ctrlc_flag.swap(false, Ordering::Relaxed);
+    engine.engine_state.reset_signals();
+
+    // Partial output stays; the prompt starts on a fresh line below it
+    start_fresh_line(session);
+    if interrupted {
+        feed_term(&session.term, &mut session.processor, b"^C\r\n");
+    }
+
+    // Show error if any; an interrupted command's error just repeats the ^C
+    if let Some(ref err) = error {
+        if !interrupted {
+            let err_msg = format!("\x1b[31mError: {}\x1b[0m\r\n", err);
+            feed_term(&session.term, &mut session.processor, err_msg.as_bytes());
+        }
+        engine.stack.set_last_exit_code(1, Span::unknown());
+    }
+
+    session.nu_engine = Some(engine);
+    show_prompt(session);
+}
+
+
+fn render_terminal(world: &mut World) {
+    let target = world.resource::<RenderTarget>().clone();
+    let Some(state) = world.get_non_send_resource_mut::<TerminalNonSendState>() else { return };
+    let state = state.into_inner();
+
+    // Check if the active tab's content has changed (dirty tracking).
+    // Hidden tabs only count for their titles; showing one repaints all.
+    let area = state.pane_area();
+    let (panes, _) = state.tabs.visible(area);
+    let mut needs_rebuild = state.force_full_render;
+    for (id, session) in state.sessions.iter_mut() {
+        let mut term = session.term.lock();
+        let damaged = match term.damage() {
+            TermDamage::Full => true,
+            TermDamage::Partial(mut iter) => iter.next().is_some(),
+        };
+        term.reset_damage();
+        drop(term);
+        let visible = panes.iter().any(|(pane, _)| pane == id);
+        needs_rebuild |= session.dirty || (visible && damaged);
+        session.dirty = false;
+    }
+
+    if !needs_rebuild {
+        return; // Bevy handles presentation — nothing to do
+    }
+
+    // The render world allocates the texture a frame after the image is
+    // created or resized; draw once it's there
+    let Some(texture) = target.texture(state.last_width, state.last_height) else {
+        state.force_full_render = true;
+        return;
+    };
+    state.force_full_render = false;
+
+    render_terminal_content(state);
+
+    // Render sugarloaf straight into the texture the sprite samples
+    let view = texture.create_view(&Default::default());
+    state.sugarloaf.render_to_view(&view);
+}
+
+/// Build sugarloaf content for the active tab: each pane's grid in its
+/// place, the dividers between panes, and the tab bar (called within
+/// NonSend borrow scope).
+fn render_terminal_content(state: &mut TerminalNonSendState) {
+    let cell = state.cell_size();
+    // Objects are placed in logical pixels; cells are measured in physical ones
+    let scale = state.sugarloaf.scale_factor();
+    let at = |col: usize, row: usize| [col as f32 * cell.x / scale, row as f32 * cell.y / scale];
+    let area = state.pane_area();
+    let (panes, dividers) = state.tabs.visible(area);
+    let focused = state.tabs.focused();
+
+    let mut objects = Vec::new();
+    if area.row > 0 {
+        let titles: Vec<&str> = state
+            .tabs
+            .tab_sessions()
+            .map(|id| state.sessions.get(&id).map_or("", |session| session.title.as_str()))
+            .collect();
+        add_tab_bar(&mut state.sugarloaf, state.tab_bar_id, &state.theme, &titles, state.tabs.active(), area.cols);
+        objects.push(Object::RichText(RichText {
+            id: state.tab_bar_id,
+            position: [0.0, 0.0],
+            lines: None,
+        }));
+    }
+
+    for (id, rect) in panes {
+        let Some(session) = state.sessions.get_mut(&id) else { continue };
+        let cursor = build_session_content(&mut state.sugarloaf, session, &state.theme, state.font_config.drawable_chars);
+        objects.push(Object::RichText(RichText {
+            id: session.rich_text_id,
+            position: at(rect.col, rect.row),
+            lines: None,
+        }));
+        if let Some((col, row, shape)) = cursor {
+            // Only the focused pane shows its real cursor
+            let shape = if id == focused { shape } else { CursorShape::HollowBlock };
+            let position = at(rect.col + col, rect.row + row);
+            objects.push(Object::Quad(cursor_quad(shape, position, cell / scale, state.theme.cursor)));
+        }
+    }
+
+    for divider in dividers {
+        let [x, y] = at(divider.col, divider.row);
+        let size = if divider.cols == 1 {
+            [1.0, divider.rows as f32 * cell.y / scale]
+        } else {
+            [divider.cols as f32 * cell.x / scale, 1.0]
+        };
+        let offset = if divider.cols == 1 { [cell.x / scale / 2.0, 0.0] } else { [0.0, cell.y / scale / 2.0] };
+        objects.push(Object::Quad(sugarloaf::Quad {
+            position: [x + offset[0], y + offset[1]],
+            size,
+            color: rgb_to_f32(state.theme.split),
+            border_color: [0.0; 4],
+            border_radius: [0.0; 4],
+            border_width: 0.0,
+            shadow_color: [0.0; 4],
+            shadow_offset: [0.0; 2],
+            shadow_blur_radius: 0.0,
+        }));
+    }
+
+    state.sugarloaf.set_objects(objects);
+}
+
+/// Fill a session's rich text from its grid. Returns the cursor's cell and
+/// shape when it is on screen.
+fn build_session_content(
+    sugarloaf: &mut Sugarloaf<'_>,
+    session: &mut Session,
+    theme: &Theme,
+    drawable_chars: bool,
+) -> Option<(usize, usize, CursorShape)> {
+    let rt_id = session.rich_text_id;
+
+    let mut cursor_col: usize = 0;
+    let mut cursor_row: i32 = -1;
+    let mut cursor_shape = CursorShape::Block;
+
+    {
+        let term = session.term.lock();
+        let matches = match session.search {
+            Some(ref mut search) => search.visible_matches(&term),
+            None => Vec::new(),
+        };
+        let focused = session.search.as_ref().and_then(|search| search.focused().cloned());
+        let hovered = session.hover.as_ref().map(|hint| hint.range.clone());
+        let hint_labels = match session.hint_mode {
+            Some(ref mode) => mode.label_cells(&term),
+            None => Vec::new(),
+        };
+        // The search bar takes the bottom row
+        let grid_rows = if session.search.is_some() { session.rows.saturating_sub(1) } else { session.rows };
+        let content = term.renderable_content();
+
+        sugarloaf.content().sel(rt_id).clear();
+
+        let display_offset = content.display_offset as i32;
+        let selection = content.selection;
+        let mut current_line: i32 = -1;
+        for indexed in content.display_iter {
+            let col = indexed.point.column.0;
+            let term_row = indexed.point.line.0;
+            let viewport_row = term_row + display_offset;
+
+            if viewport_row < 0 || col >= session.cols || viewport_row as usize >= grid_rows {
+                continue;
+            }
+
+            if term_row != current_line {
+                sugarloaf.content().sel(rt_id).new_line();
+                current_line = term_row;
+            }
+
+            let cell = &*indexed;
+            let mut fg_color = cell.fg;
+            let mut bg_color = cell.bg;
+
+            // Without theme selection colors, selected cells show inverted,
+            // so inverse text reads normally
+            let selected = selection.is_some_and(|range| range.contains(indexed.point));
+            let invert_selection = selected && theme.selection_background.is_none();
+            if cell.flags.contains(Flags::INVERSE) != invert_selection {
+                std::mem::swap(&mut fg_color, &mut bg_color);
+            }
+
+            // Bold text in the normal ANSI colors shows in the bright ones
+            let fg_color = match fg_color {
+                Color::Named(named) if cell.flags.contains(Flags::BOLD) && (named as usize) < 8 => {
+                    Color::Indexed(named as u8 + 8)
+                }
+                _ => fg_color,
+            };
+            let fg = resolve_color(fg_color, content.colors, theme);
+            let bg = resolve_color(bg_color, content.colors, theme);
+
+            let label = hint_labels.iter().find(|(point, _)| *point == indexed.point).map(|&(_, c)| c);
+            let (fg, bg) = if label.is_some() {
+                (theme.hint_foreground, theme.hint_background)
+            } else if focused.as_ref().is_some_and(|found| found.contains(&indexed.point)) {
+                (theme.search_focused_match_foreground, theme.search_focused_match_background)
+            } else if matches.iter().any(|found| found.contains(&indexed.point)) {
+                (theme.search_match_foreground, theme.search_match_background)
+            } else if selected && !invert_selection {
+                (theme.selection_foreground.unwrap_or(fg), theme.selection_background.unwrap_or(bg))
+            } else {
+                (fg, bg)
+            };
+
+            let mut style = FragmentStyle {
+                color: rgb_to_f32(fg),
+                background_color: Some(rgb_to_f32(bg)),
+                ..Default::default()
+            };
+
+            if cell.flags.contains(Flags::BOLD) {
+                style.font_attrs = sugarloaf::font_introspector::Attributes::new(
+                    sugarloaf::Stretch::NORMAL,
+                    sugarloaf::Weight(700),
+                    sugarloaf::Style::Normal,
+                );
+            }
+
+            if cell.flags.contains(Flags::ITALIC) {
+                style.font_attrs = sugarloaf::font_introspector::Attributes::new(
+                    sugarloaf::Stretch::NORMAL,
+                    if cell.flags.contains(Flags::BOLD) {
+                        sugarloaf::Weight(700)
+                    } else {
+                        sugarloaf::Weight(400)
+                    },
+                    sugarloaf::Style::Italic,
+                );
+            }
+
+            let hover = hovered.as_ref().is_some_and(|range| range.contains(&indexed.point));
+            if cell.flags.contains(Flags::UNDERLINE) || hover {
+                style.decoration = Some(FragmentStyleDecoration::Underline(UnderlineInfo {
+                    is_doubled: false,
+                    shape: UnderlineShape::Regular,
+                }));
+            }
+
+            if cell.flags.contains(Flags::STRIKEOUT) {
+                style.decoration = Some(FragmentStyleDecoration::Strikethrough);
+            }
+
+            let ch = label.unwrap_or(cell.c);
+            if drawable_chars {
+                style.drawable_char = sugarloaf::drawable_character(ch);
+            }
+
+            let text_owned;
+            let text_str = if ch == '\0' || ch == ' ' {
+                " "
+            } else {
+                text_owned = ch.to_string();
+                text_owned.as_str()
+            };
+
+            sugarloaf.content().sel(rt_id).add_text(text_str, style);
+        }
+
+        if let Some(ref search) = session.search {
+            cursor_col = add_search_bar(sugarloaf, rt_id, theme, search, session.cols);
+            cursor_row = grid_rows as i32;
+            cursor_shape = CursorShape::Beam;
+        } else {
+            cursor_col = content.cursor.point.column.0;
+            cursor_row = content.cursor.point.line.0 + display_offset;
+            cursor_shape = content.cursor.shape;
+        }
+
+        sugarloaf.content().sel(rt_id).build();
+    }
+
+    let on_screen = cursor_row >= 0 && (cursor_row as usize) < session.rows && cursor_col < session.cols;
+    on_screen.then_some((cursor_col, cursor_row as usize, cursor_shape))
+}
+
+/// Cursor of `shape` over the cell at `position`, both in logical pixels,
+/// translucent so the character under it shows.
+fn cursor_quad(shape: CursorShape, position: [f32; 2], cell: Vec2, color: Rgb) -> sugarloaf::Quad {
+    let [r, g, b, _] = rgb_to_f32(color);
+    let cursor_color = [r, g, b, 0.7];
+    let [cx, cy] = position;
+    let (cell_w, cell_h) = (cell.x, cell.y);
+
+    match shape {
+        CursorShape::Block => sugarloaf::Quad {
+            position: [cx, cy],
+            size: [cell_w, cell_h],
+            color: cursor_color,
+            border_color: [0.0; 4],
+            border_radius: [0.0; 4],
+            border_width: 0.0,
+            shadow_color: [0.0; 4],
+            shadow_offset: [0.0; 2],
+            shadow_blur_radius: 0.0,
+        },
+        CursorShape::Beam => sugarloaf::Quad {
+            position: [cx, cy],
+            size: [2.0, cell_h],
+            color: cursor_color,
+            border_color: [0.0; 4],
+            border_radius: [0.0; 4],
+            border_width: 0.0,
+            shadow_color: [0.0; 4],
+            shadow_offset: [0.0; 2],
+            shadow_blur_radius: 0.0,
+        },
+        CursorShape::Underline => sugarloaf::Quad {
+            position: [cx, cy + cell_h - 2.0],
+            size: [cell_w, 2.0],
+            color: cursor_color,
+            border_color: [0.0; 4],
+            border_radius: [0.0; 4],
+            border_width: 0.0,
+            shadow_color: [0.0; 4],
+            shadow_offset: [0.0; 2],
+            shadow_blur_radius: 0.0,
+        },
+        _ => sugarloaf::Quad {
+            position: [cx, cy],
+            size: [cell_w, cell_h],
+            color: [0.0; 4],
+            border_color: cursor_color,
+            border_radius: [0.0; 4],
+            border_width: 1.0,
+            shadow_color: [0.0; 4],
+            shadow_offset: [0.0; 2],
+            shadow_blur_radius: 0.0,
+        },
+    }
+}
+
+/// Bottom row while searching: the query, then the case and regex toggles
+/// at the right edge, lit when on. Returns the column after the query.
+fn add_search_bar(
+    sugarloaf: &mut Sugarloaf<'_>,
+    rt_id: usize,
+    theme: &Theme,
+    search: &ScrollbackSearch,
+    cols: usize,
+) -> usize {
+    const LABEL: &str = "Search: ";
+    let toggles = [("Aa", search.case_sensitive()), (".*", search.regex_mode())];
+    let toggles_width = toggles.iter().map(|(name, _)| name.len() + 1).sum::<usize>();
+
+    let style = |rgb: Rgb| FragmentStyle {
+        color: rgb_to_f32(rgb),
+        background_color: Some(rgb_to_f32(theme.bar)),
+        ..Default::default()
+    };
+    let text = theme.foreground;
+    let dim = theme.named(NamedColor::BrightBlack);
+    let query_color = if search.failing() { theme.named(NamedColor::BrightRed) } else { text };
+
+    // Long queries show their end, where the typing is
+    let room = cols.saturating_sub(LABEL.len() + toggles_width + 1);
+    let chars: Vec<char> = search.query().chars().collect();
+    let shown: String = chars[chars.len().saturating_sub(room)..].iter().collect();
+    let end = LABEL.len() + shown.chars().count();
+
+    let content = sugarloaf.content().sel(rt_id);
+    content.new_line();
+    content.add_text(LABEL, style(dim));
+    content.add_text(&shown, style(query_color));
+    content.add_text(&" ".repeat(cols.saturating_sub(end + toggles_width)), style(text));
+    for (name, on) in toggles {
+        content.add_text(" ", style(text));
+        content.add_text(name, style(if on { text } else { dim }));
+    }
+    end.min(cols.saturating_sub(1))
+}
+
+/// Top row while there is more than one tab: each tab's number and title,
+/// the active one lit, sharing the width evenly.
+fn add_tab_bar(sugarloaf: &mut Sugarloaf<'_>, rt_id: usize, theme: &Theme, titles: &[&str], active: usize, cols: usize) {
+    let style = |fg: Rgb, bg: Rgb| FragmentStyle {
+        color: rgb_to_f32(fg),
+        background_color: Some(rgb_to_f32(bg)),
+        ..Default::default()
+    };
+    let width = (cols / titles.len().max(1)).max(1);
+
+    let content = sugarloaf.content().sel(rt_id);
+    content.clear();
+    content.new_line();
+    let mut used = 0;
+    for (i, title) in titles.iter().enumerate() {
+        let label = format!(" {} {} ", i + 1, title);
+        let mut shown: String = label.chars().take(width).collect();
+        let padding = width.saturating_sub(shown.chars().count());
+        shown.push_str(&" ".repeat(padding));
+        let (fg, bg) = if i == active {
+            (theme.tabs_active_foreground, theme.tabs_active)
+        } else {
+            (theme.tabs_foreground, theme.tabs)
+        };
+        content.add_text(&shown, style(fg, bg));
+        used += width;
+    }
+    content.add_text(&" ".repeat(cols.saturating_sub(used)), style(theme.tabs_foreground, theme.tabs));
+    content.build();
+}
+
+fn convert_lf_to_crlf(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() + input.len() / 10);
+    for i in 0..input.len() {
+        if input[i] == b'\n' && (i == 0 || input[i - 1] != b'\r') {
+            output.push(b'\r');
+        }
+        output.push(input[i]);
+    }
+    output
+}
+
+// --- Destroy ---
+
+fn destroy_terminal(world: &mut World) {
+    // Despawn camera + sprite (Bevy naturally shows other worlds)
+    let entities: Vec<Entity> = world
+        .query_filtered::<Entity, With<TerminalMarker>>()
+        .iter(world)
+        .collect();
+    for entity in entities {
+        world.despawn(entity);
+    }
+
+    // Keep TerminalNonSendState alive — state persists between world switches
+    info!("Terminal paused (state persisted)");
+}
@@ -0,0 +1,421 @@
+//! External commands on a pseudo-terminal, so full-screen and raw-mode
+//! programs (vim, htop, less, ssh) run inside the grid instead of having
+//! their output captured through a pipe.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use bevy::input::keyboard::Key;
+use nu_engine::eval_expression;
+use nu_protocol::ast::{Block, Expr, Expression, ExternalArgument};
+use nu_protocol::debugger::WithoutDebug;
+use nu_protocol::ShellError;
+use portable_pty::{Child, CommandBuilder, MasterPty, PtySize, native_pty_system};
+
+use super::NuShellEngine;
+
+/// How long output is still collected after the command exits when the
+/// PTY doesn't reach EOF, e.g. because a background job it started keeps
+/// the slave open.
+const EXIT_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A running external command attached to its own PTY.
+pub(super) struct PtySession {
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    output: Receiver<Vec<u8>>,
+    /// The reader thread saw EOF and everything it read has been taken
+    eof: bool,
+    /// Exit code, and when the exit was noticed
+    exited: Option<(i32, Instant)>,
+}
+
+/// Grid size handed to the PTY (`TIOCSWINSZ`).
+#[derive(Clone, Copy)]
+pub(super) struct GridSize {
+    pub cols: usize,
+    pub rows: usize,
+    pub cell_width: f32,
+    pub cell_height: f32,
+}
+
+impl From<GridSize> for PtySize {
+    fn from(size: GridSize) -> Self {
+        PtySize {
+            rows: size.rows.min(u16::MAX as usize) as u16,
+            cols: size.cols.min(u16::MAX as usize) as u16,
+            pixel_width: (size.cols as f32 * size.cell_width) as u16,
+            pixel_height: (size.rows as f32 * size.cell_height) as u16,
+        }
+    }
+}
+
+impl PtySession {
+    /// Spawn `argv` as the session leader of a new PTY, with the engine's
+    /// environment and working directory.
+    pub fn spawn(argv: &[String], env: &HashMap<String, String>, size: GridSize) -> Result<Self, String> {
+        let pair = native_pty_system()
+            .openpty(size.into())
+            .map_err(|e| format!("openpty: {}", e))?;
+
+        let mut cmd = CommandBuilder::new(&argv[0]);
+        cmd.args(&argv[1..]);
+        cmd.env_clear();
+        for (key, value) in env {
+            cmd.env(key, value);
+        }
+        cmd.env("TERM", "xterm-256color");
+        cmd.env("COLORTERM", "truecolor");
+        if let Some(pwd) = env.get("PWD") {
+            cmd.cwd(pwd);
+        }
+
+        let child = pair
+            .slave
+            .spawn_command(cmd)
+            .map_err(|e| format!("{}: {}", argv[0], e))?;
+        // The child holds its own copy; ours must close so EOF reaches the reader
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+        let writer = pair.master.take_writer().map_err(|e| e.to_string())?;
+
+        let (tx, output) = mpsc::channel();
+        std::thread::Builder::new()
+            .name(format!("pty-{}", argv[0]))
+            .spawn(move || {
+                let mut buf = [0u8; 8192];
+                loop {
+                    match reader.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => {
+                            if tx.send(buf[..n].to_vec()).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+            })
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            master: pair.master,
+            writer,
+            child,
+            output,
+            eof: false,
+            exited: None,
+        })
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        if let Err(e) = self.writer.write_all(bytes).and_then(|_| self.writer.flush()) {
+            bevy::log::debug!("PTY write failed: {}", e);
+        }
+    }
+
+    pub fn resize(&self, size: GridSize) {
+        if let Err(e) = self.master.resize(size.into()) {
+            bevy::log::warn!("PTY resize failed: {}", e);
+        }
+    }
+
+    /// Output produced since the last call.
+    pub fn read_output(&mut self) -> Vec<u8> {
+        let mut output = Vec::new();
+        loop {
+            match self.output.try_recv() {
+                Ok(bytes) => output.extend(bytes),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.eof = true;
+                    break;
+                }
+            }
+        }
+        output
+    }
+
+    /// Exit code once the command has finished and its last output has been
+    /// read: the reader reached EOF, or `EXIT_DRAIN_TIMEOUT` passed since
+    /// the exit. Call after [`read_output`](Self::read_output).
+    pub fn finished(&mut self) -> Option<i32> {
+        if self.exited.is_none() {
+            let status = self.child.try_wait().ok()??;
+            self.exited = Some((status.exit_code() as i32, Instant::now()));
+        }
+        let (code, exited_at) = self.exited?;
+        (self.eof || exited_at.elapsed() >= EXIT_DRAIN_TIMEOUT).then_some(code)
+    }
+}
+
+impl Drop for PtySession {
+    fn drop(&mut self) {
+        if self.exited.is_none() && !matches!(self.child.try_wait(), Ok(Some(_))) {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+/// Head and arguments of `block` when it is a lone external command
+/// (`vim foo`, `^ssh host`) without pipes or redirections. Anything else,
+/// including arguments with unquoted globs (which nushell expands itself),
+/// runs through the regular captured path. Bare words parse as glob
+/// patterns too, so only those with glob characters count.
+pub(super) fn lone_external(block: &Block) -> Option<(&Expression, &[ExternalArgument])> {
+    let [pipeline] = block.pipelines.as_slice() else {
+        return None;
+    };
+    let [element] = pipeline.elements.as_slice() else {
+        return None;
+    };
+    if element.redirection.is_some() {
+        return None;
+    }
+    let Expr::ExternalCall(head, args) = &element.expr.expr else {
+        return None;
+    };
+    if args.iter().any(expands_glob) {
+        return None;
+    }
+    Some((head, args))
+}
+
+/// Whether nushell would expand `arg` as a glob: an unquoted pattern with
+/// `*`, `?` or `[` in it.
+fn expands_glob(arg: &ExternalArgument) -> bool {
+    match arg {
+        ExternalArgument::Regular(expr) => match &expr.expr {
+            Expr::GlobPattern(pattern, _) => pattern.contains(['*', '?', '[']),
+            _ => false,
+        },
+        ExternalArgument::Spread(_) => false,
+    }
+}
+
+/// Evaluate the command name and arguments to strings.
+pub(super) fn eval_argv(engine: &mut NuShellEngine, head: &Expression, args: &[ExternalArgument]) -> Result<Vec<String>, ShellError> {
+    let mut eval = |expr: &Expression| eval_expression::<WithoutDebug>(&engine.engine_state, &mut engine.stack, expr);
+
+    let mut argv = vec![eval(head)?.coerce_into_string()?];
+    for arg in args {
+        match arg {
+            ExternalArgument::Regular(expr) => argv.push(expand_tilde(eval(expr)?.coerce_into_string()?)),
+            ExternalArgument::Spread(expr) => {
+                for item in eval(expr)?.into_list()? {
+                    argv.push(item.coerce_into_string()?);
+                }
+            }
+        }
+    }
+    Ok(argv)
+}
+
+fn expand_tilde(arg: String) -> String {
+    match (arg.strip_prefix('~'), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", home, rest),
+        _ => arg,
+    }
+}
+
+/// Held modifiers for [`encode_key`].
+#[derive(Clone, Copy, Default)]
+pub(super) struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+impl Modifiers {
+    /// xterm modifier parameter (`CSI 1;<n>A`), 1 meaning none.
+    fn param(self) -> u8 {
+        1 + self.shift as u8 + 2 * self.alt as u8 + 4 * self.ctrl as u8
+    }
+}
+
+/// Encode a key press the way xterm does. `app_cursor` is DECCKM
+/// (`TermMode::APP_CURSOR`), set by full-screen programs.
+pub(super) fn encode_key(key: &Key, mods: Modifiers, app_cursor: bool) -> Option<Vec<u8>> {
+    let csi_letter = |letter: char| -> Vec<u8> {
+        match mods.param() {
+            1 if app_cursor => format!("\x1bO{}", letter).into_bytes(),
+            1 => format!("\x1b[{}", letter).into_bytes(),
+            p => format!("\x1b[1;{}{}", p, letter).into_bytes(),
+        }
+    };
+    let csi_tilde = |code: u8| -> Vec<u8> {
+        match mods.param() {
+            1 => format!("\x1b[{}~", code).into_bytes(),
+            p => format!("\x1b[{};{}~", code, p).into_bytes(),
+        }
+    };
+    let ss3 = |letter: char| -> Vec<u8> {
+        match mods.param() {
+            1 => format!("\x1bO{}", letter).into_bytes(),
+            p => format!("\x1b[1;{}{}", p, letter).into_bytes(),
+        }
+    };
+
+    let bytes = match key {
+        Key::Character(s) => {
+            let mut chars = s.chars();
+            let mut bytes = match (chars.next(), chars.next()) {
+                (Some(c), None) if mods.ctrl => vec![ctrl_byte(c)?],
+                _ => s.as_bytes().to_vec(),
+            };
+            if mods.alt {
+                bytes.insert(0, 0x1b);
+            }
+            bytes
+        }
+        Key::Space if mods.ctrl => vec![0],
+        Key::Space => b" ".to_vec(),
+        Key::Enter => b"\r".to_vec(),
+        Key::Tab if mods.shift => b"\x1b[Z".to_vec(),
+        Key::Tab => b"\t".to_vec(),
+        Key::Backspace if mods.alt => b"\x1b\x7f".to_vec(),
+        Key::Backspace if mods.ctrl => vec![0x08],
+        Key::Backspace => vec![0x7f],
+        Key::Escape => vec![0x1b],
+        Key::ArrowUp => csi_letter('A'),
+        Key::ArrowDown => csi_letter('B'),
+        Key::ArrowRight => csi_letter('C'),
+        Key::ArrowLeft => csi_letter('D'),
+        Key::Home => csi_letter('H'),
+        Key::End => csi_letter('F'),
+        Key::Insert => csi_tilde(2),
+        Key::Delete => csi_tilde(3),
+        Key::PageUp => csi_tilde(5),
+        Key::PageDown => csi_tilde(6),
+        Key::F1 => ss3('P'),
+        Key::F2 => ss3('Q'),
+        Key::F3 => ss3('R'),
+        Key::F4 => ss3('S'),
+        Key::F5 => csi_tilde(15),
+        Key::F6 => csi_tilde(17),
+        Key::F7 => csi_tilde(18),
+        Key::F8 => csi_tilde(19),
+        Key::F9 => csi_tilde(20),
+        Key::F10 => csi_tilde(21),
+        Key::F11 => csi_tilde(23),
+        Key::F12 => csi_tilde(24),
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+/// Control character for Ctrl+`c` (`^A`..`^Z`, `^[`, `^\`, `^]`, `^^`, `^_`, `^?`).
+fn ctrl_byte(c: char) -> Option<u8> {
+    match c.to_ascii_lowercase() {
+        c @ 'a'..='z' => Some(c as u8 - b'a' + 1),
+        '@' | '2' | ' ' => Some(0),
+        '[' | '3' => Some(0x1b),
+        '\\' | '4' => Some(0x1c),
+        ']' | '5' => Some(0x1d),
+        '^' | '6' => Some(0x1e),
+        '_' | '-' | '7' => Some(0x1f),
+        '?' | '8' => Some(0x7f),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nu_cmd_lang::create_default_context;
+    use nu_command::add_shell_command_context;
+    use nu_parser::parse;
+    use nu_protocol::engine::StateWorkingSet;
+
+    const NONE: Modifiers = mods(false, false, false);
+    const SHIFT: Modifiers = mods(true, false, false);
+    const ALT: Modifiers = mods(false, true, false);
+    const CTRL: Modifiers = mods(false, false, true);
+
+    const fn mods(shift: bool, alt: bool, ctrl: bool) -> Modifiers {
+        Modifiers { shift, alt, ctrl }
+    }
+
+    fn encode(key: Key, mods: Modifiers) -> Option<Vec<u8>> {
+        encode_key(&key, mods, false)
+    }
+
+    fn char_key(s: &str) -> Key {
+        Key::Character(s.into())
+    }
+
+    #[test]
+    fn test_encode_ctrl_letters() {
+        assert_eq!(encode(char_key("a"), CTRL), Some(vec![0x01]));
+        assert_eq!(encode(char_key("Z"), CTRL), Some(vec![0x1a]));
+        assert_eq!(encode(char_key("["), CTRL), Some(vec![0x1b]));
+        assert_eq!(encode(char_key("-"), CTRL), Some(vec![0x1f]));
+        assert_eq!(encode(char_key("?"), CTRL), Some(vec![0x7f]));
+        assert_eq!(encode(Key::Space, CTRL), Some(vec![0]));
+        assert_eq!(encode(Key::Backspace, CTRL), Some(vec![0x08]));
+        // No control character for it, so nothing is sent
+        assert_eq!(encode(char_key("é"), CTRL), None);
+        assert_eq!(encode(char_key("a"), NONE), Some(b"a".to_vec()));
+    }
+
+    #[test]
+    fn test_encode_alt_prefixes_escape() {
+        assert_eq!(encode(char_key("x"), ALT), Some(b"\x1bx".to_vec()));
+        assert_eq!(encode(char_key("c"), mods(false, true, true)), Some(vec![0x1b, 0x03]));
+        assert_eq!(encode(Key::Backspace, ALT), Some(b"\x1b\x7f".to_vec()));
+    }
+
+    #[test]
+    fn test_encode_shift_tab() {
+        assert_eq!(encode(Key::Tab, SHIFT), Some(b"\x1b[Z".to_vec()));
+        assert_eq!(encode(Key::Tab, NONE), Some(b"\t".to_vec()));
+    }
+
+    #[test]
+    fn test_encode_arrows_follow_app_cursor() {
+        assert_eq!(encode_key(&Key::ArrowUp, NONE, false), Some(b"\x1b[A".to_vec()));
+        assert_eq!(encode_key(&Key::ArrowUp, NONE, true), Some(b"\x1bOA".to_vec()));
+        assert_eq!(encode_key(&Key::Home, NONE, true), Some(b"\x1bOH".to_vec()));
+        // A modifier switches to the CSI form in either mode
+        assert_eq!(encode_key(&Key::ArrowLeft, CTRL, true), Some(b"\x1b[1;5D".to_vec()));
+        let alt_shift = mods(true, true, false);
+        assert_eq!(encode(Key::ArrowRight, alt_shift), Some(b"\x1b[1;4C".to_vec()));
+    }
+
+    #[test]
+    fn test_encode_function_keys_with_modifiers() {
+        assert_eq!(encode(Key::F1, NONE), Some(b"\x1bOP".to_vec()));
+        assert_eq!(encode(Key::F1, SHIFT), Some(b"\x1b[1;2P".to_vec()));
+        assert_eq!(encode(Key::F4, CTRL), Some(b"\x1b[1;5S".to_vec()));
+        assert_eq!(encode(Key::F5, NONE), Some(b"\x1b[15~".to_vec()));
+        assert_eq!(encode(Key::F12, CTRL), Some(b"\x1b[24;5~".to_vec()));
+        assert_eq!(encode(Key::Delete, mods(true, true, true)), Some(b"\x1b[3;8~".to_vec()));
+        assert_eq!(encode(Key::Shift, NONE), None);
+    }
+
+    fn is_lone_external(source: &str) -> bool {
+        let engine_state = add_shell_command_context(create_default_context());
+        let mut working_set = StateWorkingSet::new(&engine_state);
+        let block = parse(&mut working_set, None, source.as_bytes(), false);
+        let errors = &working_set.parse_errors;
+        assert!(errors.is_empty(), "{}: {:?}", source, errors);
+        lone_external(&block).is_some()
+    }
+
+    #[test]
+    fn test_lone_external() {
+        assert!(is_lone_external("vim foo"));
+        assert!(is_lone_external("^ssh host"));
+        assert!(is_lone_external("vim '*.rs'"));
+
+        assert!(!is_lone_external("ls"));
+        assert!(!is_lone_external("vim foo | less"));
+        assert!(!is_lone_external("vim foo o> out.txt"));
+        assert!(!is_lone_external("vim *.rs"));
+        assert!(!is_lone_external("vim src/[ab].rs"));
+        assert!(!is_lone_external("^cat foo; vim"));
+    }
+}
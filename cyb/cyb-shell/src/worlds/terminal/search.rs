@@ -0,0 +1,331 @@
+//! Scrollback search: a query line over the bottom row, matched with
+//! alacritty's `RegexSearch` across the whole grid. Matches on screen are
+//! highlighted and the focused one is scrolled into view.
+
+use alacritty_terminal::event::EventListener;
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::{Boundary, Column, Direction, Line, Point, Side};
+use alacritty_terminal::term::search::{Match, RegexIter, RegexSearch};
+use alacritty_terminal::Term;
+use bevy::input::keyboard::{Key, KeyCode, KeyboardInput};
+
+use super::pty::Modifiers;
+
+/// Lines above the viewport scanned for a match that starts there and ends
+/// on screen.
+const MATCH_LOOKBEHIND_LINES: i32 = 100;
+
+/// What the search did with a key.
+pub(super) enum SearchAction {
+    /// Used by the search; repaint in case the query, toggles or focus
+    /// changed
+    Redraw,
+    /// Escape: close the search
+    Close,
+    /// Not a search key; handle it as usual
+    None,
+}
+
+pub(super) struct ScrollbackSearch {
+    query: String,
+    /// Off by default: the query matches regardless of case
+    case_sensitive: bool,
+    /// Off by default: the query is matched literally
+    regex_mode: bool,
+    /// Compiled query; `None` when it is empty or not a valid regex
+    regex: Option<RegexSearch>,
+    /// Match scrolled into view and highlighted as the current one
+    focused: Option<Match>,
+    /// Where the next search starts: the bottom of the screen when opened,
+    /// then next to the focused match
+    origin: Point,
+}
+
+impl ScrollbackSearch {
+    /// Search upwards from the bottom of the screen.
+    pub fn open<T>(term: &Term<T>) -> Self {
+        let bottom = Line(term.screen_lines() as i32 - 1 - term.grid().display_offset() as i32);
+        Self {
+            query: String::new(),
+            case_sensitive: false,
+            regex_mode: false,
+            regex: None,
+            focused: None,
+            origin: Point::new(bottom, Column(term.columns() - 1)),
+        }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn case_sensitive(&self) -> bool {
+        self.case_sensitive
+    }
+
+    pub fn regex_mode(&self) -> bool {
+        self.regex_mode
+    }
+
+    pub fn focused(&self) -> Option<&Match> {
+        self.focused.as_ref()
+    }
+
+    /// The query is set but matches nothing, or doesn't compile.
+    pub fn failing(&self) -> bool {
+        !self.query.is_empty() && self.focused.is_none()
+    }
+
+    /// Typing edits the query; Enter or Up goes to the next older match,
+    /// Shift+Enter or Down to the next newer one; Alt+C toggles case
+    /// sensitivity and Alt+R regex syntax.
+    pub fn handle_key<T: EventListener>(
+        &mut self,
+        event: &KeyboardInput,
+        mods: Modifiers,
+        term: &mut Term<T>,
+    ) -> SearchAction {
+        match &event.logical_key {
+            Key::Escape => return SearchAction::Close,
+            Key::Enter if mods.shift => self.step(term, Direction::Right),
+            Key::Enter | Key::ArrowUp => self.step(term, Direction::Left),
+            Key::ArrowDown => self.step(term, Direction::Right),
+            Key::Backspace => {
+                self.query.pop();
+                self.update(term);
+            }
+            _ if mods.alt && event.key_code == KeyCode::KeyC => {
+                self.case_sensitive = !self.case_sensitive;
+                self.update(term);
+            }
+            _ if mods.alt && event.key_code == KeyCode::KeyR => {
+                self.regex_mode = !self.regex_mode;
+                self.update(term);
+            }
+            Key::Character(c) if mods.ctrl && c.eq_ignore_ascii_case("u") => {
+                self.query.clear();
+                self.update(term);
+            }
+            Key::Character(c) if !mods.ctrl && !mods.alt => {
+                self.query.push_str(c);
+                self.update(term);
+            }
+            Key::Space => {
+                self.query.push(' ');
+                self.update(term);
+            }
+            // Ctrl chords (Ctrl+C interrupts) and scrolling stay with the terminal
+            _ if mods.ctrl => return SearchAction::None,
+            Key::PageUp | Key::PageDown => return SearchAction::None,
+            Key::Home | Key::End if mods.shift => return SearchAction::None,
+            _ => {}
+        }
+        SearchAction::Redraw
+    }
+
+    /// Matches overlapping the screen, in grid order.
+    pub fn visible_matches<T>(&mut self, term: &Term<T>) -> Vec<Match> {
+        let Some(regex) = self.regex.as_mut() else {
+            return Vec::new();
+        };
+        let offset = term.grid().display_offset() as i32;
+        let top = Line(-offset);
+        let bottom = Line(term.screen_lines() as i32 - 1 - offset);
+        let start = Point::new(Line(top.0 - MATCH_LOOKBEHIND_LINES).max(term.topmost_line()), Column(0));
+        let end = Point::new(bottom, Column(term.columns() - 1));
+        RegexIter::new(start, end, Direction::Right, term, regex)
+            .skip_while(|found| found.end().line < top)
+            .collect()
+    }
+
+    /// Recompile the query and focus the nearest match from the origin.
+    fn update<T: EventListener>(&mut self, term: &mut Term<T>) {
+        self.regex = if self.query.is_empty() {
+            None
+        } else {
+            let pattern = if self.regex_mode { self.query.clone() } else { escape(&self.query) };
+            let flags = if self.case_sensitive { "(?-i)" } else { "(?i)" };
+            RegexSearch::new(&format!("{}{}", flags, pattern)).ok()
+        };
+        self.focus(term, self.origin, Direction::Left);
+    }
+
+    /// Move to the next match in `direction` from the focused one, wrapping
+    /// around the scrollback.
+    fn step<T: EventListener>(&mut self, term: &mut Term<T>, direction: Direction) {
+        let Some(ref focused) = self.focused else { return };
+        self.origin = match direction {
+            Direction::Right => focused.end().add(term, Boundary::None, 1),
+            Direction::Left => focused.start().sub(term, Boundary::None, 1),
+        };
+        self.focus(term, self.origin, direction);
+    }
+
+    fn focus<T: EventListener>(&mut self, term: &mut Term<T>, origin: Point, direction: Direction) {
+        let side = match direction {
+            Direction::Right => Side::Left,
+            Direction::Left => Side::Right,
+        };
+        self.focused = self
+            .regex
+            .as_mut()
+            .and_then(|regex| term.search_next(regex, origin, direction, side, None));
+        if let Some(ref found) = self.focused {
+            term.scroll_to_point(*found.start());
+        }
+    }
+}
+
+/// `text` with regex metacharacters escaped, for literal search.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$#&-~".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Cmd+F on macOS, Ctrl+Shift+F elsewhere.
+pub(super) fn is_search_shortcut(key: &Key, cmd: bool, mods: Modifiers) -> bool {
+    let chord = if cfg!(target_os = "macos") { cmd && !mods.ctrl } else { mods.ctrl && mods.shift };
+    chord && matches!(key, Key::Character(c) if c.eq_ignore_ascii_case("f"))
+}
+
+#[cfg(test)]
+mod tests {
+    use alacritty_terminal::event::VoidListener;
+    use alacritty_terminal::term::test::mock_term;
+    use bevy::ecs::entity::Entity;
+    use bevy::input::ButtonState;
+    use bevy::input::keyboard::NativeKeyCode;
+
+    use super::*;
+
+    /// Five rows; the last is the bottom of the screen, where a search starts.
+    const LINES: &str = "foo bar\r\nFoo.baz\r\nfoo bar\r\nfooXbaz\r\nend";
+
+    const NONE: Modifiers = Modifiers {
+        shift: false,
+        alt: false,
+        ctrl: false,
+    };
+    /// Physical key of typed characters, which the search ignores.
+    const ANY_KEY: KeyCode = KeyCode::Unidentified(NativeKeyCode::Unidentified);
+
+    fn press(
+        search: &mut ScrollbackSearch,
+        term: &mut Term<VoidListener>,
+        key: Key,
+        key_code: KeyCode,
+        mods: Modifiers,
+    ) -> SearchAction {
+        let event = KeyboardInput {
+            key_code,
+            logical_key: key,
+            state: ButtonState::Pressed,
+            text: None,
+            repeat: false,
+            window: Entity::PLACEHOLDER,
+        };
+        search.handle_key(&event, mods, term)
+    }
+
+    fn type_query(search: &mut ScrollbackSearch, term: &mut Term<VoidListener>, text: &str) {
+        for c in text.chars() {
+            let key = Key::Character(c.to_string().into());
+            press(search, term, key, ANY_KEY, NONE);
+        }
+    }
+
+    /// Alt+`key_code`, e.g. Alt+C for case sensitivity.
+    fn toggle(search: &mut ScrollbackSearch, term: &mut Term<VoidListener>, key_code: KeyCode) {
+        let mods = Modifiers { alt: true, ..NONE };
+        press(search, term, Key::Character("x".into()), key_code, mods);
+    }
+
+    fn step(search: &mut ScrollbackSearch, term: &mut Term<VoidListener>, key: Key, mods: Modifiers) {
+        press(search, term, key, ANY_KEY, mods);
+    }
+
+    /// Row and column where the focused match starts.
+    fn focused(search: &ScrollbackSearch) -> Option<(i32, usize)> {
+        search.focused().map(|found| (found.start().line.0, found.start().column.0))
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a.b*(c)"), r"a\.b\*\(c\)");
+        assert_eq!(escape("~/src-1 [x]"), r"\~/src\-1 \[x\]");
+        assert_eq!(escape("plain text"), "plain text");
+    }
+
+    #[test]
+    fn test_literal_and_regex_toggle() {
+        let mut term = mock_term(LINES);
+        let mut search = ScrollbackSearch::open(&term);
+
+        // Literally, only "Foo.baz" has "o.b"
+        type_query(&mut search, &mut term, "o.b");
+        assert_eq!(focused(&search), Some((1, 2)));
+
+        // As a regex the dot matches anything, so the nearest row wins
+        toggle(&mut search, &mut term, KeyCode::KeyR);
+        assert!(search.regex_mode());
+        assert_eq!(focused(&search), Some((3, 2)));
+
+        // An invalid regex matches nothing until it's fixed
+        type_query(&mut search, &mut term, "(");
+        assert!(search.failing());
+        step(&mut search, &mut term, Key::Backspace, NONE);
+        assert_eq!(focused(&search), Some((3, 2)));
+
+        toggle(&mut search, &mut term, KeyCode::KeyR);
+        assert!(!search.regex_mode());
+        assert_eq!(focused(&search), Some((1, 2)));
+    }
+
+    #[test]
+    fn test_case_toggle() {
+        let mut term = mock_term(LINES);
+        let mut search = ScrollbackSearch::open(&term);
+
+        type_query(&mut search, &mut term, "Foo");
+        assert!(!search.case_sensitive());
+        assert_eq!(focused(&search), Some((3, 0)));
+
+        toggle(&mut search, &mut term, KeyCode::KeyC);
+        assert!(search.case_sensitive());
+        assert_eq!(focused(&search), Some((1, 0)));
+
+        type_query(&mut search, &mut term, "X");
+        assert!(search.failing());
+    }
+
+    #[test]
+    fn test_step_wraps_around() {
+        let mut term = mock_term(LINES);
+        let mut search = ScrollbackSearch::open(&term);
+
+        type_query(&mut search, &mut term, "foo");
+        assert_eq!(focused(&search), Some((3, 0)));
+
+        // Enter and Up go to older matches, then wrap to the bottom
+        step(&mut search, &mut term, Key::Enter, NONE);
+        assert_eq!(focused(&search), Some((2, 0)));
+        step(&mut search, &mut term, Key::ArrowUp, NONE);
+        assert_eq!(focused(&search), Some((1, 0)));
+        step(&mut search, &mut term, Key::Enter, NONE);
+        assert_eq!(focused(&search), Some((0, 0)));
+        step(&mut search, &mut term, Key::Enter, NONE);
+        assert_eq!(focused(&search), Some((3, 0)));
+
+        // Down and Shift+Enter go to newer ones, wrapping to the top
+        step(&mut search, &mut term, Key::ArrowDown, NONE);
+        assert_eq!(focused(&search), Some((0, 0)));
+        step(&mut search, &mut term, Key::Enter, Modifiers { shift: true, ..NONE });
+        assert_eq!(focused(&search), Some((1, 0)));
+    }
+}
@@ -0,0 +1,185 @@
+//! Mouse selection on the grid and the clipboard shortcuts. Selections are
+//! alacritty's own `Selection`, kept on the `Term`, so they follow the
+//! scrollback as output arrives and `selection_to_string` copies them.
+
+use std::time::{Duration, Instant};
+
+use alacritty_terminal::index::{Column, Line, Point, Side};
+use alacritty_terminal::selection::{Selection, SelectionType};
+use alacritty_terminal::Term;
+use bevy::input::keyboard::Key;
+use bevy::math::Vec2;
+
+use super::pty::Modifiers;
+
+/// Clicks on the same cell closer together than this count as a double or
+/// triple click.
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Start of a bracketed paste (`TermMode::BRACKETED_PASTE`).
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Left-button state between frames.
+#[derive(Default)]
+pub(super) struct MouseSelection {
+    last_click: Option<(Instant, Point)>,
+    /// 1, 2 or 3 for a single, double or triple click
+    clicks: u8,
+    /// Where the selection was last extended to while the button is held
+    drag: Option<(Point, Side)>,
+}
+
+impl MouseSelection {
+    /// Button pressed on `point`: select from there by cell, word or line,
+    /// depending on how many clicks came in quick succession. With `extend`
+    /// (Shift) the current selection grows to `point` instead.
+    pub fn press<T>(&mut self, term: &mut Term<T>, point: Point, side: Side, extend: bool) {
+        let now = Instant::now();
+        self.clicks = match self.last_click {
+            Some((at, last)) if last == point && now.duration_since(at) < MULTI_CLICK_INTERVAL => self.clicks % 3 + 1,
+            _ => 1,
+        };
+        self.last_click = Some((now, point));
+        self.drag = Some((point, side));
+
+        match term.selection.as_mut() {
+            Some(selection) if extend => selection.update(point, side),
+            _ => {
+                let ty = match self.clicks {
+                    1 => SelectionType::Simple,
+                    2 => SelectionType::Semantic,
+                    _ => SelectionType::Lines,
+                };
+                term.selection = Some(Selection::new(ty, point, side));
+            }
+        }
+    }
+
+    /// Pointer over `point` with the button held. Returns true when the
+    /// selection changed.
+    pub fn drag<T>(&mut self, term: &mut Term<T>, point: Point, side: Side) -> bool {
+        if self.drag.is_none_or(|last| last == (point, side)) {
+            return false;
+        }
+        let Some(selection) = term.selection.as_mut() else {
+            return false;
+        };
+        selection.update(point, side);
+        self.drag = Some((point, side));
+        true
+    }
+
+    /// Button released. A click that didn't drag selects nothing.
+    pub fn release<T>(&mut self, term: &mut Term<T>) {
+        self.drag = None;
+        if term.selection.as_ref().is_some_and(Selection::is_empty) {
+            term.selection = None;
+        }
+    }
+}
+
+/// Cell under `position` (physical pixels from the window's top-left) and
+/// which half of it, clamped to the grid. Rows scrolled back by
+/// `display_offset` map to negative lines, as alacritty's grid counts them.
+pub(super) fn cell_at(position: Vec2, cell: Vec2, cols: usize, rows: usize, display_offset: usize) -> (Point, Side) {
+    let x = position.x.max(0.0);
+    let y = position.y.max(0.0);
+    let col = ((x / cell.x) as usize).min(cols.saturating_sub(1));
+    let row = ((y / cell.y) as usize).min(rows.saturating_sub(1));
+
+    let within = x - col as f32 * cell.x;
+    let side = if within > cell.x / 2.0 || x >= cols as f32 * cell.x { Side::Right } else { Side::Left };
+    let line = Line(row as i32 - display_offset as i32);
+    (Point::new(line, Column(col)), side)
+}
+
+/// Copy or paste requested by a key press.
+#[derive(Clone, Copy)]
+pub(super) enum ClipboardAction {
+    Copy,
+    Paste,
+}
+
+/// Cmd+C / Cmd+V on macOS; Ctrl+Shift+C / Ctrl+Shift+V and Shift+Insert
+/// elsewhere, leaving Ctrl+C to interrupt.
+pub(super) fn clipboard_shortcut(key: &Key, cmd: bool, mods: Modifiers) -> Option<ClipboardAction> {
+    let chord = if cfg!(target_os = "macos") { cmd && !mods.ctrl } else { mods.ctrl && mods.shift };
+    match key {
+        Key::Character(c) if chord && c.eq_ignore_ascii_case("c") => Some(ClipboardAction::Copy),
+        Key::Character(c) if chord && c.eq_ignore_ascii_case("v") => Some(ClipboardAction::Paste),
+        Key::Insert if mods.shift && !mods.ctrl => Some(ClipboardAction::Paste),
+        _ => None,
+    }
+}
+
+/// Clipboard text as a PTY program should receive it: wrapped in
+/// bracketed-paste markers when it asked for them, so an editor inserts it
+/// verbatim, otherwise with line breaks sent as Enter. Escapes are dropped
+/// from bracketed text so it can't end the paste early.
+pub(super) fn paste_bytes(text: &str, bracketed: bool) -> Vec<u8> {
+    if bracketed {
+        let mut bytes = PASTE_START.to_vec();
+        bytes.extend(text.bytes().filter(|&b| b != 0x1b));
+        bytes.extend_from_slice(PASTE_END);
+        bytes
+    } else {
+        text.replace("\r\n", "\r").replace('\n', "\r").into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL: Vec2 = Vec2::new(10.0, 20.0);
+
+    fn mods(shift: bool, ctrl: bool) -> Modifiers {
+        Modifiers { shift, alt: false, ctrl }
+    }
+
+    #[test]
+    fn test_paste_bytes() {
+        assert_eq!(paste_bytes("a\x1b[201~b\n", true), b"\x1b[200~a[201~b\n\x1b[201~");
+        assert_eq!(paste_bytes("one\r\ntwo\nthree", false), b"one\rtwo\rthree");
+    }
+
+    #[test]
+    fn test_cell_at_picks_side() {
+        assert_eq!(cell_at(Vec2::new(14.0, 5.0), CELL, 80, 24, 0), (Point::new(Line(0), Column(1)), Side::Left));
+        assert_eq!(cell_at(Vec2::new(15.0, 5.0), CELL, 80, 24, 0), (Point::new(Line(0), Column(1)), Side::Left));
+        assert_eq!(cell_at(Vec2::new(16.0, 5.0), CELL, 80, 24, 0), (Point::new(Line(0), Column(1)), Side::Right));
+        // Past the last column the pointer is on the right of it
+        assert_eq!(cell_at(Vec2::new(850.0, 5.0), CELL, 80, 24, 0), (Point::new(Line(0), Column(79)), Side::Right));
+        assert_eq!(cell_at(Vec2::new(-5.0, -5.0), CELL, 80, 24, 0), (Point::new(Line(0), Column(0)), Side::Left));
+    }
+
+    #[test]
+    fn test_cell_at_scrolled_back() {
+        assert_eq!(cell_at(Vec2::new(0.0, 30.0), CELL, 80, 24, 3).0, Point::new(Line(-2), Column(0)));
+        assert_eq!(cell_at(Vec2::new(0.0, 1000.0), CELL, 80, 24, 3).0, Point::new(Line(20), Column(0)));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn test_clipboard_shortcut() {
+        let c = Key::Character("c".into());
+        assert!(clipboard_shortcut(&c, false, mods(false, true)).is_none());
+        assert!(clipboard_shortcut(&c, true, mods(false, false)).is_none());
+        let shifted = Key::Character("C".into());
+        assert!(matches!(clipboard_shortcut(&shifted, false, mods(true, true)), Some(ClipboardAction::Copy)));
+        let v = Key::Character("V".into());
+        assert!(matches!(clipboard_shortcut(&v, false, mods(true, true)), Some(ClipboardAction::Paste)));
+        assert!(matches!(clipboard_shortcut(&Key::Insert, false, mods(true, false)), Some(ClipboardAction::Paste)));
+        assert!(clipboard_shortcut(&Key::Insert, false, mods(true, true)).is_none());
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_clipboard_shortcut() {
+        let c = Key::Character("c".into());
+        assert!(matches!(clipboard_shortcut(&c, true, mods(false, false)), Some(ClipboardAction::Copy)));
+        assert!(clipboard_shortcut(&c, false, mods(false, true)).is_none());
+        assert!(clipboard_shortcut(&c, true, mods(false, true)).is_none());
+    }
+}
@@ -0,0 +1,415 @@
+//! Tabs and splits: where each session is shown. A tab is a tree of panes,
+//! split side by side or stacked; layouts are in cells, with a one-cell
+//! divider between the halves of a split. Only the active tab is drawn, but
+//! every session keeps running and keeps a grid sized for its pane.
+
+use bevy::input::keyboard::{Key, KeyCode, KeyboardInput};
+
+use super::pty::Modifiers;
+
+pub(super) type SessionId = u64;
+
+/// Smallest pane a split may leave, so a shell stays usable.
+const MIN_PANE_COLS: usize = 10;
+const MIN_PANE_ROWS: usize = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Axis {
+    /// Side by side, divided by a column
+    Horizontal,
+    /// Stacked, divided by a row
+    Vertical,
+}
+
+/// Cells from the window's top-left.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) struct Rect {
+    pub col: usize,
+    pub row: usize,
+    pub cols: usize,
+    pub rows: usize,
+}
+
+impl Rect {
+    pub fn contains(&self, col: usize, row: usize) -> bool {
+        (self.col..self.col + self.cols).contains(&col) && (self.row..self.row + self.rows).contains(&row)
+    }
+
+    /// The two halves along `axis` and the divider between them.
+    fn split(self, axis: Axis) -> (Rect, Rect, Rect) {
+        match axis {
+            Axis::Horizontal => {
+                let first = self.cols.saturating_sub(1) / 2;
+                let second = self.cols.saturating_sub(first + 1);
+                (
+                    Rect { cols: first, ..self },
+                    Rect { col: self.col + first + 1, cols: second, ..self },
+                    Rect { col: self.col + first, cols: 1, ..self },
+                )
+            }
+            Axis::Vertical => {
+                let first = self.rows.saturating_sub(1) / 2;
+                let second = self.rows.saturating_sub(first + 1);
+                (
+                    Rect { rows: first, ..self },
+                    Rect { row: self.row + first + 1, rows: second, ..self },
+                    Rect { row: self.row + first, rows: 1, ..self },
+                )
+            }
+        }
+    }
+
+    fn fits(&self, axis: Axis) -> bool {
+        match axis {
+            Axis::Horizontal => self.cols > 2 * MIN_PANE_COLS,
+            Axis::Vertical => self.rows > 2 * MIN_PANE_ROWS,
+        }
+    }
+}
+
+enum Node {
+    Pane(SessionId),
+    Split {
+        axis: Axis,
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    fn layout(&self, area: Rect, panes: &mut Vec<(SessionId, Rect)>, dividers: &mut Vec<Rect>) {
+        match self {
+            Node::Pane(id) => panes.push((*id, area)),
+            Node::Split { axis, first, second } => {
+                let (a, b, divider) = area.split(*axis);
+                first.layout(a, panes, dividers);
+                second.layout(b, panes, dividers);
+                dividers.push(divider);
+            }
+        }
+    }
+
+    fn first_pane(&self) -> SessionId {
+        match self {
+            Node::Pane(id) => *id,
+            Node::Split { first, .. } => first.first_pane(),
+        }
+    }
+
+    /// Replace the pane `target` with a split of it and `new`.
+    fn split(&mut self, target: SessionId, new: SessionId, axis: Axis) -> bool {
+        match self {
+            Node::Pane(id) if *id == target => {
+                *self = Node::Split {
+                    axis,
+                    first: Box::new(Node::Pane(target)),
+                    second: Box::new(Node::Pane(new)),
+                };
+                true
+            }
+            Node::Pane(_) => false,
+            Node::Split { first, second, .. } => first.split(target, new, axis) || second.split(target, new, axis),
+        }
+    }
+
+    /// The tree without the pane `id`; its sibling takes the split's place.
+    /// `None` when nothing is left.
+    fn remove(self, id: SessionId) -> Option<Node> {
+        match self {
+            Node::Pane(pane) if pane == id => None,
+            Node::Pane(_) => Some(self),
+            Node::Split { axis, first, second } => match (first.remove(id), second.remove(id)) {
+                (Some(first), Some(second)) => Some(Node::Split {
+                    axis,
+                    first: Box::new(first),
+                    second: Box::new(second),
+                }),
+                (Some(rest), None) | (None, Some(rest)) => Some(rest),
+                (None, None) => None,
+            },
+        }
+    }
+
+    fn contains(&self, id: SessionId) -> bool {
+        match self {
+            Node::Pane(pane) => *pane == id,
+            Node::Split { first, second, .. } => first.contains(id) || second.contains(id),
+        }
+    }
+}
+
+struct Tab {
+    root: Node,
+    /// Pane that gets the keyboard while the tab is active
+    focused: SessionId,
+}
+
+impl Tab {
+    fn layout(&self, area: Rect) -> (Vec<(SessionId, Rect)>, Vec<Rect>) {
+        let mut panes = Vec::new();
+        let mut dividers = Vec::new();
+        self.root.layout(area, &mut panes, &mut dividers);
+        (panes, dividers)
+    }
+}
+
+/// Every tab and its panes. Never empty: closing the last pane is left to
+/// the caller, who opens a new session first.
+pub(super) struct Tabs {
+    tabs: Vec<Tab>,
+    active: usize,
+}
+
+impl Tabs {
+    pub fn new(first: SessionId) -> Self {
+        Self {
+            tabs: vec![Tab { root: Node::Pane(first), focused: first }],
+            active: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// Session with the keyboard.
+    pub fn focused(&self) -> SessionId {
+        self.tabs[self.active].focused
+    }
+
+    /// The focused pane of each tab, in tab order; tabs are named after it.
+    pub fn tab_sessions(&self) -> impl Iterator<Item = SessionId> + '_ {
+        self.tabs.iter().map(|tab| tab.focused)
+    }
+
+    /// A new tab after the active one, holding `id`, made active.
+    pub fn open_tab(&mut self, id: SessionId) {
+        self.active += 1;
+        self.tabs.insert(self.active, Tab { root: Node::Pane(id), focused: id });
+    }
+
+    /// Whether the focused pane, laid out in `area`, has room to split.
+    pub fn can_split(&self, axis: Axis, area: Rect) -> bool {
+        let focused = self.focused();
+        let (panes, _) = self.tabs[self.active].layout(area);
+        panes.iter().any(|(id, rect)| *id == focused && rect.fits(axis))
+    }
+
+    /// Split the focused pane, putting `id` right of or below it, focused.
+    pub fn split(&mut self, id: SessionId, axis: Axis) {
+        let tab = &mut self.tabs[self.active];
+        if tab.root.split(tab.focused, id, axis) {
+            tab.focused = id;
+        }
+    }
+
+    /// Take the pane `id` out of its tab, and the tab out when it was the
+    /// last pane there. Returns false, changing nothing, for the very last
+    /// pane.
+    pub fn close(&mut self, id: SessionId) -> bool {
+        let Some(index) = self.tabs.iter().position(|tab| tab.root.contains(id)) else {
+            return true;
+        };
+        if self.tabs.len() == 1 && matches!(self.tabs[0].root, Node::Pane(_)) {
+            return false;
+        }
+
+        let tab = self.tabs.remove(index);
+        match tab.root.remove(id) {
+            Some(root) => {
+                let focused = if tab.focused == id { root.first_pane() } else { tab.focused };
+                self.tabs.insert(index, Tab { root, focused });
+            }
+            None if index < self.active || self.active == self.tabs.len() => {
+                self.active = self.active.saturating_sub(1);
+            }
+            None => {}
+        }
+        true
+    }
+
+    /// Move `offset` tabs along, wrapping around.
+    pub fn select_tab(&mut self, offset: isize) {
+        let len = self.tabs.len() as isize;
+        self.active = (self.active as isize + offset).rem_euclid(len) as usize;
+    }
+
+    /// Move the focus `offset` panes along the active tab, wrapping around.
+    pub fn focus_pane(&mut self, offset: isize) {
+        let tab = &mut self.tabs[self.active];
+        let mut panes = Vec::new();
+        tab.root.layout(Rect { col: 0, row: 0, cols: 0, rows: 0 }, &mut panes, &mut Vec::new());
+        let Some(current) = panes.iter().position(|(id, _)| *id == tab.focused) else { return };
+        let next = (current as isize + offset).rem_euclid(panes.len() as isize) as usize;
+        tab.focused = panes[next].0;
+    }
+
+    /// Focus `id` if it is a pane of the active tab.
+    pub fn focus(&mut self, id: SessionId) {
+        let tab = &mut self.tabs[self.active];
+        if tab.root.contains(id) {
+            tab.focused = id;
+        }
+    }
+
+    /// Panes and dividers of the active tab, laid out in `area`.
+    pub fn visible(&self, area: Rect) -> (Vec<(SessionId, Rect)>, Vec<Rect>) {
+        self.tabs[self.active].layout(area)
+    }
+
+    /// Every pane of every tab laid out in `area`, hidden tabs included, so
+    /// each session's grid already fits when its tab comes up.
+    pub fn all(&self, area: Rect) -> Vec<(SessionId, Rect)> {
+        self.tabs.iter().flat_map(|tab| tab.layout(area).0).collect()
+    }
+}
+
+/// Tab and pane commands bound to keys.
+#[derive(Clone, Copy)]
+pub(super) enum TabAction {
+    NewTab,
+    /// Close the focused pane, and its tab with it if it was the last one
+    ClosePane,
+    Split(Axis),
+    /// Move this many tabs along
+    SelectTab(isize),
+    /// Move the focus this many panes along
+    FocusPane(isize),
+}
+
+/// On macOS: Cmd+T new tab, Cmd+W close, Cmd+D split right, Cmd+Shift+D
+/// split down, Cmd+Shift+] / [ next and previous tab, Cmd+] / [ next and
+/// previous pane. Elsewhere the same with Ctrl+Shift, except Ctrl+Shift+E
+/// splits down and Ctrl+PageDown / PageUp switch tabs. Ctrl+Tab and
+/// Ctrl+Shift+Tab switch tabs everywhere.
+pub(super) fn shortcut(event: &KeyboardInput, cmd: bool, mods: Modifiers) -> Option<TabAction> {
+    if mods.ctrl && !cmd && matches!(event.logical_key, Key::Tab) {
+        return Some(TabAction::SelectTab(if mods.shift { -1 } else { 1 }));
+    }
+
+    let letter = |name: &str| matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case(name));
+    let bracket = match event.key_code {
+        KeyCode::BracketRight => Some(1),
+        KeyCode::BracketLeft => Some(-1),
+        _ => None,
+    };
+
+    if cfg!(target_os = "macos") {
+        if !cmd || mods.ctrl || mods.alt {
+            return None;
+        }
+        match bracket {
+            Some(offset) if mods.shift => Some(TabAction::SelectTab(offset)),
+            Some(offset) => Some(TabAction::FocusPane(offset)),
+            None if letter("t") => Some(TabAction::NewTab),
+            None if letter("w") => Some(TabAction::ClosePane),
+            None if letter("d") && mods.shift => Some(TabAction::Split(Axis::Vertical)),
+            None if letter("d") => Some(TabAction::Split(Axis::Horizontal)),
+            None => None,
+        }
+    } else {
+        if !mods.ctrl || mods.alt {
+            return None;
+        }
+        match (&event.logical_key, bracket) {
+            (Key::PageDown, _) if !mods.shift => Some(TabAction::SelectTab(1)),
+            (Key::PageUp, _) if !mods.shift => Some(TabAction::SelectTab(-1)),
+            _ if !mods.shift => None,
+            (_, Some(offset)) => Some(TabAction::FocusPane(offset)),
+            _ if letter("t") => Some(TabAction::NewTab),
+            _ if letter("w") => Some(TabAction::ClosePane),
+            _ if letter("d") => Some(TabAction::Split(Axis::Horizontal)),
+            _ if letter("e") => Some(TabAction::Split(Axis::Vertical)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(col: usize, row: usize, cols: usize, rows: usize) -> Rect {
+        Rect { col, row, cols, rows }
+    }
+
+    #[test]
+    fn test_rect_split() {
+        // An odd width leaves equal halves around the divider
+        assert_eq!(
+            rect(3, 0, 21, 5).split(Axis::Horizontal),
+            (rect(3, 0, 10, 5), rect(14, 0, 10, 5), rect(13, 0, 1, 5))
+        );
+        // An even one gives the extra column to the second half
+        assert_eq!(
+            rect(0, 0, 20, 5).split(Axis::Horizontal),
+            (rect(0, 0, 9, 5), rect(10, 0, 10, 5), rect(9, 0, 1, 5))
+        );
+        assert_eq!(
+            rect(0, 1, 8, 6).split(Axis::Vertical),
+            (rect(0, 1, 8, 2), rect(0, 4, 8, 3), rect(0, 3, 8, 1))
+        );
+        assert!(rect(0, 0, 21, 5).fits(Axis::Horizontal));
+        assert!(!rect(0, 0, 20, 5).fits(Axis::Horizontal));
+    }
+
+    #[test]
+    fn test_node_remove_collapses_split() {
+        let mut root = Node::Pane(1);
+        assert!(root.split(1, 2, Axis::Horizontal));
+        assert!(root.split(2, 3, Axis::Vertical));
+        assert!(!root.split(9, 4, Axis::Vertical));
+
+        // The nested split collapses to the pane left in it
+        let root = root.remove(2).unwrap();
+        let Node::Split { axis: Axis::Horizontal, first, second } = &root else {
+            panic!("expected the outer split to stay");
+        };
+        assert!(matches!(**first, Node::Pane(1)));
+        assert!(matches!(**second, Node::Pane(3)));
+
+        let root = root.remove(1).unwrap();
+        assert!(matches!(root, Node::Pane(3)));
+        assert!(root.remove(3).is_none());
+    }
+
+    #[test]
+    fn test_close() {
+        let mut tabs = Tabs::new(1);
+        assert!(!tabs.close(1));
+        assert!(tabs.close(9));
+
+        for id in 2..=5 {
+            tabs.open_tab(id);
+        }
+        tabs.select_tab(-2);
+        assert_eq!((tabs.active(), tabs.focused()), (2, 3));
+
+        // Closing an earlier tab keeps the same tab active
+        assert!(tabs.close(1));
+        assert_eq!((tabs.len(), tabs.active(), tabs.focused()), (4, 1, 3));
+
+        // So does closing a later one
+        assert!(tabs.close(5));
+        assert_eq!((tabs.len(), tabs.active(), tabs.focused()), (3, 1, 3));
+
+        // Closing the active tab activates the next one
+        assert!(tabs.close(3));
+        assert_eq!((tabs.len(), tabs.active(), tabs.focused()), (2, 1, 4));
+
+        // ... or the one before, when it was the last
+        assert!(tabs.close(4));
+        assert_eq!((tabs.len(), tabs.active(), tabs.focused()), (1, 0, 2));
+
+        // A split pane goes back to its sibling, which takes the focus
+        tabs.split(6, Axis::Horizontal);
+        assert_eq!(tabs.focused(), 6);
+        assert!(tabs.close(6));
+        assert_eq!((tabs.len(), tabs.focused()), (1, 2));
+        assert!(!tabs.close(2));
+    }
+}
@@ -0,0 +1,76 @@
+//! The texture the terminal sprite samples, which sugarloaf renders into
+//! directly. Bevy allocates it as the GPU side of the terminal `Image`; the
+//! render world hands the wgpu texture back to the main world, so frames
+//! never leave the GPU.
+
+use std::sync::{Arc, Mutex};
+
+use bevy::asset::{AssetId, RenderAssetUsages};
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use bevy::render::texture::GpuImage;
+use bevy::render::{Render, RenderApp, RenderSystems};
+use sugarloaf::wgpu;
+
+/// sugarloaf's output format, which its pipelines are built for.
+pub(super) const FORMAT: TextureFormat = TextureFormat::Bgra8UnormSrgb;
+
+/// The terminal image and, once the render world has prepared it, its
+/// texture; shared between the main and render worlds.
+#[derive(Resource, Clone, Default)]
+pub(super) struct RenderTarget(Arc<Mutex<Slot>>);
+
+#[derive(Default)]
+struct Slot {
+    image: Option<AssetId<Image>>,
+    texture: Option<wgpu::Texture>,
+}
+
+impl RenderTarget {
+    /// Share the texture behind `image` from now on.
+    pub fn track(&self, image: AssetId<Image>) {
+        let mut slot = self.0.lock().unwrap();
+        slot.image = Some(image);
+        slot.texture = None;
+    }
+
+    /// The texture to render into, if it has been allocated at `width` x
+    /// `height`; right after a resize the old one is still shared.
+    pub fn texture(&self, width: u32, height: u32) -> Option<wgpu::Texture> {
+        let slot = self.0.lock().unwrap();
+        slot.texture.clone().filter(|texture| (texture.width(), texture.height()) == (width, height))
+    }
+}
+
+/// An image with no CPU-side data that sugarloaf can render into and
+/// sprites can sample.
+pub(super) fn image(width: u32, height: u32) -> Image {
+    let mut image = Image::new_uninit(
+        Extent3d { width, height, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        FORMAT,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    );
+    image.texture_descriptor.usage |= TextureUsages::RENDER_ATTACHMENT;
+    image
+}
+
+/// Registers the shared target in both worlds.
+pub(super) fn build(app: &mut App) {
+    let target = RenderTarget::default();
+    app.insert_resource(target.clone());
+    if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+        render_app
+            .insert_resource(target)
+            .add_systems(Render, share_texture.in_set(RenderSystems::PrepareResources));
+    }
+}
+
+/// Runs after the render world prepares images, so a texture reallocated
+/// for a resized image is picked up the same frame.
+fn share_texture(target: Res<RenderTarget>, images: Res<RenderAssets<GpuImage>>) {
+    let mut slot = target.0.lock().unwrap();
+    let Some(image) = slot.image else { return };
+    slot.texture = images.get(image).map(|gpu_image| (*gpu_image.texture).clone());
+}
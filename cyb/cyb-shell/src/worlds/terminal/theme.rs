@@ -0,0 +1,304 @@
+//! Color themes in rio's format: a `[colors]` table of `#rrggbb` values
+//! keyed like rio-backend's `config::colors`, so rio themes can be dropped
+//! into `~/.cyb/themes` as they are. Keys a theme leaves out come from the
+//! bundled `default` theme; unknown keys are ignored.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use alacritty_terminal::vte::ansi::{NamedColor, Rgb};
+use cyb_services::config::expand_path;
+
+pub(super) const DEFAULT_THEME: &str = "default";
+
+/// Where user themes live, one `<name>.toml` each; they shadow bundled ones.
+const USER_THEMES_DIR: &str = "~/.cyb/themes";
+
+const BUNDLED: &[(&str, &str)] = &[
+    ("default", include_str!("../../../assets/themes/default.toml")),
+    ("dracula", include_str!("../../../assets/themes/dracula.toml")),
+    ("gruvbox-dark", include_str!("../../../assets/themes/gruvbox-dark.toml")),
+    ("nord", include_str!("../../../assets/themes/nord.toml")),
+    ("one-light", include_str!("../../../assets/themes/one-light.toml")),
+    ("solarized-dark", include_str!("../../../assets/themes/solarized-dark.toml")),
+    ("solarized-light", include_str!("../../../assets/themes/solarized-light.toml")),
+];
+
+/// ANSI colors 0-7; the bright ones are `light-<name>`, the dim ones `dim-<name>`.
+const ANSI_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+/// Brightness of dim colors a theme doesn't name, relative to the normal ones.
+const DIM_FACTOR: f32 = 0.66;
+
+#[derive(Clone)]
+pub(super) struct Theme {
+    pub name: String,
+    /// ANSI colors 0-15: the normal eight, then the bright eight
+    pub ansi: [Rgb; 16],
+    pub dim: [Rgb; 8],
+    pub foreground: Rgb,
+    pub background: Rgb,
+    pub cursor: Rgb,
+    pub dim_foreground: Rgb,
+    pub light_foreground: Rgb,
+    /// Selected cells are drawn inverted when unset
+    pub selection_background: Option<Rgb>,
+    /// Selected text keeps its own color when unset
+    pub selection_foreground: Option<Rgb>,
+    pub search_match_background: Rgb,
+    pub search_match_foreground: Rgb,
+    pub search_focused_match_background: Rgb,
+    pub search_focused_match_foreground: Rgb,
+    /// Hint labels in keyboard hint mode
+    pub hint_background: Rgb,
+    pub hint_foreground: Rgb,
+    /// Search bar
+    pub bar: Rgb,
+    /// Tab bar, and the active tab in it
+    pub tabs: Rgb,
+    pub tabs_foreground: Rgb,
+    pub tabs_active: Rgb,
+    pub tabs_active_foreground: Rgb,
+    /// Dividers between split panes
+    pub split: Rgb,
+}
+
+impl Theme {
+    /// The bundled default, which names every color.
+    pub fn default_theme() -> Self {
+        parse(DEFAULT_THEME, BUNDLED[0].1, None).expect("bundled default theme names every color")
+    }
+
+    pub fn named(&self, color: NamedColor) -> Rgb {
+        match color {
+            NamedColor::Foreground => self.foreground,
+            NamedColor::Background => self.background,
+            NamedColor::Cursor => self.cursor,
+            NamedColor::BrightForeground => self.light_foreground,
+            NamedColor::DimForeground => self.dim_foreground,
+            NamedColor::DimBlack => self.dim[0],
+            NamedColor::DimRed => self.dim[1],
+            NamedColor::DimGreen => self.dim[2],
+            NamedColor::DimYellow => self.dim[3],
+            NamedColor::DimBlue => self.dim[4],
+            NamedColor::DimMagenta => self.dim[5],
+            NamedColor::DimCyan => self.dim[6],
+            NamedColor::DimWhite => self.dim[7],
+            // Black through BrightWhite are the ANSI indices 0-15
+            ansi => self.ansi[ansi as usize],
+        }
+    }
+}
+
+/// The theme called `name`: `~/.cyb/themes/<name>.toml` if there is one,
+/// otherwise the bundled theme of that name.
+pub(super) fn load(name: &str) -> Result<Theme, String> {
+    let user = user_theme_path(name).and_then(|path| std::fs::read_to_string(path).ok());
+    let source = match user {
+        Some(ref source) => source.as_str(),
+        None => bundled(name).ok_or_else(|| format!("no theme named {:?}", name))?,
+    };
+    let default = Theme::default_theme();
+    parse(name, source, Some(&default)).map_err(|e| format!("theme {:?}: {}", name, e))
+}
+
+/// Every theme by name, with where it comes from: `"user"` or `"bundled"`.
+pub(super) fn available() -> Vec<(String, &'static str)> {
+    let mut themes: Vec<(String, &'static str)> = BUNDLED.iter().map(|(name, _)| (name.to_string(), "bundled")).collect();
+    let user_dir = expand_path(USER_THEMES_DIR).ok();
+    let entries = user_dir.and_then(|dir| std::fs::read_dir(dir).ok()).into_iter().flatten();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().is_none_or(|ext| ext != "toml") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+        match themes.iter_mut().find(|(known, _)| known == name) {
+            Some(theme) => theme.1 = "user",
+            None => themes.push((name.to_string(), "user")),
+        }
+    }
+    themes.sort();
+    themes
+}
+
+fn bundled(name: &str) -> Option<&'static str> {
+    BUNDLED.iter().find(|(bundled, _)| *bundled == name).map(|(_, source)| *source)
+}
+
+fn user_theme_path(name: &str) -> Option<PathBuf> {
+    if name.contains(['/', '\\']) {
+        return None;
+    }
+    Some(expand_path(USER_THEMES_DIR).ok()?.join(format!("{}.toml", name)))
+}
+
+/// A theme from its TOML `source`, with colors it leaves out taken from
+/// `base`. Without a base every color except the cursor, dim, light
+/// foreground and selection ones must be named.
+fn parse(name: &str, source: &str, base: Option<&Theme>) -> Result<Theme, String> {
+    let file: toml::Table = source.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let empty = toml::Table::new();
+    let colors = match file.get("colors") {
+        Some(toml::Value::Table(colors)) => colors,
+        Some(other) => return Err(format!("colors: expected a table, found {}", other.type_str())),
+        None => &empty,
+    };
+
+    let get = |key: &str| -> Result<Option<Rgb>, String> {
+        match colors.get(key) {
+            None => Ok(None),
+            Some(toml::Value::String(hex)) => {
+                parse_hex(hex).map(Some).ok_or_else(|| format!("colors.{}: {:?} is not a #rrggbb color", key, hex))
+            }
+            Some(other) => Err(format!("colors.{}: expected a string, found {}", key, other.type_str())),
+        }
+    };
+    let required = |key: &str, inherited: Option<Rgb>| -> Result<Rgb, String> {
+        get(key)?.or(inherited).ok_or_else(|| format!("colors.{} is missing", key))
+    };
+    let inherit = |field: fn(&Theme) -> Rgb| base.map(field);
+
+    let mut ansi = [Rgb { r: 0, g: 0, b: 0 }; 16];
+    let mut dim = [Rgb { r: 0, g: 0, b: 0 }; 8];
+    for (i, color) in ANSI_NAMES.iter().enumerate() {
+        ansi[i] = required(color, base.map(|base| base.ansi[i]))?;
+        ansi[i + 8] = required(&format!("light-{}", color), base.map(|base| base.ansi[i + 8]))?;
+        dim[i] = get(&format!("dim-{}", color))?.unwrap_or(scale(ansi[i], DIM_FACTOR));
+    }
+
+    let foreground = required("foreground", inherit(|theme| theme.foreground))?;
+    Ok(Theme {
+        name: name.to_string(),
+        ansi,
+        dim,
+        foreground,
+        background: required("background", inherit(|theme| theme.background))?,
+        cursor: get("cursor")?.unwrap_or(foreground),
+        dim_foreground: get("dim-foreground")?.unwrap_or(scale(foreground, DIM_FACTOR)),
+        light_foreground: get("light-foreground")?.unwrap_or(foreground),
+        selection_background: get("selection-background")?,
+        selection_foreground: get("selection-foreground")?,
+        search_match_background: required("search-match-background", inherit(|theme| theme.search_match_background))?,
+        search_match_foreground: required("search-match-foreground", inherit(|theme| theme.search_match_foreground))?,
+        search_focused_match_background: required(
+            "search-focused-match-background",
+            inherit(|theme| theme.search_focused_match_background),
+        )?,
+        search_focused_match_foreground: required(
+            "search-focused-match-foreground",
+            inherit(|theme| theme.search_focused_match_foreground),
+        )?,
+        hint_background: required("hint-background", inherit(|theme| theme.hint_background))?,
+        hint_foreground: required("hint-foreground", inherit(|theme| theme.hint_foreground))?,
+        bar: required("bar", inherit(|theme| theme.bar))?,
+        tabs: required("tabs", inherit(|theme| theme.tabs))?,
+        tabs_foreground: required("tabs-foreground", inherit(|theme| theme.tabs_foreground))?,
+        tabs_active: required("tabs-active", inherit(|theme| theme.tabs_active))?,
+        tabs_active_foreground: required("tabs-active-foreground", inherit(|theme| theme.tabs_active_foreground))?,
+        split: required("split", inherit(|theme| theme.split))?,
+    })
+}
+
+/// `#rrggbb`, or rio's `#rrggbbaa` and `0xrrggbb` with the alpha ignored.
+fn parse_hex(text: &str) -> Option<Rgb> {
+    let hex = text.strip_prefix('#').or_else(|| text.strip_prefix("0x"))?;
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return None;
+    }
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Rgb { r: byte(0)?, g: byte(2)?, b: byte(4)? })
+}
+
+fn scale(rgb: Rgb, factor: f32) -> Rgb {
+    let channel = |c: u8| (c as f32 * factor).round() as u8;
+    Rgb { r: channel(rgb.r), g: channel(rgb.g), b: channel(rgb.b) }
+}
+
+/// The theme in use, and switches asked for from nushell (`cyb theme`),
+/// shared between the terminal and every session's engine.
+#[derive(Clone, Default)]
+pub(super) struct ThemeSwitch(Arc<Mutex<SwitchState>>);
+
+#[derive(Default)]
+struct SwitchState {
+    requested: Option<String>,
+    active: String,
+}
+
+impl ThemeSwitch {
+    /// Ask the terminal to switch to `name` on its next frame.
+    pub fn request(&self, name: String) {
+        self.0.lock().unwrap().requested = Some(name);
+    }
+
+    pub fn take_request(&self) -> Option<String> {
+        self.0.lock().unwrap().requested.take()
+    }
+
+    pub fn active(&self) -> String {
+        self.0.lock().unwrap().active.clone()
+    }
+
+    pub fn set_active(&self, name: &str) {
+        self.0.lock().unwrap().active = name.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(r: u8, g: u8, b: u8) -> Rgb {
+        Rgb { r, g, b }
+    }
+
+    #[test]
+    fn test_bundled_themes_parse() {
+        let default = Theme::default_theme();
+        for (name, source) in BUNDLED {
+            let theme = parse(name, source, Some(&default)).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(theme.name, *name);
+        }
+    }
+
+    #[test]
+    fn test_missing_colors_come_from_default() {
+        let default = Theme::default_theme();
+        let source = "[colors]\nbackground = '#102030ff'\nred = '0xff0000'\nunknown = 'x'\n";
+        let theme = parse("mine", source, Some(&default)).unwrap();
+        assert_eq!(theme.background, rgb(16, 32, 48));
+        assert_eq!(theme.ansi[1], rgb(255, 0, 0));
+        assert_eq!(theme.named(NamedColor::DimRed), rgb(168, 0, 0));
+        assert_eq!(theme.foreground, default.foreground);
+        assert_eq!(theme.ansi[9], default.ansi[9]);
+        assert_eq!(theme.split, default.split);
+        assert_eq!(theme.cursor, theme.foreground);
+
+        let without_base = parse("mine", source, None);
+        assert_eq!(without_base.err().as_deref(), Some("colors.black is missing"));
+        assert!(parse("empty", "", Some(&default)).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_bad_colors() {
+        for hex in ["#12345", "#1234567", "#gg0000", "123456", "#ééé"] {
+            assert_eq!(parse_hex(hex), None, "{}", hex);
+        }
+
+        let default = Theme::default_theme();
+        let error = |source: &str| parse("bad", source, Some(&default)).err();
+        assert_eq!(
+            error("[colors]\nred = 'nope'\n").as_deref(),
+            Some("colors.red: \"nope\" is not a #rrggbb color")
+        );
+        assert_eq!(
+            error("[colors]\nred = 1\n").as_deref(),
+            Some("colors.red: expected a string, found integer")
+        );
+        assert_eq!(
+            error("colors = '#ffffff'\n").as_deref(),
+            Some("colors: expected a table, found string")
+        );
+        assert!(error("[colors\n").is_some());
+    }
+}